@@ -0,0 +1,397 @@
+//! Command line interface, to build programs out of the browser.
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use itertools::Itertools;
+
+use crate::model::{
+    guess_layers, Expansion, KeygroupProgram, LayerOrder, LayerVelocityMode, SampleFile,
+    SampleLibrary,
+};
+use crate::utils::{is_sample_file, wav_file_name, write_folder, TargetFormat};
+
+/// Build an MPC keygroup program from a folder of samples.
+///
+/// The root note of each sample is guessed from its file name.
+#[derive(Debug, Parser)]
+#[command(name = "mpc_valet", version, about)]
+pub struct Args {
+    /// Folder containing the samples (.wav, .aif, .aiff or .flac).
+    pub folder: PathBuf,
+
+    /// Name of the program, defaults to the folder name.
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Output .xpm (or .zip) file, defaults to the program name in the samples folder.
+    ///
+    /// With --expansion, the folder where the expansion folder is written,
+    /// defaults to the samples folder.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Between 0 and 1, prefer pitching the samples down (0) or up (1).
+    #[arg(short, long, default_value_t = 0.5)]
+    pub pitch_preference: f32,
+
+    /// Velocity range assignment of the layers.
+    #[arg(short, long, value_enum, default_value_t = LayerVelocityMode::Automatic)]
+    pub velocity_mode: LayerVelocityMode,
+
+    /// Order of the layers of the samples sharing the same root note.
+    #[arg(short, long, value_enum, default_value_t = LayerOrder::FileName)]
+    pub layer_order: LayerOrder,
+
+    /// Skip the silence under this level (in dB) at the start and end of the samples.
+    #[arg(short, long, allow_negative_numbers = true)]
+    pub trim_silence: Option<f32>,
+
+    /// Duration kept before the start of the samples when trimming, in ms.
+    #[arg(long, default_value_t = 5.0)]
+    pub pre_roll: f32,
+
+    /// Even out the volume of the keygroups across the keyboard.
+    #[arg(long)]
+    pub normalize_volume: bool,
+
+    /// Find a seamless sustain loop in each sample.
+    #[arg(long)]
+    pub find_loops: bool,
+
+    /// Detect the tempo of the loops and make them follow the project tempo.
+    #[arg(long)]
+    pub warp: bool,
+
+    /// Play the percussive samples as one-shots and hold the sustained ones.
+    #[arg(long)]
+    pub detect_articulation: bool,
+
+    /// Write a zip bundle with the program and its samples converted to WAV.
+    #[arg(long)]
+    pub bundle: bool,
+
+    /// Write the program and its samples in an MPC expansion folder with this title.
+    #[arg(long, conflicts_with = "bundle")]
+    pub expansion: Option<String>,
+
+    /// Manufacturer of the expansion.
+    #[arg(long, requires = "expansion", default_value = "")]
+    pub manufacturer: String,
+}
+
+/// Build a keygroup program from a list of sample files.
+///
+/// The root note of the files is guessed, the files whose root note can't be
+/// guessed are returned as an error.
+pub fn build_program(
+    name: &str,
+    files: &mut [SampleFile],
+    pitch_preference: f32,
+    velocity_mode: &LayerVelocityMode,
+    layer_order: LayerOrder,
+) -> std::result::Result<KeygroupProgram, Vec<String>> {
+    let mut unknown_roots = Vec::new();
+    for sample_file in files.iter_mut() {
+        if !sample_file.guess_root() {
+            unknown_roots.push(sample_file.file.clone());
+        }
+    }
+    if !unknown_roots.is_empty() {
+        return Err(unknown_roots);
+    }
+
+    let mut program: KeygroupProgram = guess_layers(files, layer_order).into();
+    program.name = name.to_string();
+    program.guess_ranges(pitch_preference);
+    program.set_velocity_layer_mode(velocity_mode);
+    Ok(program)
+}
+
+/// List the sample files of a folder, sorted by name.
+fn list_samples(folder: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(folder).context(format!("Failed to read {}", folder.display()))?
+    {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(file) = path.file_name().and_then(|f| f.to_str()) {
+            if is_sample_file(file) {
+                files.push(file.to_string());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn build(args: Args) -> Result<bool> {
+    if !(0.0..=1.0).contains(&args.pitch_preference) {
+        bail!("The pitch preference must be between 0 and 1");
+    }
+
+    let name = match args.name {
+        Some(name) => name,
+        None => args
+            .folder
+            .canonicalize()?
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context("Failed to find the folder name, set the program name")?
+            .to_string(),
+    };
+
+    let files = list_samples(&args.folder)?;
+    if files.is_empty() {
+        bail!("No sample file found in {}", args.folder.display());
+    }
+    let mut sample_files = Vec::new();
+    let mut library = SampleLibrary::default();
+    for file in files {
+        let content = std::fs::read(args.folder.join(&file))?;
+        let mut sample_file = SampleFile {
+            file,
+            ..Default::default()
+        };
+        sample_file.read_info(&content);
+        library.insert(sample_file.file.clone(), content);
+        if let Some(issue) = sample_file.issue() {
+            eprintln!("Warning: {}: {}", sample_file.file, issue);
+        }
+        sample_files.push(sample_file);
+    }
+
+    let mut program = match build_program(
+        &name,
+        &mut sample_files,
+        args.pitch_preference,
+        &args.velocity_mode,
+        args.layer_order,
+    ) {
+        Ok(program) => program,
+        Err(unknown_roots) => {
+            eprintln!("The root note of these files could not be guessed:");
+            for file in unknown_roots {
+                eprintln!("  {}", file);
+            }
+            return Ok(false);
+        }
+    };
+    for sample_file in &sample_files {
+        if let Some(mismatch) = sample_file.pitch_mismatch() {
+            eprintln!("Warning: {}: {}", sample_file.file, mismatch);
+        }
+    }
+    // The MPC only reads WAV samples
+    let not_wav = program
+        .layers()
+        .map(|layer| layer.file.as_str())
+        .filter(|file| wav_file_name(file) != *file)
+        .unique()
+        .join(", ");
+    if !args.bundle && args.expansion.is_none() && !not_wav.is_empty() {
+        bail!(
+            "These samples must be converted to WAV, write a bundle with --bundle: {}",
+            not_wav
+        );
+    }
+    if let Some(threshold) = args.trim_silence {
+        program.trim_silence(&library, threshold, args.pre_roll / 1000.0)?;
+    }
+    if args.normalize_volume {
+        program.normalize_volume(&library)?;
+    }
+    if args.find_loops {
+        // The samples are not rewritten, no crossfade is baked
+        program.find_loops(&library, 0.0)?;
+    }
+    if args.detect_articulation {
+        program.detect_articulation(&library)?;
+    }
+    if args.warp {
+        program.detect_tempo(&library)?;
+        program.set_warp(true);
+    }
+
+    if let Some(title) = args.expansion {
+        let mut expansion = Expansion {
+            title,
+            manufacturer: args.manufacturer,
+            ..Default::default()
+        };
+        let mut expansion_library = SampleLibrary::default();
+        expansion.add_program(&program, &library, &mut expansion_library);
+        let output = args.output.unwrap_or(args.folder);
+        write_folder(&output, &expansion.files(&expansion_library)?)?;
+        println!(
+            "{} keygroups written to the {} expansion in {}",
+            program.keygroups.len(),
+            expansion.title,
+            output.display()
+        );
+        return Ok(true);
+    }
+
+    let extension = if args.bundle { "zip" } else { "xpm" };
+    let output = args
+        .output
+        .unwrap_or_else(|| args.folder.join(format!("{}.{}", name, extension)));
+    let file =
+        std::fs::File::create(&output).context(format!("Failed to create {}", output.display()))?;
+    if args.bundle {
+        program.export_bundle(file, &library, &TargetFormat::default())?;
+    } else {
+        program.export(file)?;
+    }
+    println!(
+        "{} keygroups written to {}",
+        program.keygroups.len(),
+        output.display()
+    );
+    Ok(true)
+}
+
+/// Run the command line interface.
+pub fn run() -> ExitCode {
+    match build(Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::model::SampleInfo;
+    use crate::utils::{Loudness, WavFormat, WavMetadata};
+    use rstest::rstest;
+
+    fn sample_files(files: &[&str]) -> Vec<SampleFile> {
+        files
+            .iter()
+            .map(|file| SampleFile {
+                file: file.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn build_program_test() {
+        let mut files = sample_files(&["Piano A2 soft.wav", "Piano A2 loud.wav", "Piano C4.wav"]);
+
+        let program = build_program(
+            "Piano",
+            &mut files,
+            0.5,
+            &LayerVelocityMode::Automatic,
+            LayerOrder::FileName,
+        )
+        .unwrap();
+
+        assert_eq!(program.name, "Piano");
+        assert_eq!(program.keygroups.len(), 2);
+        assert_eq!(program.layer_count(), 2);
+    }
+
+    #[test]
+    fn build_program_unknown_root_test() {
+        let mut files = sample_files(&["Piano A2.wav", "Noise.wav"]);
+
+        let unknown_roots = build_program(
+            "Piano",
+            &mut files,
+            0.5,
+            &LayerVelocityMode::Automatic,
+            LayerOrder::FileName,
+        )
+        .unwrap_err();
+
+        assert_eq!(unknown_roots, vec!["Noise.wav".to_string()]);
+    }
+
+    #[test]
+    fn build_program_metadata_test() {
+        let mut files = sample_files(&["Piano 1.wav", "Piano C4.wav"]);
+        files[0].info = Some(Ok(SampleInfo {
+            format: WavFormat {
+                sample_rate: 44100,
+                channels: 1,
+                bits_per_sample: 16,
+            },
+            frames: 10,
+            metadata: WavMetadata {
+                root: Some(45),
+                tune: 15,
+                keys: Some(40..=50),
+                velocity: Some(1..=100),
+            },
+            pitch: None,
+            loudness: None,
+        }));
+
+        let program = build_program(
+            "Piano",
+            &mut files,
+            0.5,
+            &LayerVelocityMode::Automatic,
+            LayerOrder::FileName,
+        )
+        .unwrap();
+
+        assert_eq!(program.keygroups.len(), 2);
+        assert_eq!(program.keygroups[0].range, 0..=50);
+        assert_eq!(program.keygroups[1].range, 51..=127);
+        let layer = program.keygroups[0].layers[0].as_ref().unwrap();
+        assert_eq!(layer.root, 45);
+        assert_eq!(layer.tune, 15);
+        assert_eq!(layer.velocity, 1..=100);
+    }
+
+    #[rstest]
+    #[case(LayerOrder::FileName, ["Piano A2 a.wav", "Piano A2 b.wav"])]
+    #[case(LayerOrder::Loudness, ["Piano A2 b.wav", "Piano A2 a.wav"])]
+    fn build_program_layer_order_test(#[case] order: LayerOrder, #[case] expected: [&str; 2]) {
+        let mut files = sample_files(&["Piano A2 a.wav", "Piano A2 b.wav"]);
+        for (file, rms) in files.iter_mut().zip([-3.0, -20.0]) {
+            file.info = Some(Ok(SampleInfo {
+                format: WavFormat {
+                    sample_rate: 44100,
+                    channels: 1,
+                    bits_per_sample: 16,
+                },
+                frames: 10,
+                metadata: WavMetadata::default(),
+                pitch: None,
+                loudness: Some(Loudness { peak: 0.0, rms }),
+            }));
+        }
+
+        let program = build_program(
+            "Piano",
+            &mut files,
+            0.5,
+            &LayerVelocityMode::Automatic,
+            order,
+        )
+        .unwrap();
+
+        let layers = program.keygroups[0]
+            .layers
+            .iter()
+            .flatten()
+            .map(|layer| layer.file.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(layers, expected);
+        let soft_layer = program.keygroups[0].layers[0].as_ref().unwrap();
+        assert_eq!(soft_layer.velocity, 0..=63);
+    }
+}
@@ -0,0 +1,113 @@
+use staff::midi::MidiNote;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew_utils::components::drop_down::DropDown;
+
+use crate::components::NoteSelect;
+use crate::model::{ChopMode, ChopSettings};
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub settings: ChopSettings,
+
+    #[prop_or_default]
+    pub on_change: Callback<ChopSettings>,
+}
+
+/// Number input with its unit, calling back with the new settings.
+fn number_field<F>(props: &Props, value: f32, unit: &str, update: F) -> Html
+where
+    F: Fn(&mut ChopSettings, f32) + 'static,
+{
+    let settings = props.settings;
+    let on_change = props.on_change.clone();
+    let oninput = move |e: InputEvent| {
+        let input = e
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+        if let Some(input) = input {
+            let value = input.value_as_number();
+            if value.is_finite() {
+                let mut settings = settings;
+                update(&mut settings, value as f32);
+                on_change.emit(settings);
+            }
+        }
+    };
+    html! {
+        <div class="control">
+            <div class="field has-addons">
+                <div class="control">
+                    <input class="input" type="number" step="any" value={value.to_string()} {oninput} />
+                </div>
+                <div class="control">
+                    <a class="button is-static">{unit.to_string()}</a>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// Settings to chop a loop into slices played across the keys.
+#[function_component(ChopSettingsForm)]
+pub fn chop_settings_form(props: &Props) -> Html {
+    let settings = props.settings;
+
+    let on_change = props.on_change.clone();
+    let on_mode = Callback::from(move |mode| on_change.emit(ChopSettings { mode, ..settings }));
+    let on_change = props.on_change.clone();
+    let on_start_note = Callback::from(move |note: MidiNote| {
+        on_change.emit(ChopSettings {
+            start_note: note.into_byte(),
+            ..settings
+        })
+    });
+
+    let (mode_fields, mode_help) = match settings.mode {
+        ChopMode::Transients => (
+            html! {
+                <>
+                    {number_field(props, settings.sensitivity, "dB rise", |s, v| s.sensitivity = v)}
+                    {number_field(props, settings.min_length, "s minimum", |s, v| s.min_length = v)}
+                </>
+            },
+            "A slice starts where the level rises suddenly, such as a drum hit.",
+        ),
+        ChopMode::Equal => (
+            html! {
+                {number_field(props, settings.count as f32, "slices", |s, v| {
+                    s.count = v.round().clamp(1.0, 128.0) as usize
+                })}
+            },
+            "The loop is split into slices of the same length.",
+        ),
+    };
+
+    html! {
+        <div class="field">
+            <label class="label">{"Chopping"}</label>
+            <div class="field is-grouped">
+                <div class="control">
+                    <div class="select">
+                        <DropDown<ChopMode>
+                            initial={settings.mode}
+                            options={vec![ChopMode::Transients, ChopMode::Equal]}
+                            selection_changed={on_mode}
+                        />
+                    </div>
+                </div>
+                {mode_fields}
+                <div class="control">
+                    <div class="select">
+                        <NoteSelect
+                            value={MidiNote::from_byte(settings.start_note)}
+                            selection_changed={on_start_note}
+                        />
+                    </div>
+                </div>
+            </div>
+            <p class="help">{mode_help}{" Each key from the start note plays one slice, at its original pitch."}</p>
+        </div>
+    }
+}
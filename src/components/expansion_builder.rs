@@ -0,0 +1,240 @@
+use std::io::Cursor;
+
+use crate::{
+    components::{files, Icon, SampleFormatSelect},
+    model::{Expansion, SampleLibrary},
+    utils::TargetFormat,
+};
+use wasm_bindgen::JsCast;
+use web_sys::{File, HtmlInputElement, HtmlTextAreaElement, InputEvent, MouseEvent};
+use yew::{html, Callback, Component, Context, Html, Properties};
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub expansion: Expansion,
+
+    /// Content of the files of the expansion
+    pub library: SampleLibrary,
+
+    #[prop_or_default]
+    pub on_change: Callback<Expansion>,
+
+    /// A cover or preview file was read
+    #[prop_or_default]
+    pub on_file_read: Callback<(String, Vec<u8>)>,
+}
+
+pub enum Msg {
+    TitleChanged(String),
+    ManufacturerChanged(String),
+    VersionChanged(String),
+    DescriptionChanged(String),
+    CoverRead(String, Vec<u8>),
+    PreviewRead(usize, String, Vec<u8>),
+    RemoveProgram(usize),
+    SampleFormatChanged(TargetFormat),
+    Save,
+}
+
+/// Gather programs in an MPC expansion.
+pub struct ExpansionBuilder;
+
+impl Component for ExpansionBuilder {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let mut expansion = ctx.props().expansion.clone();
+        match msg {
+            Msg::TitleChanged(title) => expansion.title = title,
+            Msg::ManufacturerChanged(manufacturer) => expansion.manufacturer = manufacturer,
+            Msg::VersionChanged(version) => expansion.version = version,
+            Msg::DescriptionChanged(description) => expansion.description = description,
+            Msg::CoverRead(file, content) => {
+                ctx.props().on_file_read.emit((file.clone(), content));
+                expansion.cover = Some(file);
+            }
+            Msg::PreviewRead(index, file, content) => {
+                // Each program can have a preview with the same file name
+                let file = format!(
+                    "{}/[Previews]/{}",
+                    expansion.programs[index].program.name, file
+                );
+                ctx.props().on_file_read.emit((file.clone(), content));
+                expansion.programs[index].preview = Some(file);
+            }
+            Msg::RemoveProgram(index) => {
+                expansion.programs.remove(index);
+            }
+            Msg::SampleFormatChanged(format) => expansion.sample_format = format,
+            Msg::Save => {
+                if let Err(e) = Self::export(&expansion, &ctx.props().library) {
+                    log::error!("{:#}", e);
+                }
+                return false;
+            }
+        }
+        ctx.props().on_change.emit(expansion);
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let expansion = &ctx.props().expansion;
+        let programs: Html = expansion
+            .programs
+            .iter()
+            .enumerate()
+            .map(|(index, program)| {
+                let preview = program
+                    .preview
+                    .as_deref()
+                    .and_then(|p| p.rsplit('/').next())
+                    .unwrap_or("No preview");
+                html! {
+                    <tr>
+                        <td>{&program.program.name}</td>
+                        <td>
+                            <div class="file is-small">
+                                <label class="file-label">
+                                    <input class="file-input" type="file" accept=".wav" oninput={Self::on_file_input(ctx, move |file, content| Msg::PreviewRead(index, file, content))} />
+                                    <span class="file-cta">
+                                        <Icon icon="musical-note" class="file-icon" text_after={preview.to_string()} text_class="file-label" />
+                                    </span>
+                                </label>
+                            </div>
+                        </td>
+                        <td>
+                            <button class="button is-small is-danger" onclick={ctx.link().callback(move |_: MouseEvent| Msg::RemoveProgram(index))}>
+                                <Icon icon="trash" />
+                            </button>
+                        </td>
+                    </tr>
+                }
+            })
+            .collect();
+
+        let cover = expansion.cover.as_deref().unwrap_or("No cover");
+
+        html! {
+            <>
+                <p class="title is-4">{"Expansion"}</p>
+                <div class="field">
+                    <label class="label">{"Title"}</label>
+                    <div class="control">
+                        <input class="input" type="text" value={expansion.title.clone()} oninput={Self::on_text_input(ctx, Msg::TitleChanged)} />
+                    </div>
+                </div>
+                <div class="field">
+                    <label class="label">{"Manufacturer"}</label>
+                    <div class="control">
+                        <input class="input" type="text" value={expansion.manufacturer.clone()} oninput={Self::on_text_input(ctx, Msg::ManufacturerChanged)} />
+                    </div>
+                </div>
+                <div class="field">
+                    <label class="label">{"Version"}</label>
+                    <div class="control">
+                        <input class="input" type="text" placeholder="1.0.0" value={expansion.version.clone()} oninput={Self::on_text_input(ctx, Msg::VersionChanged)} />
+                    </div>
+                </div>
+                <div class="field">
+                    <label class="label">{"Description"}</label>
+                    <div class="control">
+                        <textarea class="textarea" value={expansion.description.clone()} oninput={Self::on_description_input(ctx)} />
+                    </div>
+                </div>
+                <div class="field">
+                    <label class="label">{"Cover"}</label>
+                    <div class="file">
+                        <label class="file-label">
+                            <input class="file-input" type="file" accept=".jpg,.jpeg,.png" oninput={Self::on_file_input(ctx, Msg::CoverRead)} />
+                            <span class="file-cta">
+                                <Icon icon="image" class="file-icon" text_after={cover.to_string()} text_class="file-label" />
+                            </span>
+                        </label>
+                    </div>
+                </div>
+                <table class="table is-fullwidth">
+                    <thead>
+                        <tr>
+                            <th>{"Program"}</th>
+                            <th>{"Preview"}</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {programs}
+                    </tbody>
+                </table>
+                <SampleFormatSelect
+                    format={expansion.sample_format}
+                    on_change={ctx.link().callback(Msg::SampleFormatChanged)}
+                />
+                <div class="buttons is-centered">
+                    <button class="button is-link" onclick={ctx.link().callback(|_: MouseEvent| Msg::Save)}>
+                        <Icon icon="save" text_after="Save Expansion" />
+                    </button>
+                </div>
+            </>
+        }
+    }
+}
+
+impl ExpansionBuilder {
+    fn export(expansion: &Expansion, library: &SampleLibrary) -> anyhow::Result<()> {
+        let mut file_content = Cursor::new(Vec::<u8>::new());
+        expansion.export(&mut file_content, library)?;
+        files::download(&format!("{}.zip", expansion.title), file_content.get_ref())
+    }
+
+    fn on_text_input<F>(ctx: &Context<Self>, msg: F) -> Callback<InputEvent>
+    where
+        F: Fn(String) -> Msg + 'static,
+    {
+        ctx.link().batch_callback(move |e: InputEvent| {
+            let input: HtmlInputElement = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())?;
+            Some(msg(input.value()))
+        })
+    }
+
+    fn on_description_input(ctx: &Context<Self>) -> Callback<InputEvent> {
+        ctx.link().batch_callback(|e: InputEvent| {
+            let input: HtmlTextAreaElement = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlTextAreaElement>().ok())?;
+            Some(Msg::DescriptionChanged(input.value()))
+        })
+    }
+
+    /// Read the content of the selected file.
+    fn on_file_input<F>(ctx: &Context<Self>, msg: F) -> Callback<InputEvent>
+    where
+        F: Fn(String, Vec<u8>) -> Msg + Clone + 'static,
+    {
+        let link = ctx.link().clone();
+        Callback::from(move |e: InputEvent| {
+            let file: Option<File> = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                let msg = msg.clone();
+                link.send_future_batch(async move {
+                    match files::read_bytes(file.clone()).await {
+                        Ok(content) => vec![msg(file.name(), content)],
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            vec![]
+                        }
+                    }
+                });
+            }
+        })
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{bail, Context, Result};
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, File, HtmlElement, Url};
+
+/// Read the content of a file selected by the user as text.
+pub async fn read_text(file: File) -> Result<String> {
+    let text = JsFuture::from(file.text())
+        .await
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context(format!("Failed to read {}", file.name()))?;
+    text.as_string()
+        .context(format!("{} is not a text file", file.name()))
+}
+
+/// Read the content of a file selected by the user.
+pub async fn read_bytes(file: File) -> Result<Vec<u8>> {
+    let buffer = JsFuture::from(file.array_buffer())
+        .await
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context(format!("Failed to read {}", file.name()))?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+/// Make the browser download a file.
+pub fn download(file_name: &str, file_content: &[u8]) -> Result<()> {
+    let parts = Array::of1(&Uint8Array::from(file_content));
+    let blob = Blob::new_with_u8_array_sequence(&parts)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to create the file content")?;
+    let url = Url::create_object_url_with_blob(&blob)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to create the file URL")?;
+
+    let window = web_sys::window().context("Failed to get the browser window")?;
+    let document = window
+        .document()
+        .context("Failed to get the window document")?;
+    let body = document.body().context("Failed to get the document body")?;
+    let element = document
+        .create_element("a")
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to insert a link in the document")?;
+    let element = element
+        .dyn_into::<HtmlElement>()
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to convert the element to an HTML element")?;
+    element
+        .set_attribute("href", &url)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to set the element destination")?;
+    element
+        .set_attribute("download", file_name)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to create the download file name")?;
+    body.append_child(&element)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to insert the element in the document")?;
+    element.click();
+    body.remove_child(&element)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to remove the element from the document")?;
+    Url::revoke_object_url(&url)
+        .or_else(|e| bail!(e.as_string().unwrap_or_default()))
+        .context("Failed to release the file URL")?;
+    Ok(())
+}
@@ -1,17 +1,28 @@
 use crate::components::*;
-use crate::model::{KeygroupProgram, LayerFile, SampleFile};
+use crate::model::{
+    DrumProgram, Expansion, ImportWarning, KeygroupProgram, LayerFile, ProgramType, SampleFile,
+    SampleLibrary,
+};
 use gloo_storage::LocalStorage;
 use gloo_storage::Storage;
 use log::debug;
 use serde::Deserialize;
 use serde::Serialize;
 use yew::prelude::*;
+use yew_utils::components::drop_down::DropDown;
 
 pub enum Msg {
     Reset,
+    ProgramTypeChanged(ProgramType),
     AddSamplesDone(Vec<SampleFile>),
+    SamplesRead(SampleLibrary),
     SelectLayersDone(Vec<LayerFile>),
+    ProgramOpened(KeygroupProgram, SampleLibrary, Vec<ImportWarning>),
     FineTuningDone(KeygroupProgram),
+    AssignPadsDone(DrumProgram),
+    AddToExpansion(KeygroupProgram),
+    ExpansionChanged(Expansion),
+    ExpansionFileRead(String, Vec<u8>),
 }
 
 /// Wizard steps
@@ -23,11 +34,17 @@ pub enum Step {
     /// The file roots were selected
     SelectLayers(Vec<SampleFile>),
 
-    /// Fine Tuning of the program
-    FineTuning(Vec<LayerFile>),
+    /// Fine Tuning of the program, with the warnings of its import
+    FineTuning(KeygroupProgram, Vec<ImportWarning>),
 
     /// The program is ready to be saved
     Done(KeygroupProgram),
+
+    /// The file roots were selected, for a drum program
+    AssignPads(Vec<SampleFile>),
+
+    /// The drum program is ready to be saved
+    DrumDone(DrumProgram),
 }
 
 /// Main component: Create the keygroup programs.
@@ -35,12 +52,32 @@ pub enum Step {
 pub struct KeygroupCreator {
     /// Steps of the keygroup creation
     step: Step,
+
+    /// Kind of program being created
+    #[serde(default)]
+    program_type: ProgramType,
+
+    /// Content of the sample files, only kept in memory
+    #[serde(skip)]
+    library: SampleLibrary,
+
+    /// Expansion gathering the created programs
+    #[serde(default)]
+    expansion: Expansion,
+
+    /// Content of the files of the expansion, only kept in memory
+    #[serde(skip)]
+    expansion_library: SampleLibrary,
 }
 
 impl Default for KeygroupCreator {
     fn default() -> Self {
         Self {
             step: Step::AddSamples,
+            program_type: ProgramType::default(),
+            library: SampleLibrary::default(),
+            expansion: Expansion::default(),
+            expansion_library: SampleLibrary::default(),
         }
     }
 }
@@ -57,22 +94,63 @@ impl Component for KeygroupCreator {
     fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
         let redraw = match msg {
             Msg::Reset => {
-                *self = Self::default();
+                // The expansion gathers the programs of several sessions
+                *self = Self {
+                    expansion: std::mem::take(&mut self.expansion),
+                    expansion_library: std::mem::take(&mut self.expansion_library),
+                    ..Self::default()
+                };
                 LocalStorage::clear();
                 true
             }
+            Msg::ProgramTypeChanged(program_type) => {
+                self.program_type = program_type;
+                true
+            }
             Msg::AddSamplesDone(samples) => {
-                self.step = Step::SelectLayers(samples);
+                self.step = match self.program_type {
+                    ProgramType::Keygroup => Step::SelectLayers(samples),
+                    ProgramType::Drum => Step::AssignPads(samples),
+                };
                 true
             }
+            Msg::SamplesRead(library) => {
+                self.library.extend(library);
+                false
+            }
             Msg::SelectLayersDone(layer_files) => {
-                self.step = Step::FineTuning(layer_files);
+                self.step = Step::FineTuning(layer_files.into(), Vec::new());
+                true
+            }
+            Msg::ProgramOpened(program, library, warnings) => {
+                // Only keygroup programs can be opened
+                self.program_type = ProgramType::Keygroup;
+                self.step = Step::FineTuning(program, warnings);
+                // Keep the samples already added, the program may use them
+                self.library.extend(library);
                 true
             }
             Msg::FineTuningDone(program) => {
                 self.step = Step::Done(program);
                 true
             }
+            Msg::AssignPadsDone(program) => {
+                self.step = Step::DrumDone(program);
+                true
+            }
+            Msg::AddToExpansion(program) => {
+                self.expansion
+                    .add_program(&program, &self.library, &mut self.expansion_library);
+                true
+            }
+            Msg::ExpansionChanged(expansion) => {
+                self.expansion = expansion;
+                true
+            }
+            Msg::ExpansionFileRead(file, content) => {
+                self.expansion_library.insert(file, content);
+                false
+            }
         };
 
         LocalStorage::set("keygroup_creator", self).unwrap_or_else(|e| {
@@ -91,10 +169,30 @@ impl Component for KeygroupCreator {
         match self.step {
             Step::AddSamples => add_samples_class.push("is-active"),
             Step::SelectLayers(_) => select_layers_class.push("is-active"),
-            Step::FineTuning(_) => fine_tuning_class.push("is-active"),
+            Step::FineTuning(..) => fine_tuning_class.push("is-active"),
             Step::Done(_) => done_class.push("is-active"),
+            Step::AssignPads(_) => select_layers_class.push("is-active"),
+            Step::DrumDone(_) => done_class.push("is-active"),
         }
 
+        // Drum programs have no keyboard to fine tune
+        let (select_layers_title, fine_tuning_step) = match self.program_type {
+            ProgramType::Keygroup => (
+                "Select Layers",
+                html! {
+                    <li class={fine_tuning_class}>
+                        <span class="steps-marker">
+                            <Icon icon="options" />
+                        </span>
+                        <div class="steps-content">
+                            <p class="is-size-4">{"Fine Tuning"}</p>
+                        </div>
+                    </li>
+                },
+            ),
+            ProgramType::Drum => ("Assign Pads", html! {}),
+        };
+
         debug!("Redrawing main view");
         html! {
         <div class="container">
@@ -113,17 +211,10 @@ impl Component for KeygroupCreator {
                             <Icon icon="layers" />
                         </span>
                         <div class="steps-content">
-                            <p class="is-size-4">{"Select Layers"}</p>
-                        </div>
-                    </li>
-                    <li class={fine_tuning_class}>
-                        <span class="steps-marker">
-                            <Icon icon="options" />
-                        </span>
-                        <div class="steps-content">
-                            <p class="is-size-4">{"Fine Tuning"}</p>
+                            <p class="is-size-4">{select_layers_title}</p>
                         </div>
                     </li>
+                    {fine_tuning_step}
                     <li class={done_class}>
                         <span class="steps-marker">
                             <Icon icon="checkmark" />
@@ -135,6 +226,7 @@ impl Component for KeygroupCreator {
                 </ul>
                 {self.view_current_step(ctx)}
             </div>
+            {self.view_expansion(ctx)}
             <div class="buttons is-centered">
                 <button class="button is-danger is-large" onclick={ctx.link().callback(|_| Msg::Reset)}>
                     <Icon icon="trash" text_after="Reset" />
@@ -149,9 +241,29 @@ impl KeygroupCreator {
     fn view_current_step(&self, ctx: &Context<Self>) -> Html {
         match &self.step {
             Step::AddSamples => html! {
-                <StepAddSamples
-                    on_next={ctx.link().callback(Msg::AddSamplesDone)}
-                />
+                <>
+                    <div class="field is-horizontal">
+                        <div class="field-label is-normal">
+                            <label class="label">{"Program Type"}</label>
+                        </div>
+                        <div class="field-body">
+                            <div class="control">
+                                <div class="select">
+                                    <DropDown<ProgramType>
+                                        initial={self.program_type}
+                                        options={vec![ProgramType::Keygroup, ProgramType::Drum]}
+                                        selection_changed={ctx.link().callback(Msg::ProgramTypeChanged)}
+                                    />
+                                </div>
+                            </div>
+                        </div>
+                    </div>
+                    <StepAddSamples
+                        on_next={ctx.link().callback(Msg::AddSamplesDone)}
+                        on_samples_read={ctx.link().callback(Msg::SamplesRead)}
+                        on_program_opened={ctx.link().callback(|(program, library, warnings)| Msg::ProgramOpened(program, library, warnings))}
+                    />
+                </>
             },
             Step::SelectLayers(files) => {
                 html! {
@@ -161,10 +273,12 @@ impl KeygroupCreator {
                     />
                 }
             }
-            Step::FineTuning(layer_files) => {
+            Step::FineTuning(program, warnings) => {
                 html! {
                     <StepFineTuning
-                        layer_files = {layer_files.clone()}
+                        program = {program.clone()}
+                        warnings = {warnings.clone()}
+                        library = {self.library.clone()}
                         on_next = {ctx.link().callback(Msg::FineTuningDone)}
                     />
                 }
@@ -172,8 +286,39 @@ impl KeygroupCreator {
             Step::Done(program) => html! {
                 <StepDone
                     program = {program.clone()}
+                    library = {self.library.clone()}
+                    on_add_to_expansion = {ctx.link().callback(Msg::AddToExpansion)}
                 />
             },
+            Step::AssignPads(files) => html! {
+                <StepAssignPads
+                    files={files.clone()}
+                    on_next={ctx.link().callback(Msg::AssignPadsDone)}
+                />
+            },
+            Step::DrumDone(program) => html! {
+                <StepDrumDone
+                    program = {program.clone()}
+                    library = {self.library.clone()}
+                />
+            },
+        }
+    }
+
+    fn view_expansion(&self, ctx: &Context<Self>) -> Html {
+        if self.expansion.programs.is_empty() {
+            return html! {};
+        }
+
+        html! {
+            <div class="box">
+                <ExpansionBuilder
+                    expansion = {self.expansion.clone()}
+                    library = {self.expansion_library.clone()}
+                    on_change = {ctx.link().callback(Msg::ExpansionChanged)}
+                    on_file_read = {ctx.link().callback(|(file, content)| Msg::ExpansionFileRead(file, content))}
+                />
+            </div>
         }
     }
 }
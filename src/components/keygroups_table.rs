@@ -1,6 +1,8 @@
 use crate::model::Keygroup;
 
 use staff::midi::MidiNote;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -10,6 +12,12 @@ pub struct Props {
 
     #[prop_or_default]
     pub on_hovered_kg: Callback<Option<usize>>,
+
+    /// Called with the keygroup index, the layer index and the fine tuning in cents.
+    ///
+    /// The fine tuning is only editable when set.
+    #[prop_or_default]
+    pub on_tune_changed: Option<Callback<(usize, usize, i8)>>,
 }
 
 #[function_component(KeygroupsTable)]
@@ -30,11 +38,49 @@ pub fn keygroups_table(props: &Props) -> Html {
             let layer_cells: Html = kg
                 .layers
                 .iter()
-                .map(|layer| {
+                .enumerate()
+                .map(|(layer_index, layer)| {
                     if let Some(layer) = layer {
+                        let tune = match &props.on_tune_changed {
+                            Some(on_tune_changed) => {
+                                let on_tune_changed = on_tune_changed.clone();
+                                let oninput = move |e: InputEvent| {
+                                    let input = e
+                                        .target()
+                                        .and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+                                    if let Some(input) = input {
+                                        let tune = input.value_as_number();
+                                        if tune.is_finite() {
+                                            let tune = tune.round().clamp(-99.0, 99.0) as i8;
+                                            on_tune_changed.emit((index, layer_index, tune));
+                                        }
+                                    }
+                                };
+                                html! {
+                                    <div class="field has-addons">
+                                        <div class="control">
+                                            <input
+                                                class="input is-small"
+                                                type="number"
+                                                min="-99"
+                                                max="99"
+                                                title="Fine tuning"
+                                                value={layer.tune.to_string()}
+                                                {oninput}
+                                            />
+                                        </div>
+                                        <div class="control">
+                                            <a class="button is-small is-static">{"cents"}</a>
+                                        </div>
+                                    </div>
+                                }
+                            }
+                            None => html! {},
+                        };
                         return html! {
                             <td>
                                 {layer.file.to_string()}
+                                {tune}
                             </td>
                         };
                     }
@@ -1,23 +1,43 @@
+// yew 0.19's html! macro expands properties into statements that trip these lints.
+#![allow(clippy::unnecessary_operation, clippy::let_unit_value)]
+
 mod app;
+mod chop_settings_form;
+mod expansion_builder;
+mod files;
 mod icon;
 mod keyboard;
 mod keygroup_creator;
 mod keygroups_table;
 mod layer_select;
 mod note_select;
+mod pad_select;
+mod sample_format_select;
+mod sampling_plan_form;
+mod slice_settings_form;
 mod step_add_samples;
+mod step_assign_pads;
 mod step_done;
+mod step_drum_done;
 mod step_fine_tuning;
 mod step_select_layers;
 
-pub use app::*;
-pub use icon::*;
-pub use keyboard::*;
-pub use keygroup_creator::*;
-pub use keygroups_table::*;
+pub use app::App;
+pub use chop_settings_form::ChopSettingsForm;
+pub use expansion_builder::ExpansionBuilder;
+pub use icon::Icon;
+pub use keyboard::Keyboard;
+pub use keygroup_creator::KeygroupCreator;
+pub use keygroups_table::KeygroupsTable;
 pub use layer_select::*;
 pub use note_select::*;
-pub use step_add_samples::*;
-pub use step_done::*;
-pub use step_fine_tuning::*;
-pub use step_select_layers::*;
+pub use pad_select::PadSelect;
+pub use sample_format_select::SampleFormatSelect;
+pub use sampling_plan_form::SamplingPlanForm;
+pub use slice_settings_form::SliceSettingsForm;
+pub use step_add_samples::StepAddSamples;
+pub use step_assign_pads::StepAssignPads;
+pub use step_done::StepDone;
+pub use step_drum_done::StepDrumDone;
+pub use step_fine_tuning::StepFineTuning;
+pub use step_select_layers::StepSelectLayers;
@@ -41,7 +41,7 @@ impl Component for NoteSelect {
             }
         };
 
-        let options: Html = (0..=127_u8).into_iter()
+        let options: Html = (0..=127_u8)
             .map(|byte| {
                 let note = MidiNote::from_byte(byte);
                 html! {
@@ -0,0 +1,66 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Event, HtmlSelectElement};
+use yew::{html, Callback, Component, Html, NodeRef, Properties};
+
+use crate::model::pad_name;
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct PadSelectProps {
+    pub value: u8,
+    pub selection_changed: Callback<u8>,
+}
+
+/// Single drum pad selector drop down.
+pub struct PadSelect {
+    node_ref: NodeRef,
+}
+
+impl Component for PadSelect {
+    type Message = ();
+
+    type Properties = PadSelectProps;
+
+    fn create(_ctx: &yew::Context<Self>) -> Self {
+        Self {
+            node_ref: NodeRef::default(),
+        }
+    }
+
+    fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
+        let PadSelectProps {
+            value,
+            selection_changed,
+        } = ctx.props().clone();
+
+        let on_change = move |e: Event| {
+            if let Some(target) = e.target() {
+                if let Ok(select) = target.dyn_into::<HtmlSelectElement>() {
+                    selection_changed.emit(select.selected_index() as u8);
+                }
+            }
+        };
+
+        let options: Html = (0..=127_u8)
+            .map(|pad| {
+                html! {
+                    <option value={pad_name(pad)} selected={pad == value}>{pad_name(pad)}</option>
+                }
+            })
+            .collect();
+
+        html! {
+            <div class="select" onchange={on_change}>
+                <select ref={self.node_ref.clone()}>
+                    {options}
+                </select>
+            </div>
+        }
+    }
+
+    fn changed(&mut self, ctx: &yew::Context<Self>) -> bool {
+        if let Some(elt) = self.node_ref.cast::<HtmlSelectElement>() {
+            elt.set_value(&pad_name(ctx.props().value));
+        }
+        true
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Display;
+
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew_utils::components::drop_down::DropDown;
+
+use crate::utils::TargetFormat;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub format: TargetFormat,
+
+    #[prop_or_default]
+    pub on_change: Callback<TargetFormat>,
+}
+
+/// A value of the target format, or keeping the value of each sample.
+#[derive(Clone, PartialEq)]
+struct Choice<T> {
+    value: Option<T>,
+    label: String,
+}
+
+impl<T> Choice<T> {
+    fn new(value: Option<T>, label: &str) -> Self {
+        Self {
+            value,
+            label: label.to_string(),
+        }
+    }
+}
+
+impl<T> Display for Choice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+/// Pick the first choice with the given value.
+fn initial<T: Clone + PartialEq>(choices: &[Choice<T>], value: Option<T>) -> Choice<T> {
+    choices
+        .iter()
+        .find(|choice| choice.value == value)
+        .unwrap_or(&choices[0])
+        .clone()
+}
+
+/// Selector of the format the samples are converted to.
+#[function_component(SampleFormatSelect)]
+pub fn sample_format_select(props: &Props) -> Html {
+    let format = props.format;
+
+    let sample_rates = vec![
+        Choice::new(None, "Original Rate"),
+        Choice::new(Some(44100), "44.1 kHz"),
+        Choice::new(Some(48000), "48 kHz"),
+    ];
+    let bit_depths = vec![
+        Choice::new(None, "Original Depth"),
+        Choice::new(Some(16), "16 bits"),
+        Choice::new(Some(24), "24 bits"),
+    ];
+    let channels = vec![
+        Choice::new(None, "Original Channels"),
+        Choice::new(Some(1), "Mono"),
+        Choice::new(Some(2), "Stereo"),
+    ];
+
+    let on_change = props.on_change.clone();
+    let on_sample_rate = Callback::from(move |choice: Choice<u32>| {
+        on_change.emit(TargetFormat {
+            sample_rate: choice.value,
+            ..format
+        })
+    });
+    let on_change = props.on_change.clone();
+    let on_bit_depth = Callback::from(move |choice: Choice<u16>| {
+        on_change.emit(TargetFormat {
+            bits_per_sample: choice.value,
+            ..format
+        })
+    });
+    let on_change = props.on_change.clone();
+    let on_channels = Callback::from(move |choice: Choice<u16>| {
+        on_change.emit(TargetFormat {
+            channels: choice.value,
+            ..format
+        })
+    });
+    let on_change = props.on_change.clone();
+    let on_dither = move |e: Event| {
+        let input = e
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+        if let Some(input) = input {
+            on_change.emit(TargetFormat {
+                dither: input.checked(),
+                ..format
+            });
+        }
+    };
+
+    html! {
+        <div class="field">
+            <label class="label">{"Sample Format"}</label>
+            <div class="field is-grouped">
+                <div class="control">
+                    <div class="select">
+                        <DropDown<Choice<u32>>
+                            initial={initial(&sample_rates, format.sample_rate)}
+                            options={sample_rates}
+                            selection_changed={on_sample_rate}
+                        />
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select">
+                        <DropDown<Choice<u16>>
+                            initial={initial(&bit_depths, format.bits_per_sample)}
+                            options={bit_depths}
+                            selection_changed={on_bit_depth}
+                        />
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select">
+                        <DropDown<Choice<u16>>
+                            initial={initial(&channels, format.channels)}
+                            options={channels}
+                            selection_changed={on_channels}
+                        />
+                    </div>
+                </div>
+                <div class="control">
+                    <label class="checkbox" title="Add a low level noise to hide the distortion of the conversion">
+                        <input type="checkbox" checked={format.dither} onchange={on_dither} />
+                        {" Dither"}
+                    </label>
+                </div>
+            </div>
+            <p class="help">{"The samples of the bundles are converted to this format."}</p>
+        </div>
+    }
+}
@@ -0,0 +1,177 @@
+use staff::midi::MidiNote;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::components::{files, Icon, NoteSelect};
+use crate::model::SamplingPlan;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub plan: SamplingPlan,
+
+    #[prop_or_default]
+    pub on_change: Callback<SamplingPlan>,
+}
+
+/// Input value of an event, calling back with the updated plan.
+fn on_input<F>(props: &Props, update: F) -> Callback<Event>
+where
+    F: Fn(&mut SamplingPlan, &HtmlInputElement) + 'static,
+{
+    let plan = props.plan.clone();
+    let on_change = props.on_change.clone();
+    Callback::from(move |e: Event| {
+        let input = e
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+        if let Some(input) = input {
+            let mut plan = plan.clone();
+            update(&mut plan, &input);
+            on_change.emit(plan);
+        }
+    })
+}
+
+/// Number input with its unit.
+fn number_field<F>(props: &Props, value: f32, unit: &str, update: F) -> Html
+where
+    F: Fn(&mut SamplingPlan, f32) + 'static,
+{
+    let onchange = on_input(props, move |plan, input| {
+        let value = input.value_as_number();
+        if value.is_finite() {
+            update(plan, value as f32);
+        }
+    });
+    html! {
+        <div class="control">
+            <div class="field has-addons">
+                <div class="control">
+                    <input class="input" type="number" min="0" step="any" value={value.to_string()} {onchange} />
+                </div>
+                <div class="control">
+                    <a class="button is-static">{unit.to_string()}</a>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// Note selector, calling back with the updated plan.
+fn note_field<F>(props: &Props, value: u8, update: F) -> Html
+where
+    F: Fn(&mut SamplingPlan, u8) + 'static,
+{
+    let plan = props.plan.clone();
+    let on_change = props.on_change.clone();
+    let selection_changed = Callback::from(move |note: MidiNote| {
+        let mut plan = plan.clone();
+        update(&mut plan, note.into_byte());
+        on_change.emit(plan);
+    });
+    html! {
+        <div class="control">
+            <div class="select">
+                <NoteSelect value={MidiNote::from_byte(value)} {selection_changed} />
+            </div>
+        </div>
+    }
+}
+
+fn save_midi(plan: &SamplingPlan) -> anyhow::Result<()> {
+    let mut file_content = Vec::<u8>::new();
+    plan.export_midi(&mut file_content)?;
+    files::download(&format!("{}.mid", plan.name), &file_content)
+}
+
+fn save_manifest(plan: &SamplingPlan) -> anyhow::Result<()> {
+    let mut file_content = Vec::<u8>::new();
+    plan.export_manifest(&mut file_content)?;
+    files::download(&format!("{}.json", plan.name), &file_content)
+}
+
+/// Plan to record a hardware instrument, saved as a MIDI file and a manifest.
+#[function_component(SamplingPlanForm)]
+pub fn sampling_plan_form(props: &Props) -> Html {
+    let plan = &props.plan;
+
+    let on_name = on_input(props, |plan, input| plan.name = input.value());
+    let on_velocities = on_input(props, |plan, input| {
+        plan.velocities = input
+            .value()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(|velocity| velocity.parse().ok())
+            .collect();
+    });
+    let velocities = plan
+        .velocities
+        .iter()
+        .map(|velocity| velocity.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let on_save_midi = {
+        let plan = plan.clone();
+        Callback::from(move |_| {
+            if let Err(e) = save_midi(&plan) {
+                log::error!("{:#}", e);
+            }
+        })
+    };
+    let on_save_manifest = {
+        let plan = plan.clone();
+        Callback::from(move |_| {
+            if let Err(e) = save_manifest(&plan) {
+                log::error!("{:#}", e);
+            }
+        })
+    };
+
+    html! {
+        <>
+            <div class="field">
+                <label class="label">{"Sampling Plan"}</label>
+                <div class="field is-grouped">
+                    <div class="control">
+                        <input class="input" type="text" placeholder="Plan Name" value={plan.name.clone()} onchange={on_name} />
+                    </div>
+                    {note_field(props, *plan.keys.start(), |plan, note| plan.keys = note..=*plan.keys.end())}
+                    {note_field(props, *plan.keys.end(), |plan, note| plan.keys = *plan.keys.start()..=note)}
+                    {number_field(props, plan.interval as f32, "semitones", |plan, v| {
+                        plan.interval = v.round().clamp(1.0, 127.0) as u8
+                    })}
+                </div>
+                <div class="field is-grouped">
+                    <div class="control">
+                        <div class="field has-addons">
+                            <div class="control">
+                                <input class="input" type="text" title="Velocities" value={velocities} onchange={on_velocities} />
+                            </div>
+                            <div class="control">
+                                <a class="button is-static">{"velocities"}</a>
+                            </div>
+                        </div>
+                    </div>
+                    {number_field(props, plan.note_length, "s per note", |plan, v| plan.note_length = v)}
+                    {number_field(props, plan.release, "s of release", |plan, v| plan.release = v)}
+                </div>
+                <p class="help">
+                    {format!(
+                        "Play the MIDI file to the instrument and record it. {} notes, {:.0} s of recording.",
+                        plan.notes().len(),
+                        plan.duration()
+                    )}
+                </p>
+            </div>
+            <div class="buttons">
+                <button class="button" onclick={on_save_midi}>
+                    <Icon icon="musical-notes" text_after="Save MIDI" />
+                </button>
+                <button class="button" onclick={on_save_manifest}>
+                    <Icon icon="save" text_after="Save Plan" />
+                </button>
+            </div>
+        </>
+    }
+}
@@ -0,0 +1,151 @@
+use staff::midi::MidiNote;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew_utils::components::drop_down::DropDown;
+
+use crate::components::NoteSelect;
+use crate::model::{SliceMode, SliceRoots, SliceSettings};
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub settings: SliceSettings,
+
+    #[prop_or_default]
+    pub on_change: Callback<SliceSettings>,
+}
+
+/// Number input with its unit, calling back with the new settings.
+fn number_field<F>(props: &Props, value: f32, unit: &str, update: F) -> Html
+where
+    F: Fn(&mut SliceSettings, f32) + 'static,
+{
+    let settings = props.settings;
+    let on_change = props.on_change.clone();
+    let oninput = move |e: InputEvent| {
+        let input = e
+            .target()
+            .and_then(|t| t.dyn_into::<HtmlInputElement>().ok());
+        if let Some(input) = input {
+            let value = input.value_as_number();
+            if value.is_finite() {
+                let mut settings = settings;
+                update(&mut settings, value as f32);
+                on_change.emit(settings);
+            }
+        }
+    };
+    html! {
+        <div class="control">
+            <div class="field has-addons">
+                <div class="control">
+                    <input class="input" type="number" step="any" value={value.to_string()} {oninput} />
+                </div>
+                <div class="control">
+                    <a class="button is-static">{unit.to_string()}</a>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// Settings to cut a recording of sequential notes into samples.
+#[function_component(SliceSettingsForm)]
+pub fn slice_settings_form(props: &Props) -> Html {
+    let settings = props.settings;
+
+    let on_change = props.on_change.clone();
+    let on_mode = Callback::from(move |mode| on_change.emit(SliceSettings { mode, ..settings }));
+    let on_change = props.on_change.clone();
+    let on_roots = Callback::from(move |roots| on_change.emit(SliceSettings { roots, ..settings }));
+    let on_change = props.on_change.clone();
+    let on_start_note = Callback::from(move |note: MidiNote| {
+        on_change.emit(SliceSettings {
+            start_note: note.into_byte(),
+            ..settings
+        })
+    });
+
+    let (mode_fields, mode_help) = match settings.mode {
+        SliceMode::Detect => (
+            html! {
+                <>
+                    {number_field(props, settings.threshold, "dB", |s, v| s.threshold = v)}
+                    {number_field(props, settings.min_silence, "s of silence", |s, v| s.min_silence = v)}
+                </>
+            },
+            "A note starts above the threshold, and ends after a silence under the threshold.",
+        ),
+        SliceMode::Fixed => (
+            html! {
+                <>
+                    {number_field(props, settings.length, "s per note", |s, v| s.length = v)}
+                    {number_field(props, settings.gap, "s of gap", |s, v| s.gap = v)}
+                </>
+            },
+            "The notes have the same length and are separated by the same gap.",
+        ),
+    };
+
+    let (roots_fields, roots_help) = match settings.roots {
+        SliceRoots::Sequence => (
+            html! {
+                <>
+                    <div class="control">
+                        <div class="select">
+                            <NoteSelect
+                                value={MidiNote::from_byte(settings.start_note)}
+                                selection_changed={on_start_note}
+                            />
+                        </div>
+                    </div>
+                    {number_field(props, settings.interval as f32, "semitones", |s, v| {
+                        s.interval = v.round().clamp(0.0, 127.0) as u8
+                    })}
+                </>
+            },
+            "The notes are played from the start note, with the same interval.",
+        ),
+        SliceRoots::Pitch => (
+            html! {},
+            "The root note of each slice is detected from its pitch.",
+        ),
+    };
+
+    html! {
+        <>
+            <div class="field">
+                <label class="label">{"Slicing"}</label>
+                <div class="field is-grouped">
+                    <div class="control">
+                        <div class="select">
+                            <DropDown<SliceMode>
+                                initial={settings.mode}
+                                options={vec![SliceMode::Detect, SliceMode::Fixed]}
+                                selection_changed={on_mode}
+                            />
+                        </div>
+                    </div>
+                    {mode_fields}
+                </div>
+                <p class="help">{mode_help}</p>
+            </div>
+            <div class="field">
+                <label class="label">{"Root Notes"}</label>
+                <div class="field is-grouped">
+                    <div class="control">
+                        <div class="select">
+                            <DropDown<SliceRoots>
+                                initial={settings.roots}
+                                options={vec![SliceRoots::Sequence, SliceRoots::Pitch]}
+                                selection_changed={on_roots}
+                            />
+                        </div>
+                    </div>
+                    {roots_fields}
+                </div>
+                <p class="help">{roots_help}</p>
+            </div>
+        </>
+    }
+}
@@ -1,21 +1,46 @@
-use crate::components::{Icon, NoteSelect};
+use crate::components::{
+    files, ChopSettingsForm, Icon, NoteSelect, SamplingPlanForm, SliceSettingsForm,
+};
 use gloo_storage::{LocalStorage, Storage};
 use serde::{Deserialize, Serialize};
 use staff::midi::MidiNote;
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlInputElement, InputEvent};
+use web_sys::{Event, File, HtmlInputElement, InputEvent};
 use yew::{html, Callback, Component, Context, Html, Properties};
+use yew_utils::components::drop_down::DropDown;
 
-use crate::model::SampleFile;
+use crate::model::{
+    ChopSettings, ImportWarning, KeygroupProgram, RootSource, SampleFile, SampleLibrary,
+    SamplingPlan, SliceSettings,
+};
+use crate::utils::sf2_presets;
 
 #[derive(Properties, PartialEq)]
 pub struct Props {
     #[prop_or_default]
     pub on_next: Callback<Vec<SampleFile>>,
+
+    /// Content of the added sample files, read in the background
+    #[prop_or_default]
+    pub on_samples_read: Callback<SampleLibrary>,
+
+    #[prop_or_default]
+    pub on_program_opened: Callback<(KeygroupProgram, SampleLibrary, Vec<ImportWarning>)>,
 }
 
 pub enum Msg {
     FilesDropped(Vec<String>),
+    SamplesRead(SampleLibrary),
+    ProgramOpened(KeygroupProgram, SampleLibrary, Vec<ImportWarning>),
+    SoundFontRead(Vec<u8>),
+    SoundFontPresetChanged(usize),
+    OpenSoundFontPreset,
+    RecordingRead(String, Vec<u8>),
+    SliceSettingsChanged(SliceSettings),
+    SamplingPlanChanged(SamplingPlan),
+    FollowPlanChanged(bool),
+    LoopRead(String, Vec<u8>),
+    ChopSettingsChanged(ChopSettings),
     RootNoteChanged(usize, MidiNote),
     IncreaseOctave,
     DecreaseOctave,
@@ -27,12 +52,42 @@ pub enum Msg {
 #[derive(Default, Serialize, Deserialize)]
 pub struct StepAddSamples {
     pub sample_files: Vec<SampleFile>,
+
+    /// How the long recordings are cut into samples
+    #[serde(default)]
+    pub slice_settings: SliceSettings,
+
+    /// Plan of the recordings of a hardware instrument
+    #[serde(default)]
+    pub sampling_plan: SamplingPlan,
+
+    /// Cut the recordings following the sampling plan
+    #[serde(default)]
+    pub follow_plan: bool,
+
+    /// How the loops are chopped into slices
+    #[serde(default)]
+    pub chop_settings: ChopSettings,
+
+    /// SoundFont waiting for the choice of a preset, with the preset names
+    #[serde(skip)]
+    pub sound_font: Option<(Vec<u8>, Vec<String>)>,
+
+    /// Preset of the SoundFont to open
+    #[serde(skip)]
+    pub sound_font_preset: usize,
 }
 
 impl From<Vec<String>> for StepAddSamples {
     fn from(files: Vec<String>) -> Self {
         Self {
             sample_files: files.into_iter().map(|f| f.into()).collect(),
+            slice_settings: SliceSettings::default(),
+            sampling_plan: SamplingPlan::default(),
+            follow_plan: false,
+            chop_settings: ChopSettings::default(),
+            sound_font: None,
+            sound_font_preset: 0,
         }
     }
 }
@@ -53,17 +108,20 @@ impl Component for StepAddSamples {
         let redraw = match msg {
             Msg::RootNoteChanged(index, note) => {
                 self.sample_files[index].root = note.into_byte();
+                self.sample_files[index].root_source = RootSource::Manual;
                 true
             }
             Msg::IncreaseOctave => {
                 self.sample_files.iter_mut().for_each(|s| {
                     s.root = (s.root + 12).clamp(0, 127);
+                    s.root_source = RootSource::Manual;
                 });
                 true
             }
             Msg::DecreaseOctave => {
                 self.sample_files.iter_mut().for_each(|s| {
                     s.root = (s.root - 12).clamp(0, 127);
+                    s.root_source = RootSource::Manual;
                 });
                 true
             }
@@ -72,7 +130,9 @@ impl Component for StepAddSamples {
                 false
             }
             Msg::GuessRoots => {
-                self.sample_files.iter_mut().for_each(|f| f.guess_root());
+                self.sample_files.iter_mut().for_each(|f| {
+                    f.guess_root();
+                });
                 true
             }
             Msg::FilesDropped(files) => {
@@ -81,6 +141,94 @@ impl Component for StepAddSamples {
                     .for_each(|f| self.sample_files.push(f.into()));
                 true
             }
+            Msg::SamplesRead(library) => {
+                for sample_file in self.sample_files.iter_mut() {
+                    if let Some(content) = library.get(&sample_file.file) {
+                        sample_file.read_info(content);
+                        // The root note from the metadata is preferred,
+                        // unless the user already chose one
+                        if sample_file.root_source != RootSource::Manual {
+                            sample_file.guess_root();
+                        }
+                    }
+                }
+                ctx.props().on_samples_read.emit(library);
+                true
+            }
+            Msg::RecordingRead(file, content) => {
+                let sliced = if self.follow_plan {
+                    self.sampling_plan.slice(&file, &content)
+                } else {
+                    self.slice_settings.slice(&file, &content)
+                };
+                match sliced {
+                    Ok((sample_files, library)) => {
+                        self.sample_files.extend(sample_files);
+                        ctx.props().on_samples_read.emit(library);
+                    }
+                    Err(e) => log::error!("{:#}", e),
+                }
+                true
+            }
+            Msg::SliceSettingsChanged(settings) => {
+                self.slice_settings = settings;
+                true
+            }
+            Msg::SamplingPlanChanged(plan) => {
+                self.sampling_plan = plan;
+                true
+            }
+            Msg::FollowPlanChanged(follow_plan) => {
+                self.follow_plan = follow_plan;
+                true
+            }
+            Msg::LoopRead(file, content) => {
+                let mut library = SampleLibrary::default();
+                library.insert(file.clone(), content);
+                match self.chop_settings.chop(&file, &library) {
+                    Ok((program, warnings)) => ctx
+                        .props()
+                        .on_program_opened
+                        .emit((program, library, warnings)),
+                    Err(e) => log::error!("{:#}", e),
+                }
+                false
+            }
+            Msg::ChopSettingsChanged(settings) => {
+                self.chop_settings = settings;
+                true
+            }
+            Msg::ProgramOpened(program, library, warnings) => {
+                ctx.props()
+                    .on_program_opened
+                    .emit((program, library, warnings));
+                false
+            }
+            Msg::SoundFontRead(sf2) => match sf2_presets(&sf2) {
+                Ok(presets) if presets.len() > 1 => {
+                    self.sound_font = Some((sf2, presets));
+                    self.sound_font_preset = 0;
+                    true
+                }
+                Ok(_) => {
+                    StepAddSamples::open_sound_font(ctx, &sf2, 0);
+                    false
+                }
+                Err(e) => {
+                    log::error!("{:#}", e);
+                    false
+                }
+            },
+            Msg::SoundFontPresetChanged(preset) => {
+                self.sound_font_preset = preset;
+                false
+            }
+            Msg::OpenSoundFontPreset => {
+                if let Some((sf2, _)) = self.sound_font.take() {
+                    StepAddSamples::open_sound_font(ctx, &sf2, self.sound_font_preset);
+                }
+                true
+            }
         };
 
         LocalStorage::set("add_samples_form", self).unwrap_or_else(|e| {
@@ -96,24 +244,99 @@ impl Component for StepAddSamples {
             .iter()
             .enumerate()
             .map(|(index, sample)| {
+                let info = match &sample.info {
+                    Some(Ok(info)) => info.to_string(),
+                    _ => String::new(),
+                };
+                let issue = match sample.issue() {
+                    Some(issue) => html! {
+                        <p class="help is-danger">
+                            <Icon icon="warning" text_after={issue} />
+                        </p>
+                    },
+                    None => html! {},
+                };
+                let pitch_mismatch = match sample.pitch_mismatch() {
+                    Some(mismatch) => html! {
+                        <p class="help is-warning">
+                            <Icon icon="warning" text_after={mismatch} />
+                        </p>
+                    },
+                    None => html! {},
+                };
                 html! {
                     <div class="tile">
-                        <div class="tile">
+                        <div class="tile is-vertical">
                             {&sample.file}
+                            <p class="help">{info}</p>
+                            {issue}
+                            {pitch_mismatch}
                         </div>
-                        <div class="tile">
+                        <div class="tile is-vertical">
                             <div class="control select">
                                 <NoteSelect
                                     value={MidiNote::from_byte(sample.root)}
                                     selection_changed={ctx.link().callback(move |root: MidiNote| Msg::RootNoteChanged(index, root))}
                                 />
                             </div>
+                            <p class="help">{sample.root_source.to_string()}</p>
                         </div>
                     </div>
                 }
             })
             .collect();
 
+        let slicing = if self.follow_plan {
+            html! {
+                <SamplingPlanForm
+                    plan={self.sampling_plan.clone()}
+                    on_change={ctx.link().callback(Msg::SamplingPlanChanged)}
+                />
+            }
+        } else {
+            html! {
+                <SliceSettingsForm
+                    settings={self.slice_settings}
+                    on_change={ctx.link().callback(Msg::SliceSettingsChanged)}
+                />
+            }
+        };
+
+        let sound_font_presets = match &self.sound_font {
+            Some((_, presets)) => {
+                let labels: Vec<String> = presets
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| format!("{}. {}", index + 1, name))
+                    .collect();
+                let options = labels.clone();
+                let on_change = ctx.link().callback(move |label: String| {
+                    Msg::SoundFontPresetChanged(
+                        labels.iter().position(|l| *l == label).unwrap_or_default(),
+                    )
+                });
+                html! {
+                    <div class="field has-addons has-addons-centered">
+                        <div class="control">
+                            <div class="select">
+                                <DropDown<String>
+                                    initial={options[self.sound_font_preset].clone()}
+                                    options={options}
+                                    selection_changed={on_change}
+                                />
+                            </div>
+                        </div>
+                        <div class="control">
+                            <button class="button is-primary" onclick={ctx.link().callback(|_| Msg::OpenSoundFontPreset)}>
+                                <Icon icon="folder-open" text_after="Open Preset" />
+                            </button>
+                        </div>
+                    </div>
+                }
+            }
+            None => html! {},
+        };
+
         html! {
             <>
             <section class="section">
@@ -140,12 +363,52 @@ impl Component for StepAddSamples {
             </div>
             <div class="file is-boxed is-centered is-primary">
                 <label class="file-label">
-                    <input class="file-input" type="file" name="samples" multiple=true accept=".wav" oninput={StepAddSamples::on_file_input(ctx)} />
+                    <input class="file-input" type="file" name="samples" multiple=true accept=".wav,.aif,.aiff,.flac" oninput={StepAddSamples::on_file_input(ctx)} />
                     <span class="file-cta">
                         <Icon icon="add-circle" class="file-icon" text_after="Add Samples..." text_class="file-label" />
                     </span>
                 </label>
             </div>
+            <section class="section">
+                {slicing}
+                <div class="field">
+                    <label class="checkbox">
+                        <input type="checkbox" checked={self.follow_plan} onchange={StepAddSamples::on_follow_plan_change(ctx)} />
+                        {" Follow the sampling plan"}
+                    </label>
+                    <p class="help">{"The recording is cut and mapped following the notes of the plan."}</p>
+                </div>
+                <div class="file is-centered">
+                    <label class="file-label">
+                        <input class="file-input" type="file" name="recording" accept=".wav,.aif,.aiff,.flac" oninput={StepAddSamples::on_recording_input(ctx)} />
+                        <span class="file-cta">
+                            <Icon icon="cut" class="file-icon" text_after="Slice Recording..." text_class="file-label" />
+                        </span>
+                    </label>
+                </div>
+                <div class="file is-centered">
+                    <label class="file-label">
+                        <input class="file-input" type="file" name="plan" accept=".json" oninput={StepAddSamples::on_plan_input(ctx)} />
+                        <span class="file-cta">
+                            <Icon icon="folder-open" class="file-icon" text_after="Open Plan..." text_class="file-label" />
+                        </span>
+                    </label>
+                </div>
+            </section>
+            <section class="section">
+                <ChopSettingsForm
+                    settings={self.chop_settings}
+                    on_change={ctx.link().callback(Msg::ChopSettingsChanged)}
+                />
+                <div class="file is-centered">
+                    <label class="file-label">
+                        <input class="file-input" type="file" name="loop" accept=".wav,.aif,.aiff,.flac" oninput={StepAddSamples::on_loop_input(ctx)} />
+                        <span class="file-cta">
+                            <Icon icon="grid" class="file-icon" text_after="Chop Loop..." text_class="file-label" />
+                        </span>
+                    </label>
+                </div>
+            </section>
             <div class="columns is-centered">
                 <section class="section">
                     <div class="buttons has-addons">
@@ -153,6 +416,15 @@ impl Component for StepAddSamples {
                     </div>
                 </section>
             </div>
+            <div class="file is-centered">
+                <label class="file-label">
+                    <input class="file-input" type="file" name="program" accept=".xpm,.sfz,.dspreset,.sf2" oninput={StepAddSamples::on_program_input(ctx)} />
+                    <span class="file-cta">
+                        <Icon icon="folder-open" class="file-icon" text_after="Open Program..." text_class="file-label" />
+                    </span>
+                </label>
+            </div>
+            {sound_font_presets}
             </>
         }
     }
@@ -160,15 +432,168 @@ impl Component for StepAddSamples {
 
 impl StepAddSamples {
     fn on_file_input(ctx: &Context<StepAddSamples>) -> Callback<InputEvent> {
-        ctx.link().batch_callback(move |e: InputEvent| {
+        let link = ctx.link().clone();
+        Callback::from(move |e: InputEvent| {
+            let files = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|input| input.files());
+            let Some(files) = files else {
+                return;
+            };
+            let files: Vec<File> = (0..files.length()).filter_map(|i| files.get(i)).collect();
+            link.send_message(Msg::FilesDropped(files.iter().map(|f| f.name()).collect()));
+            link.send_future_batch(async move {
+                let mut library = SampleLibrary::default();
+                for file in files {
+                    match files::read_bytes(file.clone()).await {
+                        Ok(content) => library.insert(file.name(), content),
+                        Err(e) => log::error!("{:#}", e),
+                    }
+                }
+                vec![Msg::SamplesRead(library)]
+            });
+        })
+    }
+
+    fn on_recording_input(ctx: &Context<StepAddSamples>) -> Callback<InputEvent> {
+        let link = ctx.link().clone();
+        Callback::from(move |e: InputEvent| {
+            let file = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                link.send_future_batch(async move {
+                    match files::read_bytes(file.clone()).await {
+                        Ok(content) => vec![Msg::RecordingRead(file.name(), content)],
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            vec![]
+                        }
+                    }
+                });
+            }
+        })
+    }
+
+    fn on_loop_input(ctx: &Context<StepAddSamples>) -> Callback<InputEvent> {
+        let link = ctx.link().clone();
+        Callback::from(move |e: InputEvent| {
+            let file = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                link.send_future_batch(async move {
+                    match files::read_bytes(file.clone()).await {
+                        Ok(content) => vec![Msg::LoopRead(file.name(), content)],
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            vec![]
+                        }
+                    }
+                });
+            }
+        })
+    }
+
+    fn on_follow_plan_change(ctx: &Context<StepAddSamples>) -> Callback<Event> {
+        ctx.link().batch_callback(|e: Event| {
             let input: HtmlInputElement = e
                 .target()
                 .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())?;
-            let files = input.files()?;
-            let file_names: Vec<String> = (0..files.length())
-                .filter_map(|i| Some(files.get(i)?.name()))
-                .collect();
-            Some(Msg::FilesDropped(file_names))
+            Some(Msg::FollowPlanChanged(input.checked()))
         })
     }
+
+    fn on_plan_input(ctx: &Context<StepAddSamples>) -> Callback<InputEvent> {
+        let link = ctx.link().clone();
+        Callback::from(move |e: InputEvent| {
+            let file = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                link.send_future_batch(async move {
+                    let plan = match files::read_text(file).await {
+                        Ok(content) => SamplingPlan::import_manifest(content.as_bytes()),
+                        Err(e) => Err(e),
+                    };
+                    match plan {
+                        Ok(plan) => {
+                            vec![Msg::SamplingPlanChanged(plan), Msg::FollowPlanChanged(true)]
+                        }
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            vec![]
+                        }
+                    }
+                });
+            }
+        })
+    }
+
+    fn on_program_input(ctx: &Context<StepAddSamples>) -> Callback<InputEvent> {
+        let link = ctx.link().clone();
+        Callback::from(move |e: InputEvent| {
+            let file = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                let is_sound_font = file.name().to_lowercase().ends_with(".sf2");
+                link.send_future_batch(async move {
+                    if is_sound_font {
+                        // The preset is chosen once the presets are known
+                        return match files::read_bytes(file).await {
+                            Ok(sf2) => vec![Msg::SoundFontRead(sf2)],
+                            Err(e) => {
+                                log::error!("{:#}", e);
+                                vec![]
+                            }
+                        };
+                    }
+                    match StepAddSamples::open_program(file).await {
+                        Ok((program, library, warnings)) => {
+                            vec![Msg::ProgramOpened(program, library, warnings)]
+                        }
+                        Err(e) => {
+                            log::error!("{:#}", e);
+                            vec![]
+                        }
+                    }
+                });
+            }
+        })
+    }
+
+    /// Import a preset of a SoundFont as a program.
+    fn open_sound_font(ctx: &Context<StepAddSamples>, sf2: &[u8], preset: usize) {
+        match KeygroupProgram::import_sf2(sf2, preset) {
+            Ok(opened) => ctx.props().on_program_opened.emit(opened),
+            Err(e) => log::error!("{:#}", e),
+        }
+    }
+
+    /// Import a program, depending on its file extension.
+    async fn open_program(
+        file: File,
+    ) -> anyhow::Result<(KeygroupProgram, SampleLibrary, Vec<ImportWarning>)> {
+        let name = file.name();
+        let (stem, extension) = name.rsplit_once('.').unwrap_or((&name, ""));
+        let extension = extension.to_lowercase();
+
+        let content = files::read_text(file).await?;
+        let (program, warnings) = match extension.as_str() {
+            "sfz" => KeygroupProgram::import_sfz(content.as_bytes(), stem)?,
+            "dspreset" => KeygroupProgram::import_dspreset(content.as_bytes(), stem)?,
+            _ => KeygroupProgram::import(content.as_bytes())?,
+        };
+        Ok((program, SampleLibrary::default(), warnings))
+    }
 }
@@ -0,0 +1,153 @@
+use crate::components::{Icon, PadSelect};
+use crate::model::{pad_name, DrumProgram, PadFile, SampleFile};
+use gloo_storage::{LocalStorage, Storage};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use yew::{html, Callback, Component, Context, Html, Properties};
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    #[prop_or_default]
+    pub files: Vec<SampleFile>,
+
+    #[prop_or_default]
+    pub on_next: Callback<DrumProgram>,
+}
+
+pub enum Msg {
+    PadChanged(usize, u8),
+    GuessPads,
+    Next,
+}
+
+/// Assign a drum pad to each sample file.
+///
+/// The files assigned to the same pad are stacked as velocity layers.
+#[derive(Default, Serialize, Deserialize)]
+pub struct StepAssignPads {
+    pub pad_files: Vec<PadFile>,
+}
+
+impl From<Vec<SampleFile>> for StepAssignPads {
+    fn from(sample_files: Vec<SampleFile>) -> Self {
+        // One pad per file, in the alphabetical order
+        let pad_files = sample_files
+            .into_iter()
+            .sorted_by(|a, b| a.file.cmp(&b.file))
+            .enumerate()
+            .map(|(index, file)| PadFile::from_sample_file(file, (index % 128) as u8))
+            .collect();
+        Self { pad_files }
+    }
+}
+
+impl Component for StepAssignPads {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        LocalStorage::get("assign_pads_form").unwrap_or_else(|_| ctx.props().files.clone().into())
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        LocalStorage::delete("assign_pads_form");
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let redraw = match msg {
+            Msg::PadChanged(index, pad) => {
+                self.pad_files[index].pad = pad;
+                true
+            }
+            Msg::GuessPads => {
+                *self = ctx.props().files.clone().into();
+                true
+            }
+            Msg::Next => {
+                ctx.props()
+                    .on_next
+                    .emit(DrumProgram::from(self.pad_files.clone()));
+                false
+            }
+        };
+        LocalStorage::set("assign_pads_form", self).unwrap_or_else(|e| {
+            log::error!("{e}");
+        });
+        redraw
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let samples: Html = self
+            .pad_files
+            .iter()
+            .enumerate()
+            .map(|(index, sample)| {
+                html! {
+                    <div class="tile">
+                        <div class="tile">
+                            {&sample.file}
+                        </div>
+                        <div class="tile">
+                            <PadSelect
+                                value={sample.pad}
+                                selection_changed={ctx.link().callback(move |pad: u8| Msg::PadChanged(index, pad))}
+                            />
+                        </div>
+                    </div>
+                }
+            })
+            .collect();
+
+        let full_pads: Vec<Html> = self
+            .pad_files
+            .iter()
+            .map(|f| f.pad)
+            .counts()
+            .into_iter()
+            .filter(|(_, count)| *count > 4)
+            .sorted()
+            .map(|(pad, count)| {
+                html! {
+                    <li>{format!("{} has {} samples, only the first 4 are used.", pad_name(pad), count)}</li>
+                }
+            })
+            .collect();
+        let full_pads = if full_pads.is_empty() {
+            html! {}
+        } else {
+            html! {
+                <div class="notification is-warning">
+                    <ul>{full_pads}</ul>
+                </div>
+            }
+        };
+
+        html! {
+            <>
+                {full_pads}
+                <div class="columns">
+                    <div class="column"></div>
+                    <div class="column is-half">
+                        <div class="tile is-ancestor is-vertical">
+                            {samples}
+                        </div>
+                    </div>
+                    <div class="column"></div>
+                </div>
+                <div class="buttons has-addons is-centered">
+                    <button class="button" onclick={ctx.link().callback(|_| Msg::GuessPads)}>
+                        <Icon icon="color-wand" text_after="Guess Pads" />
+                    </button>
+                    <button class="button is-success" onclick={ctx.link().callback(|_| Msg::Next)}>
+                        <Icon icon="caret-forward" text_before="Next" />
+                    </button>
+                </div>
+            </>
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>) -> bool {
+        *self = ctx.props().files.clone().into();
+        true
+    }
+}
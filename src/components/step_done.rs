@@ -1,30 +1,56 @@
-use crate::{components::Icon, model::KeygroupProgram};
-use anyhow::bail;
+use crate::{
+    components::{files, Icon, SampleFormatSelect},
+    model::{KeygroupProgram, SampleLibrary},
+    utils::{wav_file_name, TargetFormat},
+};
 use gloo_storage::{LocalStorage, Storage};
-use js_sys::encode_uri_component;
 use serde::{Deserialize, Serialize};
+use std::io::Cursor;
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlInputElement, InputEvent, MouseEvent};
+use web_sys::{Event, HtmlInputElement, InputEvent, MouseEvent};
 use yew::{html, Callback, Component, Context, Html, Properties};
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct StepDone {
     /// The keygroup program ready to be saved
     program: KeygroupProgram,
+
+    /// Even out the volume of the keygroups
+    #[serde(default)]
+    normalize_volume: bool,
+
+    /// Format of the samples of the bundle
+    #[serde(default)]
+    sample_format: TargetFormat,
 }
 
 pub enum Msg {
     Previous,
     ProgramNameChanged(String),
     Save,
+    SaveSfz,
+    SaveDspreset,
+    SaveSf2,
+    SaveBundle,
+    AddToExpansion,
+    SaveSamples,
+    NormalizeVolumeChanged(bool),
+    SampleFormatChanged(TargetFormat),
 }
 
 #[derive(Properties, PartialEq)]
 pub struct Props {
     pub program: KeygroupProgram,
 
+    /// Content of the sample files, when known
+    #[prop_or_default]
+    pub library: SampleLibrary,
+
     #[prop_or_default]
     pub on_previous: Callback<()>,
+
+    #[prop_or_default]
+    pub on_add_to_expansion: Callback<KeygroupProgram>,
 }
 
 impl Component for StepDone {
@@ -35,6 +61,8 @@ impl Component for StepDone {
     fn create(ctx: &Context<Self>) -> Self {
         LocalStorage::get("step_done").unwrap_or_else(|_| Self {
             program: ctx.props().program.clone(),
+            normalize_volume: false,
+            sample_format: TargetFormat::default(),
         })
     }
 
@@ -54,6 +82,56 @@ impl Component for StepDone {
                 };
                 true
             }
+            Msg::SaveSfz => {
+                if let Err(e) = self.export_sfz() {
+                    log::error!("{}", e);
+                };
+                true
+            }
+            Msg::SaveDspreset => {
+                if let Err(e) = self.export_dspreset() {
+                    log::error!("{}", e);
+                };
+                true
+            }
+            Msg::SaveSf2 => {
+                if let Err(e) = self.export_sf2(&ctx.props().library) {
+                    log::error!("{:#}", e);
+                };
+                true
+            }
+            Msg::SaveBundle => {
+                if let Err(e) = self.export_bundle(&ctx.props().library) {
+                    log::error!("{:#}", e);
+                };
+                true
+            }
+            Msg::AddToExpansion => {
+                ctx.props().on_add_to_expansion.emit(self.program.clone());
+                false
+            }
+            Msg::SaveSamples => {
+                if let Err(e) = Self::export_samples(&ctx.props().library) {
+                    log::error!("{}", e);
+                };
+                true
+            }
+            Msg::NormalizeVolumeChanged(normalize_volume) => {
+                self.normalize_volume = normalize_volume;
+                if normalize_volume {
+                    if let Err(e) = self.program.normalize_volume(&ctx.props().library) {
+                        log::error!("{:#}", e);
+                        self.normalize_volume = false;
+                    }
+                } else {
+                    self.program.reset_volume();
+                }
+                true
+            }
+            Msg::SampleFormatChanged(format) => {
+                self.sample_format = format;
+                true
+            }
             Msg::Previous => {
                 ctx.props().on_previous.emit(());
                 true
@@ -66,6 +144,35 @@ impl Component for StepDone {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
+        let save_samples = if ctx.props().library.is_empty() {
+            html! {}
+        } else {
+            html! {
+                <div class="control">
+                    <button class="button" onclick={ctx.link().callback(|_: MouseEvent| Msg::SaveSamples)}>
+                        <Icon icon="musical-notes" text_after="Save Samples" />
+                    </button>
+                </div>
+            }
+        };
+
+        // The bundle and the SoundFont embed the samples, their content is needed
+        let library = &ctx.props().library;
+        let missing_samples = self
+            .program
+            .layers()
+            .any(|layer| library.get(&layer.file).is_none());
+        let missing_samples_help =
+            missing_samples.then_some("Add the sample files again to embed them");
+
+        // The MPC only reads WAV samples, the bundle converts the other ones
+        let not_wav = self
+            .program
+            .layers()
+            .any(|layer| wav_file_name(&layer.file) != layer.file);
+        let not_wav_help =
+            not_wav.then_some("The MPC only reads WAV samples, use Save Bundle to convert them");
+
         html! {
             <div class="block">
                 <div class="field has-addons">
@@ -79,10 +186,73 @@ impl Component for StepDone {
                         />
                     </div>
                     <div class="control">
-                        <button class="button is-link" onclick={ctx.link().callback(|_: MouseEvent| Msg::Save)}>
+                        <button
+                            class="button is-link"
+                            disabled={not_wav}
+                            title={not_wav_help}
+                            onclick={ctx.link().callback(|_: MouseEvent| Msg::Save)}
+                        >
                             <Icon icon="save" text_after="Save" />
                         </button>
                     </div>
+                    <div class="control">
+                        <button
+                            class="button"
+                            disabled={missing_samples}
+                            title={missing_samples_help}
+                            onclick={ctx.link().callback(|_: MouseEvent| Msg::SaveBundle)}
+                        >
+                            <Icon icon="archive" text_after="Save Bundle" />
+                        </button>
+                    </div>
+                    <div class="control">
+                        <button class="button" onclick={ctx.link().callback(|_: MouseEvent| Msg::SaveSfz)}>
+                            <Icon icon="document-text" text_after="Save SFZ" />
+                        </button>
+                    </div>
+                    <div class="control">
+                        <button class="button" onclick={ctx.link().callback(|_: MouseEvent| Msg::SaveDspreset)}>
+                            <Icon icon="document-text" text_after="Save Decent Sampler" />
+                        </button>
+                    </div>
+                    <div class="control">
+                        <button
+                            class="button"
+                            disabled={missing_samples}
+                            title={missing_samples_help}
+                            onclick={ctx.link().callback(|_: MouseEvent| Msg::SaveSf2)}
+                        >
+                            <Icon icon="musical-notes" text_after="Save SoundFont" />
+                        </button>
+                    </div>
+                    {save_samples}
+                </div>
+                <div class="field">
+                    <div class="control">
+                        <label class="checkbox" title={missing_samples_help.unwrap_or("Set the volume of each keygroup so that the instrument plays evenly across the keyboard")}>
+                            <input
+                                type="checkbox"
+                                disabled={missing_samples}
+                                checked={self.normalize_volume}
+                                onchange={StepDone::on_normalize_volume_change(ctx)}
+                            />
+                            {" Even out the volume across the keyboard"}
+                        </label>
+                    </div>
+                </div>
+                <SampleFormatSelect
+                    format={self.sample_format}
+                    on_change={ctx.link().callback(Msg::SampleFormatChanged)}
+                />
+                <div class="buttons">
+                    <button
+                        class="button"
+                        disabled={missing_samples}
+                        title={missing_samples_help}
+                        onclick={ctx.link().callback(|_: MouseEvent| Msg::AddToExpansion)}
+                    >
+                        <Icon icon="albums" text_after="Add to Expansion" />
+                    </button>
                 </div>
                 /*<div class="buttons has-addons is-centered">
                     <button class="button" onclick={ctx.link().callback(|_| Msg::Previous)}>
@@ -96,48 +266,55 @@ impl Component for StepDone {
 
 impl StepDone {
     fn export(&self) -> anyhow::Result<()> {
-        use anyhow::Context;
         let mut file_content = Vec::<u8>::new();
         self.program.export(&mut file_content)?;
-        let file_content = String::from_utf8(file_content)
-            .context("Failed to convert the instrument file to UTF8")?;
-        let file_content = encode_uri_component(&file_content);
-        let file_name = format!("{}.xpm", self.program.name);
-
-        let window = web_sys::window().context("Failed to get the browser window")?;
-        let document = window
-            .document()
-            .context("Failed to get the window document")?;
-        let body = document.body().context("Failed to get the document body")?;
-        let element = document
-            .create_element("a")
-            .or_else(|e| bail!(e.as_string().unwrap_or_default()))
-            .context("Failed to insert a link in the document")?;
-        let element = element
-            .dyn_into::<web_sys::HtmlElement>()
-            .or_else(|e| bail!(e.as_string().unwrap_or_default()))
-            .context("Failed to convert the element to an HTML element")?;
-        element
-            .set_attribute(
-                "href",
-                format!("data:text/plain;charset=utf-8,{}", file_content).as_str(),
-            )
-            .or_else(|e| bail!(e.as_string().unwrap_or_default()))
-            .context("Failed to set the element destination")?;
-        element
-            .set_attribute("download", &file_name)
-            .or_else(|e| bail!(e.as_string().unwrap_or_default()))
-            .context("Failed to create the download file name")?;
-        body.append_child(&element)
-            .or_else(|e| bail!(e.as_string().unwrap_or_default()))
-            .context("Failed to insert the element in the document")?;
-        element.click();
-        body.remove_child(&element)
-            .or_else(|e| bail!(e.as_string().unwrap_or_default()))
-            .context("Failed to remove the element from the document")?;
+        files::download(&format!("{}.xpm", self.program.name), &file_content)
+    }
+
+    fn export_bundle(&self, library: &SampleLibrary) -> anyhow::Result<()> {
+        let mut file_content = Cursor::new(Vec::<u8>::new());
+        self.program
+            .export_bundle(&mut file_content, library, &self.sample_format)?;
+        files::download(
+            &format!("{}.zip", self.program.name),
+            file_content.get_ref(),
+        )
+    }
+
+    fn export_sfz(&self) -> anyhow::Result<()> {
+        let mut file_content = Vec::<u8>::new();
+        self.program.export_sfz(&mut file_content)?;
+        files::download(&format!("{}.sfz", self.program.name), &file_content)
+    }
+
+    fn export_dspreset(&self) -> anyhow::Result<()> {
+        let mut file_content = Vec::<u8>::new();
+        self.program.export_dspreset(&mut file_content)?;
+        files::download(&format!("{}.dspreset", self.program.name), &file_content)
+    }
+
+    fn export_sf2(&self, library: &SampleLibrary) -> anyhow::Result<()> {
+        let mut file_content = Vec::<u8>::new();
+        self.program.export_sf2(&mut file_content, library)?;
+        files::download(&format!("{}.sf2", self.program.name), &file_content)
+    }
+
+    fn export_samples(library: &SampleLibrary) -> anyhow::Result<()> {
+        for (file_name, file_content) in library.iter() {
+            files::download(file_name, file_content)?;
+        }
         Ok(())
     }
 
+    fn on_normalize_volume_change(ctx: &Context<StepDone>) -> Callback<Event> {
+        ctx.link().batch_callback(|e: Event| {
+            let input: HtmlInputElement = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())?;
+            Some(Msg::NormalizeVolumeChanged(input.checked()))
+        })
+    }
+
     fn on_program_name_change(ctx: &Context<StepDone>) -> Callback<InputEvent> {
         ctx.link().batch_callback(|e: InputEvent| {
             let input: HtmlInputElement = e
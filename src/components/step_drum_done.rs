@@ -0,0 +1,187 @@
+use crate::{
+    components::{files, Icon, SampleFormatSelect},
+    model::{DrumProgram, SampleLibrary},
+    utils::TargetFormat,
+};
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, InputEvent, MouseEvent};
+use yew::{html, Callback, Component, Context, Html, Properties};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct StepDrumDone {
+    /// The drum program ready to be saved
+    program: DrumProgram,
+
+    /// Format of the samples of the bundle
+    #[serde(default)]
+    sample_format: TargetFormat,
+}
+
+pub enum Msg {
+    ProgramNameChanged(String),
+    Save,
+    SaveBundle,
+    SampleFormatChanged(TargetFormat),
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub program: DrumProgram,
+
+    /// Content of the sample files, when known
+    #[prop_or_default]
+    pub library: SampleLibrary,
+}
+
+impl Component for StepDrumDone {
+    type Message = Msg;
+
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        LocalStorage::get("step_drum_done").unwrap_or_else(|_| Self {
+            program: ctx.props().program.clone(),
+            sample_format: TargetFormat::default(),
+        })
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        LocalStorage::delete("step_drum_done");
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let redraw = match msg {
+            Msg::ProgramNameChanged(name) => {
+                self.program.name = name;
+                true
+            }
+            Msg::Save => {
+                if let Err(e) = self.export() {
+                    log::error!("{}", e);
+                };
+                true
+            }
+            Msg::SaveBundle => {
+                if let Err(e) = self.export_bundle(&ctx.props().library) {
+                    log::error!("{:#}", e);
+                };
+                true
+            }
+            Msg::SampleFormatChanged(format) => {
+                self.sample_format = format;
+                true
+            }
+        };
+        LocalStorage::set("step_drum_done", self).unwrap_or_else(|e| {
+            log::error!("{e}");
+        });
+        redraw
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let pads: Html = self
+            .program
+            .pads
+            .iter()
+            .map(|pad| {
+                let files: Vec<&str> = pad
+                    .layers
+                    .iter()
+                    .flatten()
+                    .map(|layer| layer.file.as_str())
+                    .collect();
+                html! {
+                    <tr>
+                        <td>{pad.name()}</td>
+                        <td>{files.join(", ")}</td>
+                    </tr>
+                }
+            })
+            .collect();
+
+        // The bundle embeds the samples, their content is needed
+        let library = &ctx.props().library;
+        let missing_samples = self
+            .program
+            .layers()
+            .any(|layer| library.get(&layer.file).is_none());
+
+        html! {
+            <>
+                <table class="table is-fullwidth">
+                    <thead>
+                        <tr>
+                            <th>{"Pad"}</th>
+                            <th>{"Layers"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {pads}
+                    </tbody>
+                </table>
+                <div class="block">
+                    <div class="field has-addons">
+                        <div class="control">
+                            <input
+                                class="input"
+                                type="text"
+                                placeholder="Program Name"
+                                value={self.program.name.clone()}
+                                oninput={StepDrumDone::on_program_name_change(ctx)}
+                            />
+                        </div>
+                        <div class="control">
+                            <button class="button is-link" onclick={ctx.link().callback(|_: MouseEvent| Msg::Save)}>
+                                <Icon icon="save" text_after="Save" />
+                            </button>
+                        </div>
+                        <div class="control">
+                            <button
+                                class="button"
+                                disabled={missing_samples}
+                                title={missing_samples.then_some("Add the sample files again to embed them")}
+                                onclick={ctx.link().callback(|_: MouseEvent| Msg::SaveBundle)}
+                            >
+                                <Icon icon="archive" text_after="Save Bundle" />
+                            </button>
+                        </div>
+                    </div>
+                    <SampleFormatSelect
+                        format={self.sample_format}
+                        on_change={ctx.link().callback(Msg::SampleFormatChanged)}
+                    />
+                </div>
+            </>
+        }
+    }
+}
+
+impl StepDrumDone {
+    fn export(&self) -> anyhow::Result<()> {
+        let mut file_content = Vec::<u8>::new();
+        self.program.export(&mut file_content)?;
+        files::download(&format!("{}.xpm", self.program.name), &file_content)
+    }
+
+    fn export_bundle(&self, library: &SampleLibrary) -> anyhow::Result<()> {
+        let mut file_content = Cursor::new(Vec::<u8>::new());
+        self.program
+            .export_bundle(&mut file_content, library, &self.sample_format)?;
+        files::download(
+            &format!("{}.zip", self.program.name),
+            file_content.get_ref(),
+        )
+    }
+
+    fn on_program_name_change(ctx: &Context<StepDrumDone>) -> Callback<InputEvent> {
+        ctx.link().batch_callback(|e: InputEvent| {
+            let input: HtmlInputElement = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())?;
+            Some(Msg::ProgramNameChanged(input.value()))
+        })
+    }
+}
@@ -1,17 +1,26 @@
 use crate::{
     components::{Icon, Keyboard, KeygroupsTable},
-    model::{KeygroupProgram, LayerFile, LayerVelocityMode},
+    model::{ImportWarning, KeygroupProgram, LayerVelocityMode, SampleLibrary},
+    utils::Articulation,
 };
 use gloo_storage::{LocalStorage, Storage};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::JsCast;
-use web_sys::{HtmlInputElement, InputEvent};
+use web_sys::{Event, HtmlInputElement, InputEvent};
 use yew::{html, Callback, Component, Context, Html, Properties};
 use yew_utils::components::drop_down::DropDown;
 
 #[derive(Properties, PartialEq)]
 pub struct Props {
-    pub layer_files: Vec<LayerFile>,
+    pub program: KeygroupProgram,
+
+    #[prop_or_default]
+    pub warnings: Vec<ImportWarning>,
+
+    /// Content of the sample files, when known
+    #[prop_or_default]
+    pub library: SampleLibrary,
 
     #[prop_or_default]
     pub on_previous: Callback<()>,
@@ -24,6 +33,18 @@ pub enum Msg {
     PitchPreferenceChange(f32),
     LayerVelocityModeChange(LayerVelocityMode),
     HighlightKeygroup(Option<usize>),
+    TuneChanged(usize, usize, i8),
+    SilenceThresholdChange(f32),
+    PreRollChange(f32),
+    TrimSilence,
+    ResetSampleRegions,
+    LoopCrossfadeChange(f32),
+    FindLoops,
+    ResetLoops,
+    DetectTempo,
+    WarpChange(bool),
+    DetectArticulation,
+    ResetArticulation,
     Previous,
     Next,
 }
@@ -42,20 +63,43 @@ pub struct StepFineTuning {
 
     /// Keygroup index to highlight
     highlight_keygroup: Option<usize>,
+
+    /// Level under which the start and end of the samples are trimmed (dB)
+    #[serde(default = "default_silence_threshold")]
+    silence_threshold: f32,
+
+    /// Duration kept before the start of the samples when trimming (ms)
+    #[serde(default = "default_pre_roll")]
+    pre_roll: f32,
+
+    /// Crossfade baked at the end of the loops (ms)
+    #[serde(default)]
+    loop_crossfade: f32,
+
+    /// Warp the loops to the project tempo
+    #[serde(default)]
+    warp: bool,
+}
+
+fn default_silence_threshold() -> f32 {
+    -60.0
 }
 
-impl From<Vec<LayerFile>> for StepFineTuning {
-    fn from(layer_files: Vec<LayerFile>) -> Self {
-        let mut program = KeygroupProgram::default();
-        program.insert_layer_files(layer_files);
-        program.sort_keygroups();
-        program.guess_ranges(0.5);
+fn default_pre_roll() -> f32 {
+    5.0
+}
 
+impl From<KeygroupProgram> for StepFineTuning {
+    fn from(program: KeygroupProgram) -> Self {
         Self {
             program,
             pitch_preference: 0.5,
             layer_velocity_mode: LayerVelocityMode::Automatic,
             highlight_keygroup: None,
+            silence_threshold: default_silence_threshold(),
+            pre_roll: default_pre_roll(),
+            loop_crossfade: 0.0,
+            warp: false,
         }
     }
 }
@@ -65,8 +109,7 @@ impl Component for StepFineTuning {
     type Properties = Props;
 
     fn create(ctx: &Context<Self>) -> Self {
-        LocalStorage::get("step_fine_tuning")
-            .unwrap_or_else(|_| ctx.props().layer_files.clone().into())
+        LocalStorage::get("step_fine_tuning").unwrap_or_else(|_| ctx.props().program.clone().into())
     }
 
     fn destroy(&mut self, _ctx: &Context<Self>) {
@@ -89,6 +132,79 @@ impl Component for StepFineTuning {
                 self.highlight_keygroup = index;
                 true
             }
+            Msg::TuneChanged(keygroup, layer, tune) => {
+                if let Some(layer) = self
+                    .program
+                    .keygroups
+                    .get_mut(keygroup)
+                    .and_then(|kg| kg.layers.get_mut(layer))
+                    .and_then(|layer| layer.as_mut())
+                {
+                    layer.tune = tune;
+                }
+                true
+            }
+            Msg::SilenceThresholdChange(threshold) => {
+                self.silence_threshold = threshold;
+                false
+            }
+            Msg::PreRollChange(pre_roll) => {
+                self.pre_roll = pre_roll;
+                false
+            }
+            Msg::TrimSilence => {
+                if let Err(e) = self.program.trim_silence(
+                    &ctx.props().library,
+                    self.silence_threshold,
+                    self.pre_roll / 1000.0,
+                ) {
+                    log::error!("{:#}", e);
+                }
+                true
+            }
+            Msg::ResetSampleRegions => {
+                self.program.reset_sample_regions();
+                true
+            }
+            Msg::LoopCrossfadeChange(crossfade) => {
+                self.loop_crossfade = crossfade;
+                false
+            }
+            Msg::FindLoops => {
+                if let Err(e) = self
+                    .program
+                    .find_loops(&ctx.props().library, self.loop_crossfade / 1000.0)
+                {
+                    log::error!("{:#}", e);
+                }
+                true
+            }
+            Msg::ResetLoops => {
+                self.program.reset_loops();
+                true
+            }
+            Msg::DetectTempo => {
+                if let Err(e) = self.program.detect_tempo(&ctx.props().library) {
+                    log::error!("{:#}", e);
+                }
+                self.program.set_warp(self.warp);
+                true
+            }
+            Msg::WarpChange(warp) => {
+                self.warp = warp;
+                self.program.set_warp(warp);
+                true
+            }
+            Msg::DetectArticulation => {
+                if let Err(e) = self.program.detect_articulation(&ctx.props().library) {
+                    log::error!("{:#}", e);
+                }
+                true
+            }
+            Msg::ResetArticulation => {
+                self.program.reset_articulation();
+                true
+            }
             Msg::Previous => {
                 ctx.props().on_previous.emit(());
                 false
@@ -113,10 +229,77 @@ impl Component for StepFineTuning {
             }
             LayerVelocityMode::Unison => "All the layers will play at the same time.",
         };
+        let library = &ctx.props().library;
+        let missing_samples = self
+            .program
+            .layers()
+            .any(|layer| library.get(&layer.file).is_none());
+        let missing_samples_help =
+            missing_samples.then_some("Add the sample files again to analyse them");
+        let trimmed_layers = self
+            .program
+            .layers()
+            .filter(|layer| {
+                layer.sample_region.is_some() && layer.sample_region != layer.sample_slice
+            })
+            .count();
+        let looped_layers = self
+            .program
+            .layers()
+            .filter(|layer| layer.sample_loop.is_some())
+            .count();
+        let tempos = self
+            .program
+            .keygroups
+            .iter()
+            .filter_map(|kg| kg.bpm())
+            .map(|bpm| format!("{:.2}", bpm))
+            .unique()
+            .join(", ");
+        let articulation_count = |articulation| {
+            self.program
+                .keygroups
+                .iter()
+                .filter(|kg| kg.articulation == Some(articulation))
+                .count()
+        };
+        let articulation_help = format!(
+            "Percussive samples are played to their end, sustained samples are held with a release. {} percussive and {} sustained keygroups.",
+            articulation_count(Articulation::Percussive),
+            articulation_count(Articulation::Sustained)
+        );
+        let tempo_help = if tempos.is_empty() {
+            "Detect the tempo of the loops to warp them.".to_string()
+        } else {
+            format!("The loops follow the project tempo when played chromatically. Detected tempo: {} BPM.", tempos)
+        };
+
+        let warnings: Html = ctx
+            .props()
+            .warnings
+            .iter()
+            .map(|warning| html! { <li>{warning.to_string()}</li> })
+            .collect();
+        let warnings = if ctx.props().warnings.is_empty() {
+            html! {}
+        } else {
+            html! {
+                <div class="notification is-warning">
+                    <p>{"Some settings of the program could not be imported:"}</p>
+                    <ul>{warnings}</ul>
+                </div>
+            }
+        };
+
         html! {
             <>
+                {warnings}
                 <Keyboard keygroups={self.program.keygroups.clone()} highlight_keygroup={self.highlight_keygroup} />
-                <KeygroupsTable keygroups={self.program.keygroups.clone()} on_hovered_kg={ctx.link().callback(Msg::HighlightKeygroup)} />
+                <KeygroupsTable
+                    keygroups={self.program.keygroups.clone()}
+                    on_hovered_kg={ctx.link().callback(Msg::HighlightKeygroup)}
+                    on_tune_changed={ctx.link().callback(|(keygroup, layer, tune)| Msg::TuneChanged(keygroup, layer, tune))}
+                />
                 <div class="block">
                     <div class="field">
                         <label class="label">{"Pitch Preference"}</label>
@@ -145,6 +328,149 @@ impl Component for StepFineTuning {
                         </div>
                         <p class="help">{layer_help_text}</p>
                     </div>
+                    <div class="field">
+                        <label class="label">{"Silence Trimming"}</label>
+                        <div class="field is-grouped">
+                            <div class="control">
+                                <div class="field has-addons">
+                                    <div class="control">
+                                        <input
+                                            class="input"
+                                            type="number"
+                                            max="0"
+                                            step="1"
+                                            title="Threshold"
+                                            value={self.silence_threshold.to_string()}
+                                            oninput={StepFineTuning::on_number_change(ctx, Msg::SilenceThresholdChange)}
+                                        />
+                                    </div>
+                                    <div class="control">
+                                        <a class="button is-static">{"dB"}</a>
+                                    </div>
+                                </div>
+                            </div>
+                            <div class="control">
+                                <div class="field has-addons">
+                                    <div class="control">
+                                        <input
+                                            class="input"
+                                            type="number"
+                                            min="0"
+                                            step="1"
+                                            title="Pre-roll"
+                                            value={self.pre_roll.to_string()}
+                                            oninput={StepFineTuning::on_number_change(ctx, Msg::PreRollChange)}
+                                        />
+                                    </div>
+                                    <div class="control">
+                                        <a class="button is-static">{"ms pre-roll"}</a>
+                                    </div>
+                                </div>
+                            </div>
+                            <div class="control">
+                                <button
+                                    class="button"
+                                    disabled={missing_samples}
+                                    title={missing_samples_help}
+                                    onclick={ctx.link().callback(|_| Msg::TrimSilence)}
+                                >
+                                    <Icon icon="cut" text_after="Trim Silence" />
+                                </button>
+                            </div>
+                            <div class="control">
+                                <button class="button" onclick={ctx.link().callback(|_| Msg::ResetSampleRegions)}>
+                                    <Icon icon="refresh" text_after="Reset" />
+                                </button>
+                            </div>
+                        </div>
+                        <p class="help">
+                            {format!("The silence under the threshold is skipped at the start and the end of the samples. {} samples are trimmed.", trimmed_layers)}
+                        </p>
+                    </div>
+                    <div class="field">
+                        <label class="label">{"Sustain Loops"}</label>
+                        <div class="field is-grouped">
+                            <div class="control">
+                                <div class="field has-addons">
+                                    <div class="control">
+                                        <input
+                                            class="input"
+                                            type="number"
+                                            min="0"
+                                            step="1"
+                                            title="Crossfade"
+                                            value={self.loop_crossfade.to_string()}
+                                            oninput={StepFineTuning::on_number_change(ctx, Msg::LoopCrossfadeChange)}
+                                        />
+                                    </div>
+                                    <div class="control">
+                                        <a class="button is-static">{"ms crossfade"}</a>
+                                    </div>
+                                </div>
+                            </div>
+                            <div class="control">
+                                <button
+                                    class="button"
+                                    disabled={missing_samples}
+                                    title={missing_samples_help}
+                                    onclick={ctx.link().callback(|_| Msg::FindLoops)}
+                                >
+                                    <Icon icon="infinite" text_after="Find Loops" />
+                                </button>
+                            </div>
+                            <div class="control">
+                                <button class="button" onclick={ctx.link().callback(|_| Msg::ResetLoops)}>
+                                    <Icon icon="refresh" text_after="Reset" />
+                                </button>
+                            </div>
+                        </div>
+                        <p class="help">
+                            {format!("A seamless loop is found in the sustain of the samples, the crossfade is baked into the samples of the bundles. {} samples are looped.", looped_layers)}
+                        </p>
+                    </div>
+                    <div class="field">
+                        <label class="label">{"Warp"}</label>
+                        <div class="field is-grouped">
+                            <div class="control">
+                                <button
+                                    class="button"
+                                    disabled={missing_samples}
+                                    title={missing_samples_help}
+                                    onclick={ctx.link().callback(|_| Msg::DetectTempo)}
+                                >
+                                    <Icon icon="speedometer" text_after="Detect Tempo" />
+                                </button>
+                            </div>
+                            <div class="control">
+                                <label class="checkbox">
+                                    <input type="checkbox" checked={self.warp} onchange={StepFineTuning::on_warp_change(ctx)} />
+                                    {" Warp to the project tempo"}
+                                </label>
+                            </div>
+                        </div>
+                        <p class="help">{tempo_help}</p>
+                    </div>
+                    <div class="field">
+                        <label class="label">{"Playback"}</label>
+                        <div class="field is-grouped">
+                            <div class="control">
+                                <button
+                                    class="button"
+                                    disabled={missing_samples}
+                                    title={missing_samples_help}
+                                    onclick={ctx.link().callback(|_| Msg::DetectArticulation)}
+                                >
+                                    <Icon icon="pulse" text_after="Detect Envelopes" />
+                                </button>
+                            </div>
+                            <div class="control">
+                                <button class="button" onclick={ctx.link().callback(|_| Msg::ResetArticulation)}>
+                                    <Icon icon="refresh" text_after="Reset" />
+                                </button>
+                            </div>
+                        </div>
+                        <p class="help">{articulation_help}</p>
+                    </div>
                 </div>
                 <div class="buttons has-addons is-centered">
                     /*<button class="button" onclick={ctx.link().callback(|_| Msg::Previous)}>
@@ -168,4 +494,26 @@ impl StepFineTuning {
             Some(Msg::PitchPreferenceChange(input.value_as_number() as f32))
         })
     }
+
+    fn on_warp_change(ctx: &Context<StepFineTuning>) -> Callback<Event> {
+        ctx.link().batch_callback(|e: Event| {
+            let input: HtmlInputElement = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())?;
+            Some(Msg::WarpChange(input.checked()))
+        })
+    }
+
+    fn on_number_change(
+        ctx: &Context<StepFineTuning>,
+        msg: fn(f32) -> Msg,
+    ) -> Callback<InputEvent> {
+        ctx.link().batch_callback(move |e: InputEvent| {
+            let input: HtmlInputElement = e
+                .target()
+                .and_then(|t| t.dyn_into::<HtmlInputElement>().ok())?;
+            let value = input.value_as_number();
+            value.is_finite().then(|| msg(value as f32))
+        })
+    }
 }
@@ -1,9 +1,10 @@
 use crate::components::{Icon, LayerSelect};
-use crate::model::LayerFile;
+use crate::model::{guess_layers, LayerFile, LayerOrder};
 use gloo_storage::{LocalStorage, Storage};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use yew::{html, Callback, Component, Context, Html, Properties};
+use yew_utils::components::drop_down::DropDown;
 
 use crate::model::SampleFile;
 
@@ -25,6 +26,7 @@ pub enum Msg {
     Swap(usize, usize),
     Next,
     GuessLayers,
+    LayerOrderChanged(LayerOrder),
     Previous,
 }
 
@@ -32,30 +34,24 @@ pub enum Msg {
 #[derive(Default, Serialize, Deserialize)]
 pub struct StepSelectLayers {
     pub layer_files: Vec<LayerFile>,
+
+    /// Order of the layers sharing the same root note, when guessing them
+    #[serde(default)]
+    pub layer_order: LayerOrder,
 }
 
 impl From<Vec<SampleFile>> for StepSelectLayers {
     fn from(sample_files: Vec<SampleFile>) -> Self {
-        // Initiate the list of layer files from the list of files with roots
-        let layer_files: Vec<LayerFile> = sample_files
-            .iter()
-            // Sort by root note (group_by needs it)
-            .sorted_by(|a, b| a.root.cmp(&b.root))
-            // group by root note
-            .group_by(|f| f.root)
-            .into_iter()
-            .flat_map(|(_, group)| {
-                group
-                    // Sort each note with the same root per file name
-                    .sorted_by(|a, b| a.file.cmp(&b.file))
-                    .enumerate()
-                    // Assign a different layer to each note with the same root,
-                    // based on the sample alphabetical order
-                    .map(|(index, file)| LayerFile::from_sample_file(file.clone(), index % 4))
-            })
-            .sorted_by(|a, b| a.file.cmp(&b.file))
-            .collect();
-        Self { layer_files }
+        Self::new(&sample_files, LayerOrder::default())
+    }
+}
+
+impl StepSelectLayers {
+    fn new(sample_files: &[SampleFile], layer_order: LayerOrder) -> Self {
+        Self {
+            layer_files: guess_layers(sample_files, layer_order),
+            layer_order,
+        }
     }
 }
 
@@ -102,7 +98,11 @@ impl Component for StepSelectLayers {
                 false
             }
             Msg::GuessLayers => {
-                *self = ctx.props().files.clone().into();
+                *self = Self::new(&ctx.props().files, self.layer_order);
+                true
+            }
+            Msg::LayerOrderChanged(layer_order) => {
+                *self = Self::new(&ctx.props().files, layer_order);
                 true
             }
         };
@@ -140,8 +140,38 @@ impl Component for StepSelectLayers {
             _ => None,
         };
 
+        let loudness_known = ctx
+            .props()
+            .files
+            .iter()
+            .all(|f| f.sample_info().is_some_and(|info| info.loudness.is_some()));
+        let layer_order_help = match (self.layer_order, loudness_known) {
+            (LayerOrder::FileName, _) => {
+                "The samples with the same root note are layered by file name."
+            }
+            (LayerOrder::Loudness, true) => {
+                "The samples with the same root note are layered from the softest to the loudest."
+            }
+            (LayerOrder::Loudness, false) => {
+                "The loudness of some samples is unknown, add the sample files again to measure it."
+            }
+        };
+
         html! {
             <>
+                <div class="field">
+                    <label class="label">{"Layer Order"}</label>
+                    <div class="control">
+                        <div class="select">
+                            <DropDown<LayerOrder>
+                                initial={self.layer_order}
+                                options={vec![LayerOrder::FileName, LayerOrder::Loudness]}
+                                selection_changed={ctx.link().callback(Msg::LayerOrderChanged)}
+                            />
+                        </div>
+                    </div>
+                    <p class="help">{layer_order_help}</p>
+                </div>
                 <LayerSelect
                     label={"All"}
                     initial={all_layers}
@@ -175,7 +205,7 @@ impl Component for StepSelectLayers {
     }
 
     fn changed(&mut self, ctx: &Context<Self>) -> bool {
-        *self = ctx.props().files.clone().into();
+        *self = Self::new(&ctx.props().files, self.layer_order);
         true
     }
 }
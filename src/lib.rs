@@ -1,6 +1,9 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![doc = include_str!("../README.md")]
 
+#[cfg(all(feature = "cli", not(target_arch = "wasm32")))]
+pub mod cli;
+#[cfg(feature = "web")]
 pub mod components;
 pub mod model;
 pub mod utils;
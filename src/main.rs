@@ -1,4 +1,18 @@
+#[cfg(all(target_arch = "wasm32", feature = "web"))]
 fn main() {
     wasm_logger::init(wasm_logger::Config::default());
     yew::start_app::<mpc_valet::components::App>();
 }
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "cli"))]
+fn main() -> std::process::ExitCode {
+    mpc_valet::cli::run()
+}
+
+#[cfg(not(any(
+    all(target_arch = "wasm32", feature = "web"),
+    all(not(target_arch = "wasm32"), feature = "cli")
+)))]
+fn main() {
+    eprintln!("mpc_valet was built without its interface, enable the web or cli feature");
+}
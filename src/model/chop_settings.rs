@@ -0,0 +1,220 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{bundled_sample_name, detect_transients, equal_slices, Wav};
+
+use super::{ImportWarning, Keygroup, KeygroupProgram, Layer, SampleLibrary};
+
+/// How a loop is chopped into slices.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum ChopMode {
+    /// Cut at each transient.
+    #[default]
+    Transients,
+
+    /// Slices of the same length.
+    Equal,
+}
+
+impl Display for ChopMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChopMode::Transients => write!(f, "Detect Transients"),
+            ChopMode::Equal => write!(f, "Equal Slices"),
+        }
+    }
+}
+
+/// Settings to chop a loop into slices played across the keys.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct ChopSettings {
+    /// How the loop is chopped.
+    pub mode: ChopMode,
+
+    /// Rise of the level starting a slice, in dB (transients).
+    pub sensitivity: f32,
+
+    /// Shortest slice, in seconds (transients).
+    pub min_length: f32,
+
+    /// Number of slices (equal slices).
+    pub count: usize,
+
+    /// Key playing the first slice.
+    pub start_note: u8,
+}
+
+impl Default for ChopSettings {
+    fn default() -> Self {
+        Self {
+            mode: ChopMode::Transients,
+            sensitivity: 12.0,
+            min_length: 0.05,
+            count: 16,
+            start_note: 36,
+        }
+    }
+}
+
+impl ChopSettings {
+    /// Regions of the slices of a loop, in frames.
+    pub fn slices(&self, wav: &Wav<'_>) -> Vec<Range<usize>> {
+        match self.mode {
+            ChopMode::Transients => detect_transients(wav, self.sensitivity, self.min_length),
+            ChopMode::Equal => equal_slices(wav.frames(), self.count),
+        }
+    }
+
+    /// Build a keygroup program playing one slice of a loop on each key.
+    ///
+    /// The slices are played from the start note, at their original pitch. They
+    /// are regions of the loop sample, so the audio is not rewritten. The content
+    /// of the loop must be in the library. The slices past the end of the
+    /// keyboard are dropped, with a warning.
+    pub fn chop(
+        &self,
+        file: &str,
+        library: &SampleLibrary,
+    ) -> Result<(KeygroupProgram, Vec<ImportWarning>)> {
+        let content = library.wav(file)?;
+        let wav = Wav::parse(&content).with_context(|| format!("Failed to read {}", file))?;
+        let slices = self.slices(&wav);
+        if slices.is_empty() {
+            bail!("{} is empty", file);
+        }
+        let keys = (self.start_note..=127).len();
+        let mut warnings = Vec::new();
+        if slices.len() > keys {
+            warnings.push(ImportWarning::new(
+                file,
+                format!(
+                    "{} of the {} slices are past the end of the keyboard and were dropped",
+                    slices.len() - keys,
+                    slices.len()
+                ),
+            ));
+        }
+
+        let keygroups = (self.start_note..=127)
+            .zip(slices)
+            .map(|(key, slice)| Keygroup {
+                fixed_pitch: true,
+                ..Keygroup::new(
+                    key..=key,
+                    [
+                        Some(Layer {
+                            sample_keys: Some(key..=key),
+                            sample_region: Some(slice.clone()),
+                            sample_slice: Some(slice),
+                            ..Layer::new(file.to_string(), key, 0..=127)
+                        }),
+                        None,
+                        None,
+                        None,
+                    ],
+                )
+            })
+            .collect();
+
+        let name = bundled_sample_name(file);
+        let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+        Ok((
+            KeygroupProgram {
+                name: stem.to_string(),
+                keygroups,
+            },
+            warnings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::{write_wav, WavFormat};
+    use itertools::Itertools;
+
+    fn library() -> SampleLibrary {
+        let format = WavFormat {
+            sample_rate: 1000,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &[0; 2000]).unwrap();
+        let mut library = SampleLibrary::default();
+        library.insert("Loops/Break.wav".to_string(), wav);
+        library
+    }
+
+    #[test]
+    fn chop_test() {
+        let settings = ChopSettings {
+            mode: ChopMode::Equal,
+            count: 4,
+            start_note: 60,
+            ..Default::default()
+        };
+
+        let library = library();
+        let (mut program, warnings) = settings.chop("Loops/Break.wav", &library).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(program.name, "Break");
+        let regions = program
+            .layers()
+            .map(|layer| (layer.root, layer.sample_region.clone().unwrap()))
+            .collect_vec();
+        assert_eq!(
+            regions,
+            vec![
+                (60, 0..250),
+                (61, 250..500),
+                (62, 500..750),
+                (63, 750..1000)
+            ]
+        );
+        assert!(program.keygroups.iter().all(|kg| kg.fixed_pitch));
+        // Each slice keeps its key
+        program.guess_ranges(0.5);
+        let ranges = program
+            .keygroups
+            .iter()
+            .map(|kg| kg.range.clone())
+            .collect_vec();
+        assert_eq!(ranges, vec![60..=60, 61..=61, 62..=62, 63..=63]);
+        // Trimming and resetting the regions keep the slices
+        program.trim_silence(&library, -60.0, 0.0).unwrap();
+        program.reset_sample_regions();
+        let slices = program
+            .layers()
+            .map(|layer| (layer.root, layer.sample_region.clone().unwrap()))
+            .collect_vec();
+        assert_eq!(slices, regions);
+    }
+
+    #[test]
+    fn chop_keyboard_end_test() {
+        let settings = ChopSettings {
+            mode: ChopMode::Equal,
+            count: 8,
+            start_note: 125,
+            ..Default::default()
+        };
+
+        let (program, warnings) = settings.chop("Loops/Break.wav", &library()).unwrap();
+
+        assert_eq!(program.keygroups.len(), 3);
+        assert_eq!(
+            warnings,
+            vec![ImportWarning::new(
+                "Loops/Break.wav",
+                "5 of the 8 slices are past the end of the keyboard and were dropped"
+            )]
+        );
+        assert!(settings.chop("Missing.wav", &library()).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::io::{Seek, Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use xmltree::EmitterConfig;
+
+use crate::utils::{
+    bundle_layer, bundle_program, make_drum_program, write_zip, BundleFile, TargetFormat,
+};
+
+use super::{Layer, LayerVelocityMode, Pad, PadFile, SampleLibrary};
+
+/// A drum program is a set of samples assigned to pads.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrumProgram {
+    /// Name of the drum program.
+    pub name: String,
+
+    /// Assigned pads of this program, sorted by pad.
+    pub pads: Vec<Pad>,
+}
+
+impl From<Vec<PadFile>> for DrumProgram {
+    /// Stack the files assigned to the same pad as velocity layers.
+    ///
+    /// Only the first 4 files of each pad are used.
+    fn from(pad_files: Vec<PadFile>) -> Self {
+        let mut pads = BTreeMap::<u8, Pad>::new();
+        for file in pad_files {
+            let pad = pads
+                .entry(file.pad)
+                .or_insert_with(|| Pad::new(file.pad, Default::default()));
+            if let Some(layer) = pad.layers.iter_mut().find(|l| l.is_none()) {
+                *layer = Some(Layer::new(file.file, file.root, 0..=127));
+            }
+        }
+
+        let mut program = Self {
+            name: String::new(),
+            pads: pads.into_values().collect(),
+        };
+        program.set_velocity_layer_mode(&LayerVelocityMode::Automatic);
+        program
+    }
+}
+
+impl DrumProgram {
+    pub fn export<W: Write>(&self, w: W) -> Result<()> {
+        let program = make_drum_program(&self.name, &self.pads)?;
+        let mut cfg = EmitterConfig::new();
+        cfg.perform_indent = true;
+
+        program.write_with_config(w, cfg)?;
+        Ok(())
+    }
+
+    /// Export the drum program as a zip bundle, with its samples.
+    ///
+    /// The content of every sample file of the program must be in the library.
+    ///
+    /// The samples are converted to the target format, and the loop crossfades
+    /// are baked into them.
+    pub fn export_bundle<W: Write + Seek>(
+        &self,
+        w: W,
+        library: &SampleLibrary,
+        format: &TargetFormat,
+    ) -> Result<()> {
+        write_zip(w, &self.bundle_files("", library, format)?)
+    }
+
+    /// Files of the program and its samples, stored in a folder of a bundle.
+    ///
+    /// The folder is either empty or ends with a slash.
+    pub fn bundle_files<'a>(
+        &self,
+        folder: &str,
+        library: &'a SampleLibrary,
+        format: &TargetFormat,
+    ) -> Result<Vec<BundleFile<'a>>> {
+        // The samples are next to the program in the bundle
+        let mut program = self.clone();
+        for layer in program.layers_mut() {
+            bundle_layer(layer, library, format)?;
+        }
+        let mut xpm = Vec::new();
+        program.export(&mut xpm)?;
+
+        bundle_program(
+            folder,
+            &self.name,
+            xpm,
+            self.layers().zip(program.layers()),
+            library,
+            format,
+        )
+    }
+
+    /// Iterate over the assigned layers of all the pads.
+    pub fn layers(&self) -> impl Iterator<Item = &Layer> {
+        self.pads.iter().flat_map(|pad| pad.layers.iter().flatten())
+    }
+
+    /// Iterate over the assigned layers of all the pads (mutable).
+    pub fn layers_mut(&mut self) -> impl Iterator<Item = &mut Layer> {
+        self.pads
+            .iter_mut()
+            .flat_map(|pad| pad.layers.iter_mut().flatten())
+    }
+
+    pub fn set_velocity_layer_mode(&mut self, mode: &LayerVelocityMode) {
+        for pad in self.pads.iter_mut() {
+            pad.set_velocity_layer_mode(mode);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+
+    fn pad_file(file: &str, pad: u8) -> PadFile {
+        PadFile {
+            file: file.to_string(),
+            root: 60,
+            pad,
+        }
+    }
+
+    #[test]
+    fn drum_program_from_pad_files_test() {
+        let program: DrumProgram = vec![
+            pad_file("snare.wav", 1),
+            pad_file("kick soft.wav", 0),
+            pad_file("kick loud.wav", 0),
+        ]
+        .into();
+
+        assert_eq!(
+            program.pads,
+            vec![
+                Pad::new(
+                    0,
+                    [
+                        Some(Layer::new("kick soft.wav".to_string(), 60, 0..=63)),
+                        Some(Layer::new("kick loud.wav".to_string(), 60, 64..=127)),
+                        None,
+                        None,
+                    ]
+                ),
+                Pad::new(
+                    1,
+                    [
+                        Some(Layer::new("snare.wav".to_string(), 60, 0..=127)),
+                        None,
+                        None,
+                        None,
+                    ]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::borrow::Cow;
+use std::io::{Seek, Write};
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use xmltree::EmitterConfig;
+
+use crate::utils::{
+    bundle_file_name, bundled_sample_name, make_expansion, write_zip, BundleFile, TargetFormat,
+};
+
+use super::{KeygroupProgram, SampleLibrary};
+
+/// A program of an expansion.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpansionProgram {
+    /// The keygroup program.
+    ///
+    /// Its sample files are prefixed by the program name, so that the
+    /// programs of the expansion can use samples with the same name.
+    pub program: KeygroupProgram,
+
+    /// Preview audio file of the program.
+    pub preview: Option<String>,
+}
+
+/// An MPC expansion, a set of programs distributed together.
+///
+/// The content of the sample, cover and preview files is stored in a
+/// library dedicated to the expansion.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Expansion {
+    /// Title of the expansion, also used as its folder name.
+    pub title: String,
+
+    /// Manufacturer of the expansion.
+    pub manufacturer: String,
+
+    /// Version of the expansion.
+    pub version: String,
+
+    /// Description of the expansion.
+    pub description: String,
+
+    /// Cover image file.
+    pub cover: Option<String>,
+
+    /// Programs of the expansion.
+    pub programs: Vec<ExpansionProgram>,
+
+    /// Format the samples are converted to.
+    #[serde(default)]
+    pub sample_format: TargetFormat,
+}
+
+impl Expansion {
+    /// Unique identifier of the expansion, made of its manufacturer and title.
+    ///
+    /// The parts with no usable character are left out.
+    pub fn identifier(&self) -> String {
+        let sanitize = |text: &str| -> String {
+            text.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        };
+        std::iter::once("com".to_string())
+            .chain([sanitize(&self.manufacturer), sanitize(&self.title)])
+            .filter(|part| !part.is_empty())
+            .join(".")
+    }
+
+    /// Add a program to the expansion, or replace the one with the same name.
+    ///
+    /// The content of its samples is shared from the library to the expansion library.
+    pub fn add_program(
+        &mut self,
+        program: &KeygroupProgram,
+        library: &SampleLibrary,
+        expansion_library: &mut SampleLibrary,
+    ) {
+        let name = program.name.clone();
+        let mut program = program.clone();
+        for layer in program.layers_mut() {
+            let file = format!("{}/{}", name, bundled_sample_name(&layer.file));
+            expansion_library.share(file.clone(), library, &layer.file);
+            layer.file = file;
+        }
+
+        match self
+            .programs
+            .iter_mut()
+            .find(|p| p.program.name == program.name)
+        {
+            Some(existing) => existing.program = program,
+            None => self.programs.push(ExpansionProgram {
+                program,
+                preview: None,
+            }),
+        }
+    }
+
+    /// Files of the expansion, in a folder named after its title.
+    ///
+    /// Each program is in its own sub-folder, next to its samples and preview.
+    pub fn files<'a>(&self, library: &'a SampleLibrary) -> Result<Vec<BundleFile<'a>>> {
+        if self.title.trim().is_empty() {
+            bail!("The expansion needs a title");
+        }
+        let folder = format!("{}/", bundle_file_name(&self.title));
+
+        let mut xml = Vec::new();
+        let mut cfg = EmitterConfig::new();
+        cfg.perform_indent = true;
+        make_expansion(self).write_with_config(&mut xml, cfg)?;
+        let mut files = vec![(format!("{}Expansion.xml", folder), Cow::Owned(xml))];
+
+        for file in self.cover.iter() {
+            files.push(library_file(&folder, file, library)?);
+        }
+
+        for program in self.programs.iter() {
+            let name = bundle_file_name(&program.program.name);
+            let program_folder = format!("{}{}/", folder, name);
+            files.extend(program.program.bundle_files(
+                &program_folder,
+                library,
+                &self.sample_format,
+            )?);
+            if let Some(preview) = &program.preview {
+                let (_, content) = library_file("", preview, library)?;
+                files.push((
+                    format!("{}[Previews]/{}.xpm.wav", program_folder, name),
+                    content,
+                ));
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Export the expansion as a zip file.
+    pub fn export<W: Write + Seek>(&self, w: W, library: &SampleLibrary) -> Result<()> {
+        write_zip(w, &self.files(library)?)
+    }
+}
+
+/// Read a file of the library to store it in a folder.
+fn library_file<'a>(
+    folder: &str,
+    file: &str,
+    library: &'a SampleLibrary,
+) -> Result<BundleFile<'a>> {
+    match library.get(file) {
+        Some(content) => Ok((
+            format!("{}{}", folder, bundled_sample_name(file)),
+            Cow::Borrowed(content),
+        )),
+        None => bail!("The content of {} is not available, add it again", file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Keygroup, Layer};
+
+    pub use super::*;
+
+    #[test]
+    fn expansion_files_test() {
+        let program = KeygroupProgram {
+            name: "Piano".to_string(),
+            keygroups: vec![Keygroup::new(
+                0..=127,
+                [
+                    Some(Layer::new("Samples/C3.wav".to_string(), 48, 0..=127)),
+                    None,
+                    None,
+                    None,
+                ],
+            )],
+        };
+        let mut library = SampleLibrary::default();
+        library.insert("Samples/C3.wav".to_string(), vec![1, 2]);
+
+        let mut expansion_library = SampleLibrary::default();
+        expansion_library.insert("cover.jpg".to_string(), vec![3]);
+        expansion_library.insert("preview.wav".to_string(), vec![4]);
+        let mut expansion = Expansion {
+            title: "Keys".to_string(),
+            manufacturer: "Valet".to_string(),
+            version: "1.0.0".to_string(),
+            description: "Some keys".to_string(),
+            cover: Some("cover.jpg".to_string()),
+            programs: vec![],
+            sample_format: TargetFormat::default(),
+        };
+        expansion.add_program(&program, &library, &mut expansion_library);
+        expansion.programs[0].preview = Some("preview.wav".to_string());
+
+        let files: Vec<String> = expansion
+            .files(&expansion_library)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+
+        assert_eq!(expansion.identifier(), "com.valet.keys");
+        expansion.manufacturer = String::new();
+        assert_eq!(expansion.identifier(), "com.keys");
+        assert_eq!(
+            files,
+            vec![
+                "Keys/Expansion.xml",
+                "Keys/cover.jpg",
+                "Keys/Piano/Piano.xpm",
+                "Keys/Piano/C3.wav",
+                "Keys/Piano/[Previews]/Piano.xpm.wav",
+            ]
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// Part of an imported file that could not be mapped to the model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportWarning {
+    /// Where the problem was found (ie. "Instrument 2, Layer 1").
+    pub location: String,
+
+    /// What could not be mapped.
+    pub message: String,
+}
+
+impl ImportWarning {
+    pub fn new(location: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            location: location.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ImportWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
@@ -1,8 +1,9 @@
 use std::ops::RangeInclusive;
 
-use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use crate::utils::Articulation;
+
 use super::{Layer, LayerVelocityMode};
 
 /// A keygroup is a set of samples assign to a note range on a keyboard.
@@ -15,6 +16,22 @@ pub struct Keygroup {
     ///
     /// Each layer can hold a sound sample and be of its own root note.
     pub layers: [Option<Layer>; 4],
+
+    /// Tempo of the samples, in thousandths of BPM.
+    #[serde(default)]
+    pub tempo: Option<u32>,
+
+    /// Follow the project tempo on the MPC, when the tempo is known.
+    #[serde(default)]
+    pub warp: bool,
+
+    /// Play the samples at their original pitch, whatever the played note.
+    #[serde(default)]
+    pub fixed_pitch: bool,
+
+    /// How the samples are played, the settings of the template are kept when None.
+    #[serde(default)]
+    pub articulation: Option<Articulation>,
 }
 
 impl Default for Keygroup {
@@ -22,31 +39,43 @@ impl Default for Keygroup {
         Self {
             range: 0..=127,
             layers: Default::default(),
+            tempo: None,
+            warp: false,
+            fixed_pitch: false,
+            articulation: None,
         }
     }
 }
 
 impl PartialOrd for Keygroup {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.layers
-            .get(0)
-            .as_ref()
-            .partial_cmp(&other.layers.get(0).as_ref())
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Keygroup {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.layers
-            .get(0)
-            .as_ref()
-            .cmp(&other.layers.get(0).as_ref())
+        self.layers.first().cmp(&other.layers.first())
     }
 }
 
 impl Keygroup {
     pub fn new(range: RangeInclusive<u8>, layers: [Option<Layer>; 4]) -> Self {
-        Self { range, layers }
+        Self {
+            range,
+            layers,
+            ..Default::default()
+        }
+    }
+
+    /// Tempo of the samples, in BPM.
+    pub fn bpm(&self) -> Option<f32> {
+        self.tempo.map(|tempo| tempo as f32 / 1000.0)
+    }
+
+    /// Warp the samples to the project tempo, when their tempo is known.
+    pub fn set_warp(&mut self, warp: bool) {
+        self.warp = warp && self.tempo.is_some();
     }
 
     /// Get the first layer with an assigned sample.
@@ -65,33 +94,17 @@ impl Keygroup {
     }
 
     /// Choose the way the velocity range should be assigned accross the layers.
+    ///
+    /// The velocity ranges embedded in the sample files are kept in the
+    /// automatic mode.
     pub fn set_velocity_layer_mode(&mut self, mode: &LayerVelocityMode) {
-        match mode {
-            LayerVelocityMode::Unison => {
-                for layer in self.layers.iter_mut().filter_map(|l| l.as_mut()) {
-                    layer.velocity = 0..=127;
-                }
-            }
-            LayerVelocityMode::Automatic => {
-                let active_layers = self
-                    .layers
-                    .iter_mut()
-                    .filter_map(|l| l.as_mut())
-                    .collect_vec();
-                let layer_count = active_layers.len();
-                if layer_count == 0 {
-                    return;
-                }
-
-                for (index, mut layer) in active_layers.into_iter().enumerate() {
-                    let start: u8 = (128 * index / layer_count)
-                        .try_into()
-                        .expect("The lower bound velocity went out of bound");
-                    let end: u8 = ((128 * (index + 1) / layer_count) - 1)
-                        .try_into()
-                        .expect("The upper bound velocity went out of bound");
-                    layer.velocity = start..=end;
-                }
+        mode.assign_velocities(&mut self.layers);
+        if *mode != LayerVelocityMode::Automatic {
+            return;
+        }
+        for layer in self.layers.iter_mut().flatten() {
+            if let Some(velocity) = &layer.sample_velocity {
+                layer.velocity = velocity.clone();
             }
         }
     }
@@ -135,6 +148,11 @@ mod tests {
         [Some(0..=31), Some(32..=63), Some(64..=95), Some(96..=127)],
         [Some(0..=127), Some(0..=127), Some(0..=127), Some(0..=127)],
     )]
+    #[case(
+        [Some(Layer { sample_velocity: Some(1..=100), ..Default::default() }), Some(Layer::default()), None, None],
+        [Some(1..=100), Some(64..=127), None, None],
+        [Some(0..=127), Some(0..=127), None, None],
+    )]
     fn layer_velocity_test(
         #[case] layers: [Option<Layer>; 4],
         #[case] automatic_velocity: [Option<RangeInclusive<u8>>; 4],
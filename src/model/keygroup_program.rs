@@ -1,11 +1,16 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::io::Write;
-use xmltree::EmitterConfig;
+use std::io::{Read, Seek, Write};
+use xmltree::{Element, EmitterConfig};
 
-use crate::utils::{build_ranges, make_program};
+use crate::utils::{
+    audible_region, build_constrained_ranges, bundle_layer, bundle_program, classify_envelope,
+    detect_tempo, find_loop, leveling_gains, make_dspreset, make_program, make_sf2, make_sfz,
+    measure_loudness, parse_dspreset, parse_program, parse_sf2, parse_sfz, write_zip, Articulation,
+    BundleFile, TargetFormat, Wav,
+};
 
-use super::{Keygroup, Layer, LayerFile, LayerVelocityMode};
+use super::{ImportWarning, Keygroup, Layer, LayerFile, LayerVelocityMode, SampleLibrary};
 
 /// A keygroup program is an instrument based on samples.
 ///
@@ -19,6 +24,16 @@ pub struct KeygroupProgram {
     pub keygroups: Vec<Keygroup>,
 }
 
+impl From<Vec<LayerFile>> for KeygroupProgram {
+    fn from(layer_files: Vec<LayerFile>) -> Self {
+        let mut program = KeygroupProgram::default();
+        program.insert_layer_files(layer_files);
+        program.sort_keygroups();
+        program.guess_ranges(0.5);
+        program
+    }
+}
+
 impl KeygroupProgram {
     pub fn insert_layer_files(&mut self, files: Vec<LayerFile>) {
         for file in files.into_iter() {
@@ -27,7 +42,20 @@ impl KeygroupProgram {
     }
 
     pub fn insert_layer_file(&mut self, file: LayerFile) {
-        let new_layer = Some(Layer::new(file.file, file.root, 0..=127));
+        let new_layer = Some(Layer {
+            file: file.file,
+            root: file.root,
+            velocity: file.velocity.clone().unwrap_or(0..=127),
+            tune: file.tune,
+            sample_keys: file.keys,
+            sample_velocity: file.velocity,
+            sample_region: None,
+            sample_slice: None,
+            sample_loop: None,
+            loop_crossfade: 0,
+            volume: 0,
+            volume_before_normalization: None,
+        });
         // Look for a keygroup with this layer file
         if let Some(kg) = self.keygroups.iter_mut().find(|kg| {
             kg.layers
@@ -52,24 +80,42 @@ impl KeygroupProgram {
     /// The pitch preference should be between 0 and 1 and is used to choose between
     /// pitching down or pitching up the samples. 0.5 means that each root note
     /// will be at the "center" of its keygroup.
+    ///
+    /// The note ranges embedded in the sample files are kept, and the other
+    /// keygroups fill the rest of the keyboard. The keygroups played at a
+    /// fixed pitch keep their range.
     pub fn guess_ranges(&mut self, pitch_preference: f32) {
-        // keep only the keygroups with root note, and iterate in the root notes
-        let (keygroups_with_root_note, root_notes): (Vec<_>, Vec<_>) = self
+        // keep only the pitched keygroups with root note, and iterate in the root notes
+        let mut keygroups_with_root_note = self
             .keygroups
             .iter_mut()
+            .filter(|kg| !kg.fixed_pitch)
             .filter_map(|kg| {
                 kg.layers[0]
                     .as_ref()
                     .map(|layer| layer.root)
-                    .map(|root_note| (kg, root_note))
+                    .map(|root| (kg, root))
             })
-            .unzip();
+            .collect::<Vec<_>>();
+        keygroups_with_root_note.sort_by_key(|(_, root)| *root);
+        let root_notes = keygroups_with_root_note
+            .iter()
+            .map(|(_, root)| *root)
+            .collect::<Vec<_>>();
+        let sample_keys = keygroups_with_root_note
+            .iter()
+            .map(|(kg, _)| {
+                kg.layers[0]
+                    .as_ref()
+                    .and_then(|layer| layer.sample_keys.clone())
+            })
+            .collect::<Vec<_>>();
 
-        // guess the ranges from the root notes
-        let ranges = build_ranges(&root_notes, pitch_preference);
+        // guess the ranges from the root notes and the embedded ranges
+        let ranges = build_constrained_ranges(&root_notes, &sample_keys, pitch_preference);
 
         // assign the ranges to the keygroups with root notes
-        for (kg, range) in keygroups_with_root_note.into_iter().zip(ranges.into_iter()) {
+        for ((kg, _), range) in keygroups_with_root_note.into_iter().zip(ranges) {
             kg.range = range;
         }
     }
@@ -83,6 +129,134 @@ impl KeygroupProgram {
         Ok(())
     }
 
+    /// Export the keygroup program as a zip bundle, with its samples.
+    ///
+    /// The content of every sample file of the program must be in the library.
+    ///
+    /// The samples are converted to the target format, and the loop crossfades
+    /// are baked into them.
+    pub fn export_bundle<W: Write + Seek>(
+        &self,
+        w: W,
+        library: &SampleLibrary,
+        format: &TargetFormat,
+    ) -> Result<()> {
+        write_zip(w, &self.bundle_files("", library, format)?)
+    }
+
+    /// Files of the program and its samples, stored in a folder of a bundle.
+    ///
+    /// The folder is either empty or ends with a slash.
+    pub fn bundle_files<'a>(
+        &self,
+        folder: &str,
+        library: &'a SampleLibrary,
+        format: &TargetFormat,
+    ) -> Result<Vec<BundleFile<'a>>> {
+        // The samples are next to the program in the bundle
+        let mut program = self.clone();
+        for layer in program.layers_mut() {
+            bundle_layer(layer, library, format)?;
+        }
+        let mut xpm = Vec::new();
+        program.export(&mut xpm)?;
+
+        bundle_program(
+            folder,
+            &self.name,
+            xpm,
+            self.layers().zip(program.layers()),
+            library,
+            format,
+        )
+    }
+
+    /// Export the keygroup program as an .sfz file.
+    pub fn export_sfz<W: Write>(&self, mut w: W) -> Result<()> {
+        let sfz = make_sfz(&self.name, &self.keygroups)?;
+        w.write_all(sfz.as_bytes())?;
+        Ok(())
+    }
+
+    /// Import a keygroup program from an .sfz file.
+    ///
+    /// The returned warnings list the opcodes that could not be imported.
+    pub fn import_sfz<R: Read>(mut r: R, name: &str) -> Result<(Self, Vec<ImportWarning>)> {
+        let mut sfz = String::new();
+        r.read_to_string(&mut sfz)?;
+        let (keygroups, warnings) = parse_sfz(&sfz)?;
+        let program = Self {
+            name: name.to_string(),
+            keygroups,
+        };
+        Ok((program, warnings))
+    }
+
+    /// Export the keygroup program as a Decent Sampler .dspreset file.
+    pub fn export_dspreset<W: Write>(&self, w: W) -> Result<()> {
+        let preset = make_dspreset(&self.keygroups);
+        let mut cfg = EmitterConfig::new();
+        cfg.perform_indent = true;
+
+        preset.write_with_config(w, cfg)?;
+        Ok(())
+    }
+
+    /// Import a keygroup program from a Decent Sampler .dspreset file.
+    ///
+    /// The returned warnings list the attributes that could not be imported.
+    pub fn import_dspreset<R: Read>(r: R, name: &str) -> Result<(Self, Vec<ImportWarning>)> {
+        let preset = Element::parse(r)?;
+        let (keygroups, warnings) = parse_dspreset(&preset)?;
+        let program = Self {
+            name: name.to_string(),
+            keygroups,
+        };
+        Ok((program, warnings))
+    }
+
+    /// Export the keygroup program as a SoundFont .sf2 file.
+    ///
+    /// The content of every sample file of the program must be in the library.
+    pub fn export_sf2<W: Write>(&self, mut w: W, library: &SampleLibrary) -> Result<()> {
+        let sf2 = make_sf2(&self.name, &self.keygroups, library)?;
+        w.write_all(&sf2)?;
+        Ok(())
+    }
+
+    /// Import a preset of a SoundFont .sf2 file.
+    ///
+    /// The samples are extracted from the SoundFont as WAV files. The returned
+    /// warnings list the generators that could not be imported.
+    pub fn import_sf2(
+        sf2: &[u8],
+        preset: usize,
+    ) -> Result<(Self, SampleLibrary, Vec<ImportWarning>)> {
+        parse_sf2(sf2, preset)
+    }
+
+    /// Import a keygroup program from an .xpm file.
+    ///
+    /// The returned warnings list what could not be imported.
+    pub fn import<R: Read>(r: R) -> Result<(Self, Vec<ImportWarning>)> {
+        let program = Element::parse(r)?;
+        parse_program(&program)
+    }
+
+    /// Iterate over the assigned layers of all the keygroups.
+    pub fn layers(&self) -> impl Iterator<Item = &Layer> {
+        self.keygroups
+            .iter()
+            .flat_map(|kg| kg.layers.iter().flatten())
+    }
+
+    /// Iterate over the assigned layers of all the keygroups (mutable).
+    pub fn layers_mut(&mut self) -> impl Iterator<Item = &mut Layer> {
+        self.keygroups
+            .iter_mut()
+            .flat_map(|kg| kg.layers.iter_mut().flatten())
+    }
+
     pub fn layer_count(&self) -> usize {
         self.keygroups
             .iter()
@@ -96,4 +270,247 @@ impl KeygroupProgram {
             keygroup.set_velocity_layer_mode(mode);
         }
     }
+
+    /// Skip the silence at the start and at the end of each layer sample.
+    ///
+    /// The threshold is in dB below the full scale, and the pre-roll in seconds.
+    /// The slices of a chopped loop are trimmed inside the slice.
+    pub fn trim_silence(
+        &mut self,
+        library: &SampleLibrary,
+        threshold: f32,
+        pre_roll: f32,
+    ) -> Result<()> {
+        for layer in self.layers_mut() {
+            let content = library.wav(&layer.file)?;
+            let wav =
+                Wav::parse(&content).with_context(|| format!("Failed to read {}", layer.file))?;
+            let slice = layer.sample_slice.clone().unwrap_or(0..wav.frames());
+            layer.sample_region =
+                audible_region(&wav, slice, threshold, pre_roll).or(layer.sample_slice.clone());
+        }
+        Ok(())
+    }
+
+    /// Find a seamless sustain loop in each layer sample.
+    ///
+    /// The loop is searched in the played region of the sample. The crossfade,
+    /// in seconds, is baked into the samples of the bundles.
+    pub fn find_loops(&mut self, library: &SampleLibrary, crossfade: f32) -> Result<()> {
+        for layer in self.layers_mut() {
+            let content = library.wav(&layer.file)?;
+            let wav =
+                Wav::parse(&content).with_context(|| format!("Failed to read {}", layer.file))?;
+            let region = layer.sample_region.clone().unwrap_or(0..wav.frames());
+            layer.sample_loop = find_loop(&wav, region);
+            layer.loop_crossfade =
+                (crossfade.max(0.0) * wav.format.sample_rate as f32).round() as usize;
+        }
+        Ok(())
+    }
+
+    /// Detect the tempo of the samples of each keygroup, for melodic loops.
+    ///
+    /// The tempo is detected on the played region of the first layer sample.
+    /// The warp is disabled on the keygroups without a tempo.
+    pub fn detect_tempo(&mut self, library: &SampleLibrary) -> Result<()> {
+        for keygroup in self.keygroups.iter_mut() {
+            let Some(layer) = keygroup.first_assigned_layer() else {
+                continue;
+            };
+            let content = library.wav(&layer.file)?;
+            let wav =
+                Wav::parse(&content).with_context(|| format!("Failed to read {}", layer.file))?;
+            let samples = wav.mono();
+            let region = layer.sample_region.clone().unwrap_or(0..samples.len());
+            let samples = &samples[region.start.min(samples.len())..region.end.min(samples.len())];
+            keygroup.tempo = detect_tempo(samples, wav.format.sample_rate)
+                .map(|bpm| (bpm * 1000.0).round() as u32);
+            keygroup.set_warp(keygroup.warp);
+        }
+        Ok(())
+    }
+
+    /// Warp the samples to the project tempo on the MPC.
+    ///
+    /// Only the keygroups with a detected tempo are warped.
+    pub fn set_warp(&mut self, warp: bool) {
+        for keygroup in self.keygroups.iter_mut() {
+            keygroup.set_warp(warp);
+        }
+    }
+
+    /// Choose how each keygroup is played from the envelope of its samples.
+    ///
+    /// The keygroups of percussive samples are played as one-shots, the ones
+    /// of sustained samples are held with a release. The envelope is analysed
+    /// on the played region of the first layer sample, and looped samples are
+    /// always sustained.
+    ///
+    /// The program is left untouched when a sample can't be read.
+    pub fn detect_articulation(&mut self, library: &SampleLibrary) -> Result<()> {
+        let mut articulations = Vec::with_capacity(self.keygroups.len());
+        for keygroup in self.keygroups.iter() {
+            let Some(layer) = keygroup.first_assigned_layer() else {
+                articulations.push(keygroup.articulation);
+                continue;
+            };
+            if layer.sample_loop.is_some() {
+                articulations.push(Some(Articulation::Sustained));
+                continue;
+            }
+            let content = library.wav(&layer.file)?;
+            let wav =
+                Wav::parse(&content).with_context(|| format!("Failed to read {}", layer.file))?;
+            let region = layer.sample_region.clone().unwrap_or(0..wav.frames());
+            articulations.push(classify_envelope(&wav, region));
+        }
+        for (keygroup, articulation) in self.keygroups.iter_mut().zip(articulations) {
+            keygroup.articulation = articulation;
+        }
+        Ok(())
+    }
+
+    /// Keep the playback settings of the template for all the keygroups.
+    pub fn reset_articulation(&mut self) {
+        for keygroup in self.keygroups.iter_mut() {
+            keygroup.articulation = None;
+        }
+    }
+
+    /// Play the samples without looping them.
+    pub fn reset_loops(&mut self) {
+        for layer in self.layers_mut() {
+            layer.sample_loop = None;
+            layer.loop_crossfade = 0;
+        }
+    }
+
+    /// Even out the volume of the keygroups across the keyboard.
+    ///
+    /// The volume of each keygroup is based on the played region of its loudest layer,
+    /// with its previous volume, and the same correction is added to all its layers
+    /// to keep their relative dynamics. The previous volumes are kept to be restored
+    /// by [`KeygroupProgram::reset_volume`].
+    pub fn normalize_volume(&mut self, library: &SampleLibrary) -> Result<()> {
+        let mut levels = Vec::new();
+        for keygroup in self
+            .keygroups
+            .iter()
+            .filter(|kg| kg.first_assigned_layer().is_some())
+        {
+            let mut level = f32::NEG_INFINITY;
+            for layer in keygroup.layers.iter().flatten() {
+                let content = library.wav(&layer.file)?;
+                let wav = Wav::parse(&content)
+                    .with_context(|| format!("Failed to read {}", layer.file))?;
+                let region = layer.sample_region.clone().unwrap_or(0..wav.frames());
+                let volume = layer.volume_before_normalization.unwrap_or(layer.volume);
+                level = level.max(measure_loudness(&wav, region).rms + volume as f32 / 10.0);
+            }
+            levels.push(level);
+        }
+
+        let keygroups = self
+            .keygroups
+            .iter_mut()
+            .filter(|kg| kg.first_assigned_layer().is_some());
+        for (keygroup, gain) in keygroups.zip(leveling_gains(&levels)) {
+            for layer in keygroup.layers.iter_mut().flatten() {
+                let volume = *layer
+                    .volume_before_normalization
+                    .get_or_insert(layer.volume);
+                layer.volume = volume.saturating_add((gain * 10.0).round() as i16);
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore the volumes from before the keyboard was evened out.
+    pub fn reset_volume(&mut self) {
+        for layer in self.layers_mut() {
+            if let Some(volume) = layer.volume_before_normalization.take() {
+                layer.volume = volume;
+            }
+        }
+    }
+
+    /// Play the whole sample of each layer, or its whole slice for a chopped loop.
+    pub fn reset_sample_regions(&mut self) {
+        for layer in self.layers_mut() {
+            layer.sample_region = layer.sample_slice.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::wav_from_points;
+
+    #[test]
+    fn detect_tempo_keeps_articulation_test() {
+        let layer = Layer {
+            sample_loop: Some(100..900),
+            ..Layer::new("Pad A2.wav".to_string(), 45, 0..=127)
+        };
+        let mut program = KeygroupProgram {
+            name: "Pad".to_string(),
+            keygroups: vec![Keygroup::new(0..=127, [Some(layer), None, None, None])],
+        };
+        let mut library = SampleLibrary::default();
+        library.insert(
+            "Pad A2.wav".to_string(),
+            wav_from_points(1000, 1, &[0; 1000]),
+        );
+
+        program.detect_tempo(&library).unwrap();
+
+        assert_eq!(program.keygroups[0].articulation, None);
+    }
+
+    /// A square wave at the given amplitude.
+    fn square(amplitude: i16) -> Vec<u8> {
+        let points = (0..1000)
+            .map(|frame| {
+                if frame % 2 == 0 {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            })
+            .collect::<Vec<_>>();
+        wav_from_points(1000, 1, &points)
+    }
+
+    #[test]
+    fn normalize_volume_test() {
+        let loud = Layer::new("Loud A2.wav".to_string(), 45, 0..=127);
+        let soft = Layer {
+            volume: -35,
+            ..Layer::new("Soft A3.wav".to_string(), 57, 0..=127)
+        };
+        let mut program = KeygroupProgram {
+            name: "Piano".to_string(),
+            keygroups: vec![
+                Keygroup::new(0..=50, [Some(loud), None, None, None]),
+                Keygroup::new(51..=60, [Some(soft), None, None, None]),
+                Keygroup::new(61..=127, [None, None, None, None]),
+            ],
+        };
+        let mut library = SampleLibrary::default();
+        library.insert("Loud A2.wav".to_string(), square(20000));
+        library.insert("Soft A3.wav".to_string(), square(10000));
+
+        program.normalize_volume(&library).unwrap();
+
+        // 6 dB quieter samples, played 3.5 dB quieter
+        let volumes = program.layers().map(|l| l.volume).collect::<Vec<_>>();
+        assert_eq!(volumes, vec![-48, 13]);
+
+        program.reset_volume();
+
+        let volumes = program.layers().map(|l| l.volume).collect::<Vec<_>>();
+        assert_eq!(volumes, vec![0, -35]);
+    }
 }
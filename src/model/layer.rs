@@ -1,4 +1,4 @@
-use std::ops::RangeInclusive;
+use std::ops::{Range, RangeInclusive};
 
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +16,42 @@ pub struct Layer {
 
     /// Velocity range where this layer should be active.
     pub velocity: RangeInclusive<u8>,
+
+    /// Fine tuning, in cents.
+    #[serde(default)]
+    pub tune: i8,
+
+    /// Note range embedded in the sample file, used instead of the guessed range.
+    #[serde(default)]
+    pub sample_keys: Option<RangeInclusive<u8>>,
+
+    /// Velocity range embedded in the sample file, used instead of the guessed range.
+    #[serde(default)]
+    pub sample_velocity: Option<RangeInclusive<u8>>,
+
+    /// Played region of the sample, in frames. The whole sample is played when None.
+    #[serde(default)]
+    pub sample_region: Option<Range<usize>>,
+
+    /// Slice of a chopped loop, in frames. The played region stays inside it.
+    #[serde(default)]
+    pub sample_slice: Option<Range<usize>>,
+
+    /// Sustain loop of the sample, in frames. The sample is not looped when None.
+    #[serde(default)]
+    pub sample_loop: Option<Range<usize>>,
+
+    /// Crossfade baked at the end of the loop in the bundles, in frames.
+    #[serde(default)]
+    pub loop_crossfade: usize,
+
+    /// Volume correction, in tenths of dB.
+    #[serde(default)]
+    pub volume: i16,
+
+    /// Volume correction before the keyboard was evened out, restored when it is undone.
+    #[serde(default)]
+    pub volume_before_normalization: Option<i16>,
 }
 
 impl Default for Layer {
@@ -24,6 +60,15 @@ impl Default for Layer {
             file: Default::default(),
             root: 0,
             velocity: 0..=127,
+            tune: 0,
+            sample_keys: None,
+            sample_velocity: None,
+            sample_region: None,
+            sample_slice: None,
+            sample_loop: None,
+            loop_crossfade: 0,
+            volume: 0,
+            volume_before_normalization: None,
         }
     }
 }
@@ -36,16 +81,22 @@ impl Ord for Layer {
 
 impl PartialOrd for Layer {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.root.partial_cmp(&other.root)
+        Some(self.cmp(other))
     }
 }
 
 impl Layer {
+    /// Linear gain of the volume correction.
+    pub fn gain(&self) -> f32 {
+        10_f32.powf(self.volume as f32 / 200.0)
+    }
+
     pub fn new(file: String, root: u8, velocity: RangeInclusive<u8>) -> Self {
         Self {
             file,
             root,
             velocity,
+            ..Default::default()
         }
     }
 }
@@ -1,6 +1,8 @@
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 
-use super::SampleFile;
+use super::{LayerOrder, SampleFile};
 
 /// A sample file with a root note, assigned to a layer
 #[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -13,24 +15,133 @@ pub struct LayerFile {
 
     /// Destination layer
     pub layer: usize,
+
+    /// Fine tuning of the root note, in cents.
+    #[serde(default)]
+    pub tune: i8,
+
+    /// Note range embedded in the sample file.
+    #[serde(default)]
+    pub keys: Option<RangeInclusive<u8>>,
+
+    /// Velocity range embedded in the sample file.
+    #[serde(default)]
+    pub velocity: Option<RangeInclusive<u8>>,
 }
 
 impl LayerFile {
     pub fn from_sample_file(file: SampleFile, layer: usize) -> Self {
+        let tune = file.tune();
+        let (keys, velocity) = match file.metadata() {
+            Some(metadata) => (metadata.keys.clone(), metadata.velocity.clone()),
+            None => (None, None),
+        };
         Self {
             file: file.file,
             root: file.root,
             layer,
+            tune,
+            keys,
+            velocity,
         }
     }
 }
 
 impl From<SampleFile> for LayerFile {
     fn from(file: SampleFile) -> Self {
-        Self {
-            file: file.file,
-            root: file.root,
-            layer: 0,
+        Self::from_sample_file(file, 0)
+    }
+}
+
+/// Assign a layer to each sample file.
+///
+/// The files sharing the same root note are assigned to different layers,
+/// in their alphabetical order or from the softest to the loudest.
+/// The alphabetical order is kept when the loudness of a file is not known.
+pub fn guess_layers(sample_files: &[SampleFile], order: LayerOrder) -> Vec<LayerFile> {
+    sample_files
+        .iter()
+        // Sort by root note (group_by needs it)
+        .sorted_by(|a, b| a.root.cmp(&b.root))
+        // group by root note
+        .chunk_by(|f| f.root)
+        .into_iter()
+        .flat_map(|(_, group)| {
+            // Sort each note with the same root per file name
+            let group = group.sorted_by(|a, b| a.file.cmp(&b.file)).collect_vec();
+            let loudness: Option<Vec<f32>> = group
+                .iter()
+                .map(|f| f.sample_info()?.loudness.map(|l| l.rms))
+                .collect();
+            let group = match (order, loudness) {
+                (LayerOrder::Loudness, Some(loudness)) => group
+                    .into_iter()
+                    .zip(loudness)
+                    .sorted_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(file, _)| file)
+                    .collect_vec(),
+                _ => group,
+            };
+            group
+                .into_iter()
+                .enumerate()
+                // Assign a different layer to each note with the same root,
+                // based on the sample alphabetical order
+                .map(|(index, file)| LayerFile::from_sample_file(file.clone(), index % 4))
+        })
+        .sorted_by(|a, b| a.file.cmp(&b.file))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::model::SampleInfo;
+    use crate::utils::{Loudness, WavFormat, WavMetadata};
+
+    fn sample_file(file: &str, rms: Option<f32>) -> SampleFile {
+        let info = SampleInfo {
+            format: WavFormat {
+                sample_rate: 44100,
+                channels: 1,
+                bits_per_sample: 16,
+            },
+            frames: 0,
+            metadata: WavMetadata::default(),
+            pitch: None,
+            loudness: rms.map(|rms| Loudness { peak: rms, rms }),
+        };
+        SampleFile {
+            file: file.to_string(),
+            root: 45,
+            info: Some(Ok(info)),
+            ..Default::default()
         }
     }
+
+    #[rstest]
+    #[case(LayerOrder::FileName, Some(-6.0), vec![0, 1, 2])]
+    #[case(LayerOrder::Loudness, Some(-6.0), vec![2, 0, 1])]
+    // The alphabetical order is kept when a loudness is unknown
+    #[case(LayerOrder::Loudness, None, vec![0, 1, 2])]
+    fn guess_layers_test(
+        #[case] order: LayerOrder,
+        #[case] a_rms: Option<f32>,
+        #[case] expected: Vec<usize>,
+    ) {
+        let sample_files = [
+            sample_file("Piano A2 c.wav", Some(-12.0)),
+            sample_file("Piano A2 a.wav", a_rms),
+            sample_file("Piano A2 b.wav", Some(-20.0)),
+        ];
+
+        let layers = guess_layers(&sample_files, order)
+            .into_iter()
+            .map(|f| f.layer)
+            .collect::<Vec<_>>();
+
+        assert_eq!(layers, expected);
+    }
 }
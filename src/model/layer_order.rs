@@ -0,0 +1,27 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// Order of the layers of the samples sharing the same root note.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    all(feature = "cli", not(target_arch = "wasm32")),
+    derive(clap::ValueEnum)
+)]
+pub enum LayerOrder {
+    /// Alphabetical order of the file names.
+    #[default]
+    FileName,
+
+    /// From the softest to the loudest sample.
+    Loudness,
+}
+
+impl Display for LayerOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerOrder::FileName => write!(f, "File Name"),
+            LayerOrder::Loudness => write!(f, "Loudness"),
+        }
+    }
+}
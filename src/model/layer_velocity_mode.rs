@@ -1,20 +1,52 @@
 use std::fmt::Display;
 
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use super::Layer;
+
 /// Velocity range assignment mode.
-#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    all(feature = "cli", not(target_arch = "wasm32")),
+    derive(clap::ValueEnum)
+)]
 pub enum LayerVelocityMode {
     /// Assign non overlapping ranges to each layer.
+    #[default]
     Automatic,
 
     /// Set the full range to all the layers.
     Unison,
 }
 
-impl Default for LayerVelocityMode {
-    fn default() -> Self {
-        Self::Automatic
+impl LayerVelocityMode {
+    /// Assign the velocity ranges of a set of layers.
+    pub fn assign_velocities(&self, layers: &mut [Option<Layer>; 4]) {
+        match self {
+            LayerVelocityMode::Unison => {
+                for layer in layers.iter_mut().filter_map(|l| l.as_mut()) {
+                    layer.velocity = 0..=127;
+                }
+            }
+            LayerVelocityMode::Automatic => {
+                let active_layers = layers.iter_mut().filter_map(|l| l.as_mut()).collect_vec();
+                let layer_count = active_layers.len();
+                if layer_count == 0 {
+                    return;
+                }
+
+                for (index, layer) in active_layers.into_iter().enumerate() {
+                    let start: u8 = (128 * index / layer_count)
+                        .try_into()
+                        .expect("The lower bound velocity went out of bound");
+                    let end: u8 = ((128 * (index + 1) / layer_count) - 1)
+                        .try_into()
+                        .expect("The upper bound velocity went out of bound");
+                    layer.velocity = start..=end;
+                }
+            }
+        }
     }
 }
 
@@ -1,13 +1,37 @@
+mod chop_settings;
+mod drum_program;
+mod expansion;
+mod import_warning;
 mod keygroup;
 mod keygroup_program;
 mod layer;
 mod layer_file;
+mod layer_order;
 mod layer_velocity_mode;
+mod pad;
+mod pad_file;
+mod program_type;
 mod sample_file;
+mod sample_info;
+mod sample_library;
+mod sampling_plan;
+mod slice_settings;
 
+pub use chop_settings::*;
+pub use drum_program::*;
+pub use expansion::*;
+pub use import_warning::*;
 pub use keygroup::*;
 pub use keygroup_program::*;
 pub use layer::*;
 pub use layer_file::*;
+pub use layer_order::*;
 pub use layer_velocity_mode::*;
+pub use pad::*;
+pub use pad_file::*;
+pub use program_type::*;
 pub use sample_file::*;
+pub use sample_info::*;
+pub use sample_library::*;
+pub use sampling_plan::*;
+pub use slice_settings::*;
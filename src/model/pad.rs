@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Layer, LayerVelocityMode};
+
+/// Notes triggered by the 128 pads of a drum program, from A01 to H16.
+///
+/// This is the default pad layout of the MPC.
+pub const PAD_NOTES: [u8; 128] = [
+    37, 36, 42, 82, 40, 38, 46, 44, 48, 47, 45, 43, 49, 55, 51, 53, // A
+    54, 69, 81, 80, 65, 66, 76, 77, 56, 62, 63, 64, 73, 74, 71, 39, // B
+    52, 57, 58, 59, 60, 61, 67, 68, 70, 72, 75, 78, 79, 35, 41, 50, // C
+    83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97, 98, // D
+    99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, // E
+    115, 116, 117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 127, 0, 1, 2, // F
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, // G
+    19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, // H
+];
+
+/// Name of a pad, from A01 to H16.
+pub fn pad_name(pad: u8) -> String {
+    let bank = (b'A' + pad / 16) as char;
+    format!("{}{:02}", bank, pad % 16 + 1)
+}
+
+/// A pad of a drum program.
+///
+/// Each pad can stack up to 4 layers, usually split by velocity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pad {
+    /// Pad index, from 0 (A01) to 127 (H16).
+    pub pad: u8,
+
+    /// Layers of the pad.
+    pub layers: [Option<Layer>; 4],
+}
+
+impl Pad {
+    pub fn new(pad: u8, layers: [Option<Layer>; 4]) -> Self {
+        Self { pad, layers }
+    }
+
+    /// Name of the pad, from A01 to H16.
+    pub fn name(&self) -> String {
+        pad_name(self.pad)
+    }
+
+    /// Note triggered by the pad.
+    pub fn note(&self) -> u8 {
+        PAD_NOTES[self.pad as usize % PAD_NOTES.len()]
+    }
+
+    /// Number of assigned layers.
+    pub fn layer_count(&self) -> usize {
+        self.layers.iter().filter_map(|l| l.as_ref()).count()
+    }
+
+    /// Choose the way the velocity range should be assigned accross the layers.
+    pub fn set_velocity_layer_mode(&mut self, mode: &LayerVelocityMode) {
+        mode.assign_velocities(&mut self.layers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    pub use super::*;
+
+    #[rstest]
+    #[case(0, "A01", 37)]
+    #[case(15, "A16", 53)]
+    #[case(16, "B01", 54)]
+    #[case(127, "H16", 34)]
+    fn pad_test(#[case] pad: u8, #[case] name: &str, #[case] note: u8) {
+        let pad = Pad::new(pad, Default::default());
+
+        assert_eq!(pad.name(), name);
+        assert_eq!(pad.note(), note);
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use super::SampleFile;
+
+/// A sample file assigned to a drum pad
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct PadFile {
+    /// Sample file (.wav)
+    pub file: String,
+
+    /// Root note of the original file.
+    pub root: u8,
+
+    /// Destination pad, from 0 (A01) to 127 (H16)
+    pub pad: u8,
+}
+
+impl PadFile {
+    pub fn from_sample_file(file: SampleFile, pad: u8) -> Self {
+        Self {
+            file: file.file,
+            root: file.root,
+            pad,
+        }
+    }
+}
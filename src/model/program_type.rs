@@ -0,0 +1,23 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// Kind of MPC program to create.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum ProgramType {
+    /// Samples spread across the keyboard, pitched from their root note.
+    #[default]
+    Keygroup,
+
+    /// One-shot samples assigned to pads.
+    Drum,
+}
+
+impl Display for ProgramType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramType::Keygroup => write!(f, "Keygroup"),
+            ProgramType::Drum => write!(f, "Drum"),
+        }
+    }
+}
@@ -6,22 +6,69 @@ use staff::{midi::Octave, Natural, Pitch};
 
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::utils::{sample_to_wav, WavMetadata};
+
+use super::SampleInfo;
+
+/// Where the root note of a sample file comes from.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum RootSource {
+    /// Not found yet.
+    #[default]
+    Unknown,
+
+    /// Set manually.
+    Manual,
+
+    /// Parsed from the file name.
+    FileName,
+
+    /// Read from the sampler metadata of the file.
+    Metadata,
+
+    /// Detected from the pitch of the audio.
+    Pitch,
+}
+
+impl Display for RootSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RootSource::Unknown => write!(f, "Not found"),
+            RootSource::Manual => write!(f, "Manual"),
+            RootSource::FileName => write!(f, "From the file name"),
+            RootSource::Metadata => write!(f, "From the file metadata"),
+            RootSource::Pitch => write!(f, "Detected from the audio"),
+        }
+    }
+}
 
 /// A sample file with a root note.
-#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Default, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SampleFile {
     /// Sample file (.wav)
     pub file: String,
 
     /// Root note
     pub root: u8,
+
+    /// Audio properties, or the reason why they could not be read.
+    ///
+    /// None until the content of the file is read.
+    #[serde(default)]
+    pub info: Option<Result<SampleInfo, String>>,
+
+    /// Where the root note comes from
+    #[serde(default)]
+    pub root_source: RootSource,
 }
 
 impl From<String> for SampleFile {
     fn from(value: String) -> Self {
         let mut sample_file = SampleFile {
             file: value,
-            root: 0,
+            ..Default::default()
         };
         sample_file.guess_root();
         sample_file
@@ -29,12 +76,99 @@ impl From<String> for SampleFile {
 }
 
 impl SampleFile {
-    pub fn guess_root(&mut self) {
+    /// Guess the root note from the file metadata, from the file name,
+    /// or from the pitch of the audio.
+    ///
+    /// Returns false if no note was found, the root is then unchanged.
+    pub fn guess_root(&mut self) -> bool {
+        if let Some(root) = self.metadata().and_then(|metadata| metadata.root) {
+            self.root = root;
+            self.root_source = RootSource::Metadata;
+            return true;
+        }
+
         let note = parse_letter_notation(&self.file).or_else(|| parse_number_notation(&self.file));
         if let Some(note) = note {
-            self.root = note.into_byte()
+            self.root = note.into_byte();
+            self.root_source = RootSource::FileName;
+            return true;
+        }
+
+        if let Some(root) = self.sample_info().and_then(|info| info.pitch_note()) {
+            self.root = root;
+            self.root_source = RootSource::Pitch;
+            return true;
+        }
+
+        false
+    }
+
+    /// Audio properties of the file, if they were read.
+    pub fn sample_info(&self) -> Option<&SampleInfo> {
+        match &self.info {
+            Some(Ok(info)) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Sampler metadata of the file, if it was read.
+    pub fn metadata(&self) -> Option<&WavMetadata> {
+        self.sample_info().map(|info| &info.metadata)
+    }
+
+    /// Fine tuning correction of the root note, in cents.
+    ///
+    /// The tuning from the metadata only applies to the root note from the metadata.
+    /// Otherwise, it's the deviation of the detected pitch from the root note,
+    /// when it's within half a semitone.
+    pub fn tune(&self) -> i8 {
+        if let (RootSource::Metadata, Some(metadata)) = (self.root_source, self.metadata()) {
+            return metadata.tune;
+        }
+
+        match self.sample_info().and_then(|info| info.pitch) {
+            Some(pitch) => {
+                let deviation = self.root as i32 * 100 - pitch;
+                if deviation.abs() <= 50 {
+                    deviation as i8
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        }
+    }
+
+    /// Read the audio properties from the content of the file.
+    pub fn read_info(&mut self, content: &[u8]) {
+        let info = sample_to_wav(&self.file, content).and_then(|wav| SampleInfo::read(&wav));
+        self.info = Some(info.map_err(|e| format!("{:#}", e)));
+    }
+
+    /// Warn when the root note from the file name is far from the detected pitch.
+    pub fn pitch_mismatch(&self) -> Option<String> {
+        if self.root_source != RootSource::FileName {
+            return None;
+        }
+        let info = self.sample_info()?;
+        let pitch = info.pitch?;
+        if (pitch - self.root as i32 * 100).abs() <= 100 {
+            return None;
+        }
+        Some(format!(
+            "The file name says {}, but the detected pitch is {}",
+            MidiNote::from_byte(self.root),
+            MidiNote::from_byte(info.pitch_note()?)
+        ))
+    }
+
+    /// Reason why the MPC may not load this sample, if any.
+    pub fn issue(&self) -> Option<String> {
+        match &self.info {
+            Some(Ok(info)) => info.issue(),
+            Some(Err(e)) => Some(e.clone()),
+            None => None,
         }
-        // TODO else
     }
 }
 
@@ -163,9 +297,78 @@ mod tests {
     #[case("THMB48.wav", MidiNote::from(48))]
     #[case("THMB048.wav", MidiNote::from(48))]
     fn parse_note_test(#[case] input: &str, #[case] expected: MidiNote) {
+        let sample_file = SampleFile::from(input.to_string());
+        assert_eq!(sample_file.root, expected.into_byte());
+        assert_eq!(sample_file.root_source, RootSource::FileName);
+    }
+
+    fn sample_file(file: &str, metadata: WavMetadata, pitch: Option<i32>) -> SampleFile {
+        SampleFile {
+            file: file.to_string(),
+            info: Some(Ok(SampleInfo {
+                format: crate::utils::WavFormat {
+                    sample_rate: 44100,
+                    channels: 1,
+                    bits_per_sample: 16,
+                },
+                frames: 10,
+                metadata,
+                pitch,
+                loudness: None,
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn guess_root_metadata_test() {
+        let metadata = WavMetadata {
+            root: Some(46),
+            tune: -12,
+            ..Default::default()
+        };
+        let mut sample_file = sample_file("Piano A2.wav", metadata, Some(4500));
+
+        assert!(sample_file.guess_root());
+
+        assert_eq!(sample_file.root, 46);
+        assert_eq!(sample_file.root_source, RootSource::Metadata);
+        assert_eq!(sample_file.tune(), -12);
+    }
+
+    #[rstest]
+    #[case("Piano A2.wav", Some(4530), Some((45, RootSource::FileName)), false)]
+    #[case("Piano A2.wav", Some(4690), Some((45, RootSource::FileName)), true)]
+    #[case("Piano A2.wav", None, Some((45, RootSource::FileName)), false)]
+    #[case("Piano.wav", Some(4560), Some((46, RootSource::Pitch)), false)]
+    #[case("Piano.wav", None, None, false)]
+    fn guess_root_pitch_test(
+        #[case] file: &str,
+        #[case] pitch: Option<i32>,
+        #[case] expected: Option<(u8, RootSource)>,
+        #[case] mismatch: bool,
+    ) {
+        let mut sample_file = sample_file(file, WavMetadata::default(), pitch);
+
+        let found = sample_file.guess_root();
+
         assert_eq!(
-            SampleFile::from(input.to_string()).root,
-            expected.into_byte()
+            found.then_some((sample_file.root, sample_file.root_source)),
+            expected
         );
+        assert_eq!(sample_file.pitch_mismatch().is_some(), mismatch);
+    }
+
+    #[rstest]
+    #[case("Piano A2.wav", Some(4530), -30)]
+    #[case("Piano A2.wav", Some(4488), 12)]
+    #[case("Piano A2.wav", Some(4690), 0)]
+    #[case("Piano A2.wav", None, 0)]
+    #[case("Piano.wav", Some(4560), 40)]
+    fn tune_test(#[case] file: &str, #[case] pitch: Option<i32>, #[case] expected: i8) {
+        let mut sample_file = sample_file(file, WavMetadata::default(), pitch);
+        sample_file.guess_root();
+
+        assert_eq!(sample_file.tune(), expected);
     }
 }
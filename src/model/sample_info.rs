@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    detect_pitch, frequency_to_cents, measure_loudness, Loudness, Wav, WavFormat, WavMetadata,
+};
+
+/// Audio properties of a sample file, read from its header.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SampleInfo {
+    /// Format of the audio data
+    pub format: WavFormat,
+
+    /// Length in frames
+    pub frames: usize,
+
+    /// Sampler metadata embedded in the file
+    #[serde(default)]
+    pub metadata: WavMetadata,
+
+    /// Detected pitch, as a MIDI note in cents (100 per semitone)
+    #[serde(default)]
+    pub pitch: Option<i32>,
+
+    /// Measured level
+    #[serde(default)]
+    pub loudness: Option<Loudness>,
+}
+
+impl SampleInfo {
+    /// Read the properties of a WAV file.
+    pub fn read(wav: &[u8]) -> Result<Self> {
+        let wav = Wav::parse(wav)?;
+        Ok(Self {
+            format: wav.format,
+            frames: wav.frames(),
+            pitch: detect_pitch(&wav.mono(), wav.format.sample_rate).map(frequency_to_cents),
+            loudness: Some(measure_loudness(&wav, 0..wav.frames())),
+            metadata: wav.metadata,
+        })
+    }
+
+    /// Detected pitch, rounded to the closest MIDI note.
+    pub fn pitch_note(&self) -> Option<u8> {
+        self.pitch
+            .map(|pitch| ((pitch as f32 / 100.0).round() as i32).clamp(0, 127) as u8)
+    }
+
+    /// Duration in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames as f32 / self.format.sample_rate.max(1) as f32
+    }
+
+    /// Reason why the MPC may not load this sample, if any.
+    pub fn issue(&self) -> Option<String> {
+        if ![16, 24].contains(&self.format.bits_per_sample) {
+            Some(format!(
+                "{} bits samples are not supported, use 16 or 24 bits",
+                self.format.bits_per_sample
+            ))
+        } else if self.format.channels > 2 {
+            Some(format!(
+                "{} channels samples are not supported, use mono or stereo",
+                self.format.channels
+            ))
+        } else if !(8000..=96000).contains(&self.format.sample_rate) {
+            Some(format!(
+                "{} Hz samples are not supported",
+                self.format.sample_rate
+            ))
+        } else if self.frames == 0 {
+            Some("The sample is empty".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for SampleInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let channels = match self.format.channels {
+            1 => "mono".to_string(),
+            2 => "stereo".to_string(),
+            channels => format!("{} channels", channels),
+        };
+        write!(
+            f,
+            "{} Hz, {} bits, {}, {:.2} s",
+            self.format.sample_rate,
+            self.format.bits_per_sample,
+            channels,
+            self.duration()
+        )?;
+        if let Some(loudness) = &self.loudness {
+            write!(f, ", {:.1} dB RMS", loudness.rms)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::write_wav;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(44100, 2, 16, None)]
+    #[case(48000, 1, 24, None)]
+    #[case(
+        44100,
+        1,
+        8,
+        Some("8 bits samples are not supported, use 16 or 24 bits")
+    )]
+    #[case(
+        44100,
+        4,
+        16,
+        Some("4 channels samples are not supported, use mono or stereo")
+    )]
+    #[case(192000, 2, 24, Some("192000 Hz samples are not supported"))]
+    fn sample_info_issue_test(
+        #[case] sample_rate: u32,
+        #[case] channels: u16,
+        #[case] bits_per_sample: u16,
+        #[case] expected: Option<&str>,
+    ) {
+        let format = WavFormat {
+            sample_rate,
+            channels,
+            bits_per_sample,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &vec![0; format.block_align() * 10]).unwrap();
+
+        let info = SampleInfo::read(&wav).unwrap();
+
+        assert_eq!(info.format, format);
+        assert_eq!(info.frames, 10);
+        assert_eq!(info.issue().as_deref(), expected);
+    }
+
+    #[test]
+    fn sample_info_corrupt_test() {
+        assert!(SampleInfo::read(b"RIFF\x04\x00\x00\x00WAVE").is_err());
+        assert!(SampleInfo::read(b"not a wav file").is_err());
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(32768)]
+    #[case(u16::MAX)]
+    fn sample_info_corrupt_channels_test(#[case] channels: u16) {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &[0; 20]).unwrap();
+        // Overwrite the channel count of the fmt chunk
+        wav[22..24].copy_from_slice(&channels.to_le_bytes());
+
+        assert!(SampleInfo::read(&wav).is_err());
+    }
+}
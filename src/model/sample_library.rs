@@ -0,0 +1,67 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+
+use crate::utils::sample_to_wav;
+
+/// Content of the sample files, by file name.
+///
+/// The sample files are only referenced by name in the programs. The library
+/// keeps their actual content around when it's available, for the exports that
+/// need to embed the audio.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SampleLibrary {
+    files: BTreeMap<String, Rc<Vec<u8>>>,
+}
+
+impl SampleLibrary {
+    /// Add or replace the content of a sample file.
+    pub fn insert(&mut self, file: String, content: Vec<u8>) {
+        self.files.insert(file, Rc::new(content));
+    }
+
+    /// Add or replace the content of the sample files of another library.
+    pub fn extend(&mut self, other: SampleLibrary) {
+        self.files.extend(other.files);
+    }
+
+    /// Share the content of a file of another library, if known.
+    pub fn share(&mut self, file: String, other: &SampleLibrary, other_file: &str) {
+        if let Some(content) = other.files.get(other_file) {
+            self.files.insert(file, content.clone());
+        }
+    }
+
+    /// Content of a sample file, if known.
+    pub fn get(&self, file: &str) -> Option<&[u8]> {
+        self.files.get(file).map(|content| content.as_slice())
+    }
+
+    /// Content of a sample file converted to WAV.
+    ///
+    /// Fails when the content is not known or can't be decoded.
+    pub fn wav(&self, file: &str) -> Result<Cow<'_, [u8]>> {
+        let content = self
+            .get(file)
+            .with_context(|| format!("The content of {} is missing", file))?;
+        sample_to_wav(file, content).with_context(|| format!("Failed to read {}", file))
+    }
+
+    /// Iterate over the sample files and their content.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.files
+            .iter()
+            .map(|(file, content)| (file.as_str(), content.as_slice()))
+    }
+
+    /// Number of sample files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
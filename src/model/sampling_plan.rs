@@ -0,0 +1,296 @@
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use staff::midi::MidiNote;
+
+use crate::utils::{audible_region, make_midi, sample_to_wav, Wav, WavMetadata};
+
+use super::{add_slice, SampleFile, SampleLibrary};
+
+/// Level where the first note of a recording starts, in dB.
+const START_THRESHOLD: f32 = -50.0;
+
+/// Duration kept before the start of each note, in seconds.
+const PRE_ROLL: f32 = 0.005;
+
+/// A note of a sampling plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedNote {
+    /// MIDI note played.
+    pub root: u8,
+
+    /// Velocity the note is played at.
+    pub velocity: u8,
+
+    /// Velocity range of the layer recorded with this note.
+    pub velocity_range: RangeInclusive<u8>,
+
+    /// Start of the note from the start of the recording, in seconds.
+    pub start: f32,
+
+    /// Duration the note is held, in seconds.
+    pub length: f32,
+}
+
+/// Plan to record a hardware instrument, one note after the other.
+///
+/// The notes are played from a MIDI file, and the recording is then cut
+/// following the same plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingPlan {
+    /// Name of the plan, also used to name the samples.
+    pub name: String,
+
+    /// Lowest and highest notes to record.
+    pub keys: RangeInclusive<u8>,
+
+    /// Semitones between two recorded notes.
+    pub interval: u8,
+
+    /// Velocity of each layer.
+    pub velocities: Vec<u8>,
+
+    /// Duration each note is held, in seconds.
+    pub note_length: f32,
+
+    /// Silence after each note to record its release, in seconds.
+    pub release: f32,
+}
+
+impl Default for SamplingPlan {
+    fn default() -> Self {
+        Self {
+            name: "Instrument".to_string(),
+            keys: 36..=96,
+            interval: 3,
+            velocities: vec![127],
+            note_length: 2.0,
+            release: 1.0,
+        }
+    }
+}
+
+/// Content of the manifest file of a sampling plan.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    plan: SamplingPlan,
+    notes: Vec<PlannedNote>,
+}
+
+impl SamplingPlan {
+    /// Notes of the plan, in the order they are played.
+    ///
+    /// Each root note is played at every velocity, from the softest, before
+    /// moving to the next root note. Each velocity layer covers the velocities
+    /// above the previous layer.
+    pub fn notes(&self) -> Vec<PlannedNote> {
+        let velocities = self
+            .velocities
+            .iter()
+            .copied()
+            .filter(|velocity| (1..=127).contains(velocity))
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let layers = velocities
+            .iter()
+            .enumerate()
+            .map(|(index, &velocity)| {
+                let low = match index {
+                    0 => 0,
+                    _ => velocities[index - 1] + 1,
+                };
+                let high = if index + 1 == velocities.len() {
+                    127
+                } else {
+                    velocity
+                };
+                (velocity, low..=high)
+            })
+            .collect_vec();
+
+        let mut notes = Vec::new();
+        let mut start = 0.0;
+        for root in self.keys.clone().step_by(self.interval.max(1) as usize) {
+            for (velocity, velocity_range) in layers.iter() {
+                notes.push(PlannedNote {
+                    root,
+                    velocity: *velocity,
+                    velocity_range: velocity_range.clone(),
+                    start,
+                    length: self.note_length,
+                });
+                start += self.note_length + self.release;
+            }
+        }
+        notes
+    }
+
+    /// Duration of the whole recording, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.notes().len() as f32 * (self.note_length + self.release)
+    }
+
+    /// Export the notes of the plan as a Standard MIDI File.
+    pub fn export_midi<W: Write>(&self, mut w: W) -> Result<()> {
+        w.write_all(&make_midi(&self.name, &self.notes()))?;
+        Ok(())
+    }
+
+    /// Export the plan and its notes as a JSON manifest.
+    pub fn export_manifest<W: Write>(&self, w: W) -> Result<()> {
+        let manifest = Manifest {
+            plan: self.clone(),
+            notes: self.notes(),
+        };
+        serde_json::to_writer_pretty(w, &manifest)?;
+        Ok(())
+    }
+
+    /// Import a plan from its JSON manifest.
+    pub fn import_manifest<R: Read>(r: R) -> Result<Self> {
+        let manifest: Manifest =
+            serde_json::from_reader(r).context("The file is not a sampling plan manifest")?;
+        Ok(manifest.plan)
+    }
+
+    /// Cut a recording made from the plan into one sample file per note.
+    ///
+    /// The recording is aligned on its first audible frame, the start of the
+    /// first note. Each sample holds a note and its release. Its root note and
+    /// velocity range are stored in its sampler metadata.
+    pub fn slice(&self, file: &str, content: &[u8]) -> Result<(Vec<SampleFile>, SampleLibrary)> {
+        let content = sample_to_wav(file, content)?;
+        let wav = Wav::parse(&content).with_context(|| format!("Failed to read {}", file))?;
+        let offset = audible_region(&wav, 0..wav.frames(), START_THRESHOLD, PRE_ROLL)
+            .with_context(|| format!("No note was found in {}", file))?
+            .start;
+        let sample_rate = wav.format.sample_rate as f32;
+        let frame = |seconds: f32| offset + (seconds * sample_rate).round() as usize;
+
+        let mut sample_files = Vec::new();
+        let mut library = SampleLibrary::default();
+        for note in self.notes() {
+            let start = frame(note.start);
+            if start >= wav.frames() {
+                bail!(
+                    "{} is shorter than the sampling plan, only {} notes were found",
+                    file,
+                    sample_files.len()
+                );
+            }
+            let end = frame(note.start + note.length + self.release).min(wav.frames());
+            let metadata = WavMetadata {
+                root: Some(note.root),
+                velocity: Some(note.velocity_range),
+                ..Default::default()
+            };
+            let name = format!(
+                "{} {} v{:03}.wav",
+                self.name,
+                MidiNote::from_byte(note.root),
+                note.velocity
+            );
+            sample_files.push(add_slice(&wav, start..end, &metadata, name, &mut library)?);
+        }
+        Ok((sample_files, library))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::model::{guess_layers, KeygroupProgram, LayerOrder};
+    use crate::utils::{write_wav, WavFormat};
+
+    fn plan() -> SamplingPlan {
+        SamplingPlan {
+            name: "Synth".to_string(),
+            keys: 48..=55,
+            interval: 6,
+            velocities: vec![100, 40],
+            note_length: 0.1,
+            release: 0.1,
+        }
+    }
+
+    #[test]
+    fn notes_test() {
+        let notes = plan().notes();
+
+        let summary = notes
+            .iter()
+            .map(|note| (note.root, note.velocity, note.velocity_range.clone()))
+            .collect_vec();
+        assert_eq!(
+            summary,
+            vec![
+                (48, 40, 0..=40),
+                (48, 100, 41..=127),
+                (54, 40, 0..=40),
+                (54, 100, 41..=127),
+            ]
+        );
+        assert!((notes[3].start - 0.6).abs() < 1e-6);
+        assert!((plan().duration() - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn manifest_round_trip_test() {
+        let mut manifest = Vec::new();
+        plan().export_manifest(&mut manifest).unwrap();
+
+        assert_eq!(
+            SamplingPlan::import_manifest(manifest.as_slice()).unwrap(),
+            plan()
+        );
+        assert!(SamplingPlan::import_manifest(&b"{}"[..]).is_err());
+    }
+
+    #[test]
+    fn slice_test() {
+        // The recording starts with some latency, each note is a short click
+        let format = WavFormat {
+            sample_rate: 1000,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let mut points = vec![0_i16; 850];
+        for note in 0..4 {
+            points[50 + note * 200] = 10000;
+        }
+        let data = points.iter().flat_map(|p| p.to_le_bytes()).collect_vec();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &data).unwrap();
+
+        let (sample_files, library) = plan().slice("Recording.wav", &wav).unwrap();
+
+        let files = sample_files.iter().map(|f| f.file.as_str()).collect_vec();
+        assert_eq!(
+            files,
+            vec![
+                "Synth C3 v040.wav",
+                "Synth C3 v100.wav",
+                "Synth F#3 v040.wav",
+                "Synth F#3 v100.wav"
+            ]
+        );
+        assert_eq!(library.len(), 4);
+        let info = sample_files[1].sample_info().unwrap();
+        assert_eq!(info.frames, 200);
+        assert_eq!(info.metadata.velocity, Some(41..=127));
+
+        // The layers are known without looking at the file names
+        let program: KeygroupProgram = guess_layers(&sample_files, LayerOrder::FileName).into();
+        assert_eq!(program.keygroups.len(), 2);
+        let layer = program.keygroups[1].layers[1].as_ref().unwrap();
+        assert_eq!(layer.root, 54);
+        assert_eq!(layer.velocity, 41..=127);
+
+        // A recording shorter than the plan
+        assert!(plan().slice("Recording.wav", &wav[..1000]).is_err());
+    }
+}
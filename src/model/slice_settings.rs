@@ -0,0 +1,265 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use staff::midi::MidiNote;
+
+use crate::utils::{
+    bundled_sample_name, cut_wav, detect_notes, detect_pitch, fixed_slices, frequency_to_cents,
+    sample_to_wav, Wav, WavMetadata,
+};
+
+use super::{SampleFile, SampleLibrary};
+
+/// How a recording is cut into notes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SliceMode {
+    /// Find the notes separated by silences.
+    #[default]
+    Detect,
+
+    /// Notes of a fixed length, separated by a fixed gap.
+    Fixed,
+}
+
+impl Display for SliceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SliceMode::Detect => write!(f, "Detect Notes"),
+            SliceMode::Fixed => write!(f, "Fixed Length"),
+        }
+    }
+}
+
+/// How the root notes of the slices are found.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SliceRoots {
+    /// Notes played in sequence, from a start note and with a fixed interval.
+    #[default]
+    Sequence,
+
+    /// Detected from the pitch of each slice.
+    Pitch,
+}
+
+impl Display for SliceRoots {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SliceRoots::Sequence => write!(f, "Note Sequence"),
+            SliceRoots::Pitch => write!(f, "Pitch Detection"),
+        }
+    }
+}
+
+/// Settings to cut a recording of sequential notes into samples.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct SliceSettings {
+    /// How the recording is cut.
+    pub mode: SliceMode,
+
+    /// Level above which a note is playing, in dB (note detection).
+    pub threshold: f32,
+
+    /// Shortest silence between two notes, in seconds (note detection).
+    pub min_silence: f32,
+
+    /// Duration of each note, in seconds (fixed length).
+    pub length: f32,
+
+    /// Duration between two notes, in seconds (fixed length).
+    pub gap: f32,
+
+    /// How the root notes are found.
+    pub roots: SliceRoots,
+
+    /// Root note of the first slice (note sequence).
+    pub start_note: u8,
+
+    /// Semitones between two slices (note sequence).
+    pub interval: u8,
+}
+
+impl Default for SliceSettings {
+    fn default() -> Self {
+        Self {
+            mode: SliceMode::Detect,
+            threshold: -50.0,
+            min_silence: 0.2,
+            length: 2.0,
+            gap: 0.5,
+            roots: SliceRoots::Sequence,
+            start_note: 36,
+            interval: 1,
+        }
+    }
+}
+
+impl SliceSettings {
+    /// Cut a recording into one sample file per note.
+    ///
+    /// The slices are named after the recording, and their root note is stored
+    /// in their sampler metadata. The content of the slices is returned in a library.
+    pub fn slice(&self, file: &str, content: &[u8]) -> Result<(Vec<SampleFile>, SampleLibrary)> {
+        let content = sample_to_wav(file, content)?;
+        let wav = Wav::parse(&content).with_context(|| format!("Failed to read {}", file))?;
+        let sample_rate = wav.format.sample_rate as f32;
+        let regions = match self.mode {
+            SliceMode::Detect => detect_notes(&wav, self.threshold, self.min_silence),
+            SliceMode::Fixed => fixed_slices(
+                wav.frames(),
+                (self.length.max(0.0) * sample_rate) as usize,
+                (self.gap.max(0.0) * sample_rate) as usize,
+            ),
+        };
+        if regions.is_empty() {
+            bail!("No note was found in {}", file);
+        }
+
+        let name = bundled_sample_name(file);
+        let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+        let mono = match self.roots {
+            SliceRoots::Sequence => Vec::new(),
+            SliceRoots::Pitch => wav.mono(),
+        };
+
+        let mut sample_files = Vec::new();
+        let mut library = SampleLibrary::default();
+        for (index, region) in regions.into_iter().enumerate() {
+            let mut metadata = WavMetadata::default();
+            match self.roots {
+                SliceRoots::Sequence => {
+                    let root = self.start_note as usize + index * self.interval as usize;
+                    metadata.root = Some(root as u8).filter(|_| root < 128);
+                }
+                SliceRoots::Pitch => {
+                    let pitch = detect_pitch(&mono[region.clone()], wav.format.sample_rate)
+                        .map(frequency_to_cents);
+                    if let Some(pitch) = pitch {
+                        let root = ((pitch as f32 / 100.0).round() as i32).clamp(0, 127);
+                        metadata.root = Some(root as u8);
+                        metadata.tune = (root * 100 - pitch).clamp(-50, 50) as i8;
+                    }
+                }
+            }
+
+            let file = match metadata.root {
+                Some(root) => format!(
+                    "{} {:03} {}.wav",
+                    stem,
+                    index + 1,
+                    MidiNote::from_byte(root)
+                ),
+                None => format!("{} {:03}.wav", stem, index + 1),
+            };
+            sample_files.push(add_slice(&wav, region, &metadata, file, &mut library)?);
+        }
+        Ok((sample_files, library))
+    }
+}
+
+/// Cut a region of a recording as a new sample file, stored in the library.
+pub(crate) fn add_slice(
+    wav: &Wav<'_>,
+    region: Range<usize>,
+    metadata: &WavMetadata,
+    file: String,
+    library: &mut SampleLibrary,
+) -> Result<SampleFile> {
+    let slice = cut_wav(wav, region, metadata)?;
+    let mut sample_file = SampleFile {
+        file: file.clone(),
+        ..Default::default()
+    };
+    sample_file.read_info(&slice);
+    sample_file.guess_root();
+    library.insert(file, slice);
+    Ok(sample_file)
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::model::RootSource;
+    use crate::utils::{write_wav, WavFormat};
+
+    /// Two sine notes of 0.2 s at 220 and 440 Hz, separated by 0.3 s of silence.
+    fn recording() -> Vec<u8> {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let mut points = Vec::new();
+        for frequency in [220.0, 440.0] {
+            points.extend((0..8820).map(|i| {
+                let t = i as f32 / 44100.0;
+                ((2.0 * std::f32::consts::PI * frequency * t).sin() * 16000.0) as i16
+            }));
+            points.extend(std::iter::repeat_n(0, 13230));
+        }
+        let data = points
+            .iter()
+            .flat_map(|point| point.to_le_bytes())
+            .collect::<Vec<_>>();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &data).unwrap();
+        wav
+    }
+
+    fn roots(sample_files: &[SampleFile]) -> Vec<u8> {
+        sample_files.iter().map(|file| file.root).collect()
+    }
+
+    #[test]
+    fn slice_sequence_test() {
+        let settings = SliceSettings {
+            start_note: 45,
+            interval: 12,
+            ..Default::default()
+        };
+
+        let (sample_files, library) = settings.slice("Rec/Synth.wav", &recording()).unwrap();
+
+        assert_eq!(roots(&sample_files), vec![45, 57]);
+        assert_eq!(sample_files[0].root_source, RootSource::Metadata);
+        assert_eq!(library.len(), 2);
+        let frames = sample_files[0].sample_info().unwrap().frames;
+        assert!((8820..8820 + 300).contains(&frames), "{}", frames);
+        // The root note can also be guessed from the name of the slice
+        let mut renamed = SampleFile::from(sample_files[1].file.clone());
+        assert!(renamed.guess_root());
+        assert_eq!(renamed.root, 57);
+    }
+
+    #[test]
+    fn slice_pitch_test() {
+        let settings = SliceSettings {
+            mode: SliceMode::Fixed,
+            length: 0.2,
+            gap: 0.3,
+            roots: SliceRoots::Pitch,
+            ..Default::default()
+        };
+
+        let (sample_files, _) = settings.slice("Synth.wav", &recording()).unwrap();
+
+        assert_eq!(roots(&sample_files), vec![57, 69]);
+        assert!(sample_files
+            .iter()
+            .all(|file| file.sample_info().unwrap().frames == 8820));
+    }
+
+    #[test]
+    fn slice_silence_test() {
+        let mut wav = Vec::new();
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        write_wav(&mut wav, &format, &[0; 100]).unwrap();
+
+        assert!(SliceSettings::default().slice("Synth.wav", &wav).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use anyhow::{bail, Context, Result};
+
+use super::{write_wav_with_metadata, WavFormat, WavMetadata};
+
+/// A chunk of an AIFF file, with big endian sizes.
+struct AiffChunk<'a> {
+    id: [u8; 4],
+    data: &'a [u8],
+}
+
+/// Iterate over the chunks of the FORM chunk of an AIFF file.
+fn aiff_chunks(mut data: &[u8]) -> impl Iterator<Item = Result<AiffChunk<'_>>> {
+    std::iter::from_fn(move || {
+        if data.is_empty() {
+            return None;
+        }
+        if data.len() < 8 {
+            data = &[];
+            return Some(Err(anyhow::anyhow!("The AIFF chunk header is truncated")));
+        }
+        let id = [data[0], data[1], data[2], data[3]];
+        let size = read_u32_be(data, 4) as usize;
+        let content = &data[8..];
+        if size > content.len() {
+            data = &[];
+            return Some(Err(anyhow::anyhow!("The AIFF chunk is truncated")));
+        }
+        // Chunks are padded to an even size
+        data = content.get(size + size % 2..).unwrap_or_default();
+        Some(Ok(AiffChunk {
+            id,
+            data: &content[..size],
+        }))
+    })
+}
+
+fn read_u16_be(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Read the 80 bits extended precision float used for the sample rate.
+fn read_extended(data: &[u8]) -> f64 {
+    let exponent = (((data[0] & 0x7F) as i32) << 8) | data[1] as i32;
+    let mantissa = u64::from_be_bytes([
+        data[2], data[3], data[4], data[5], data[6], data[7], data[8], data[9],
+    ]);
+    let value = mantissa as f64 * 2_f64.powi(exponent - 16383 - 63);
+    if data[0] & 0x80 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Convert an AIFF or uncompressed AIFF-C file to a WAV file.
+///
+/// The root note, tuning and ranges of the INST chunk are kept.
+pub fn aiff_to_wav(aiff: &[u8]) -> Result<Vec<u8>> {
+    if aiff.len() < 12 || &aiff[0..4] != b"FORM" {
+        bail!("The file is not an AIFF file");
+    }
+    let little_endian = match &aiff[8..12] {
+        b"AIFF" => false,
+        b"AIFC" => true,
+        _ => bail!("The file is not an AIFF file"),
+    };
+    let size = (read_u32_be(aiff, 4) as usize).saturating_sub(4);
+    let chunks = &aiff[12..aiff.len().min(size.saturating_add(12))];
+
+    let mut comm = None;
+    let mut ssnd = None;
+    let mut inst = None;
+    for chunk in aiff_chunks(chunks) {
+        let chunk = chunk?;
+        match &chunk.id {
+            b"COMM" => comm = Some(chunk.data),
+            b"SSND" => ssnd = Some(chunk.data),
+            b"INST" => inst = Some(chunk.data),
+            _ => {}
+        }
+    }
+
+    let comm = comm.context("The AIFF file does not have a COMM chunk")?;
+    let ssnd = ssnd.context("The AIFF file does not have a SSND chunk")?;
+    if comm.len() < 18 || ssnd.len() < 8 {
+        bail!("The AIFF file is truncated");
+    }
+
+    // AIFF-C files are only supported when not compressed
+    let little_endian = if little_endian {
+        match comm.get(18..22) {
+            Some(b"NONE") | Some(b"twos") => false,
+            Some(b"sowt") => true,
+            _ => bail!("Compressed AIFF files are not supported"),
+        }
+    } else {
+        false
+    };
+
+    let format = WavFormat {
+        sample_rate: read_extended(&comm[8..18]).round() as u32,
+        channels: read_u16_be(comm, 0),
+        bits_per_sample: read_u16_be(comm, 6),
+    };
+    if format.channels == 0
+        || !(1..=32).contains(&format.bits_per_sample)
+        || format.header_block_align().is_none()
+    {
+        bail!("The AIFF file format is invalid");
+    }
+
+    let frames = read_u32_be(comm, 2) as usize;
+    let offset = (read_u32_be(ssnd, 0) as usize).checked_add(8);
+    let end = frames
+        .checked_mul(format.block_align())
+        .zip(offset)
+        .and_then(|(length, offset)| offset.checked_add(length));
+    let samples = offset
+        .zip(end)
+        .and_then(|(offset, end)| ssnd.get(offset..end))
+        .context("The sound data of the AIFF file is truncated")?;
+
+    // WAV files are little endian, and unsigned with 8 bits
+    let bytes = format.bits_per_sample.div_ceil(8) as usize;
+    let mut data = Vec::with_capacity(samples.len());
+    for point in samples.chunks_exact(bytes) {
+        if little_endian {
+            data.extend_from_slice(point);
+        } else {
+            data.extend(point.iter().rev());
+        }
+        if bytes == 1 {
+            let last = data.len() - 1;
+            data[last] = data[last].wrapping_add(128);
+        }
+    }
+
+    let mut metadata = WavMetadata::default();
+    if let Some(inst) = inst.filter(|inst| inst.len() >= 6) {
+        metadata.root = Some(inst[0]).filter(|root| *root < 128);
+        metadata.tune = (inst[1] as i8).clamp(-50, 50);
+        metadata.keys = Some(inst[2].min(127)..=inst[3].min(127));
+        metadata.velocity = Some(inst[4].min(127)..=inst[5].min(127));
+    }
+
+    let mut wav = Vec::new();
+    write_wav_with_metadata(&mut wav, &format, &data, &metadata)?;
+    Ok(wav)
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::Wav;
+    use rstest::rstest;
+
+    /// Build an AIFF file of 16 bits mono samples at 44100 Hz.
+    fn aiff(form_type: &[u8; 4], compression: &[u8; 4], samples: &[u8], inst: &[u8]) -> Vec<u8> {
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&1_u16.to_be_bytes());
+        comm.extend_from_slice(&((samples.len() / 2) as u32).to_be_bytes());
+        comm.extend_from_slice(&16_u16.to_be_bytes());
+        // 44100 as an 80 bits extended float
+        comm.extend_from_slice(&[0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+        if form_type == b"AIFC" {
+            comm.extend_from_slice(compression);
+        }
+
+        let mut chunks = Vec::new();
+        for (id, data) in [(b"COMM", comm.as_slice()), (b"INST", inst)] {
+            if data.is_empty() {
+                continue;
+            }
+            chunks.extend_from_slice(id);
+            chunks.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            chunks.extend_from_slice(data);
+        }
+        chunks.extend_from_slice(b"SSND");
+        chunks.extend_from_slice(&(samples.len() as u32 + 8).to_be_bytes());
+        chunks.extend_from_slice(&[0; 8]);
+        chunks.extend_from_slice(samples);
+
+        let mut aiff = b"FORM".to_vec();
+        aiff.extend_from_slice(&(chunks.len() as u32 + 4).to_be_bytes());
+        aiff.extend_from_slice(form_type);
+        aiff.extend_from_slice(&chunks);
+        aiff
+    }
+
+    #[test]
+    fn aiff_to_wav_test() {
+        let aiff = aiff(b"AIFF", b"NONE", &[0x12, 0x34, 0xFF, 0xFE], &[]);
+
+        let wav = aiff_to_wav(&aiff).unwrap();
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(
+            wav.format,
+            WavFormat {
+                sample_rate: 44100,
+                channels: 1,
+                bits_per_sample: 16,
+            }
+        );
+        assert_eq!(wav.data, &[0x34, 0x12, 0xFE, 0xFF]);
+        assert_eq!(wav.metadata, WavMetadata::default());
+    }
+
+    #[test]
+    fn aifc_little_endian_to_wav_test() {
+        let aiff = aiff(b"AIFC", b"sowt", &[0x34, 0x12], &[]);
+
+        let wav = aiff_to_wav(&aiff).unwrap();
+
+        assert_eq!(Wav::parse(&wav).unwrap().data, &[0x34, 0x12]);
+    }
+
+    #[test]
+    fn aiff_instrument_to_wav_test() {
+        let inst = [
+            57, 0xF6, 50, 60, 1, 100, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let aiff = aiff(b"AIFF", b"NONE", &[0, 0], &inst);
+
+        let wav = aiff_to_wav(&aiff).unwrap();
+
+        assert_eq!(
+            Wav::parse(&wav).unwrap().metadata,
+            WavMetadata {
+                root: Some(57),
+                tune: -10,
+                keys: Some(50..=60),
+                velocity: Some(1..=100),
+            }
+        );
+    }
+
+    #[rstest]
+    // Channel count
+    #[case(20, &[0xFF, 0xFF])]
+    // Frame count
+    #[case(22, &[0xFF, 0xFF, 0xFF, 0xFF])]
+    // Offset of the sound data
+    #[case(46, &[0xFF, 0xFF, 0xFF, 0xFF])]
+    fn corrupt_aiff_test(#[case] position: usize, #[case] bytes: &[u8]) {
+        let mut aiff = aiff(b"AIFF", b"NONE", &[0, 0], &[]);
+        aiff[position..position + bytes.len()].copy_from_slice(bytes);
+
+        assert!(aiff_to_wav(&aiff).is_err());
+    }
+
+    #[test]
+    fn compressed_aifc_test() {
+        let aiff = aiff(b"AIFC", b"ima4", &[0, 0], &[]);
+
+        assert!(aiff_to_wav(&aiff).is_err());
+    }
+}
@@ -0,0 +1,460 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::model::{Layer, SampleLibrary};
+
+use super::{convert_wav, crossfade_loop, wav_file_name, TargetFormat, Wav};
+
+/// A file of a bundle, with its path in the bundle and its content.
+pub type BundleFile<'a> = (String, Cow<'a, [u8]>);
+
+/// File name of a sample, without its folder.
+pub fn bundled_sample_name(file: &str) -> &str {
+    file.rsplit(['/', '\\']).next().unwrap_or(file)
+}
+
+/// Name usable for a file or a folder of a bundle.
+///
+/// The path separators and the characters forbidden by some file systems are
+/// replaced, so that a name can't escape its folder.
+pub fn bundle_file_name(name: &str) -> String {
+    let name = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_control() || r#"/\:*?"<>|"#.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    match name.trim_end_matches(['.', ' ']) {
+        "" => "_".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// File name of a sample in a bundle, without its folder and converted to WAV.
+pub fn bundled_wav_name(file: &str) -> String {
+    wav_file_name(bundled_sample_name(file)).into_owned()
+}
+
+/// Point a layer to its sample in a bundle.
+///
+/// The sample is renamed as a WAV file, and its region and loop follow the
+/// sample rate it's converted to.
+pub fn bundle_layer(
+    layer: &mut Layer,
+    library: &SampleLibrary,
+    format: &TargetFormat,
+) -> Result<()> {
+    let has_frames = layer.sample_region.is_some() || layer.sample_loop.is_some();
+    if let (true, Some(sample_rate)) = (has_frames, format.sample_rate) {
+        let content = library.wav(&layer.file)?;
+        let source_rate = Wav::parse(&content)?.format.sample_rate;
+        let convert = |frame: usize| {
+            (frame as f64 * sample_rate as f64 / source_rate as f64).round() as usize
+        };
+        if let Some(region) = &layer.sample_region {
+            layer.sample_region = Some(convert(region.start)..convert(region.end));
+        }
+        if let Some(slice) = &layer.sample_slice {
+            layer.sample_slice = Some(convert(slice.start)..convert(slice.end));
+        }
+        if let Some(sample_loop) = &layer.sample_loop {
+            layer.sample_loop = Some(convert(sample_loop.start)..convert(sample_loop.end));
+        }
+        layer.loop_crossfade = convert(layer.loop_crossfade);
+    }
+    layer.file = bundled_wav_name(&layer.file);
+    Ok(())
+}
+
+/// Gather sample files to store them in a folder of a bundle.
+///
+/// The MPC looks for the samples next to their program, so they are all
+/// stored in the same folder. The sample files are read from the library,
+/// they must all be there. The AIFF and FLAC files are converted to WAV, and
+/// all the samples are converted to the target format.
+pub fn bundle_samples<'a, 'b, I>(
+    folder: &str,
+    sample_files: I,
+    library: &'a SampleLibrary,
+    format: &TargetFormat,
+) -> Result<Vec<BundleFile<'a>>>
+where
+    I: IntoIterator<Item = &'b str>,
+{
+    let sample_files = sample_files.into_iter().unique().collect_vec();
+
+    let missing = sample_files
+        .iter()
+        .filter(|file| library.get(file).is_none())
+        .join(", ");
+    if !missing.is_empty() {
+        bail!(
+            "The content of these samples is not available, add them again: {}",
+            missing
+        );
+    }
+
+    let mut bundled = BTreeMap::<String, &str>::new();
+    for file in sample_files.iter() {
+        let name = bundled_wav_name(file);
+        if let Some(other) = bundled.insert(name, file) {
+            bail!(
+                "{} and {} would have the same name in the bundle",
+                other,
+                file
+            );
+        }
+    }
+
+    bundled
+        .into_iter()
+        .map(|(name, file)| {
+            let content = convert_wav(library.wav(file)?, format)
+                .with_context(|| format!("Failed to convert {}", file))?;
+            Ok((format!("{}{}", folder, name), content))
+        })
+        .collect()
+}
+
+/// Bake the loop crossfades of bundled layers into their bundled samples.
+///
+/// The layers must already point to their samples in the bundle. A sample
+/// shared by several layers is crossfaded with the loop of the first one.
+pub fn bundle_crossfades<'a, 'b, I>(
+    folder: &str,
+    files: &mut [BundleFile<'a>],
+    layers: I,
+) -> Result<()>
+where
+    I: IntoIterator<Item = &'b Layer>,
+{
+    let mut crossfaded = Vec::new();
+    for layer in layers {
+        let Some(sample_loop) = &layer.sample_loop else {
+            continue;
+        };
+        let path = format!("{}{}", folder, layer.file);
+        if layer.loop_crossfade == 0 || crossfaded.contains(&path) {
+            continue;
+        }
+        if let Some((_, content)) = files.iter_mut().find(|(file, _)| *file == path) {
+            let wav = Wav::parse(content).with_context(|| format!("Failed to read {}", path))?;
+            *content = Cow::Owned(crossfade_loop(&wav, sample_loop, layer.loop_crossfade)?);
+            crossfaded.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Files of a program and its samples, stored in a folder of a bundle.
+///
+/// The program is given as its XPM, exported with its layers pointing to their
+/// samples in the bundle (see [bundle_layer]). The layers are given as pairs of
+/// original and bundled layers: the samples are read from the library with
+/// their original names, and the loop crossfades are baked in the bundled ones.
+///
+/// The folder is either empty or ends with a slash.
+pub fn bundle_program<'a, 'b, I>(
+    folder: &str,
+    name: &str,
+    xpm: Vec<u8>,
+    layers: I,
+    library: &'a SampleLibrary,
+    format: &TargetFormat,
+) -> Result<Vec<BundleFile<'a>>>
+where
+    I: IntoIterator<Item = (&'b Layer, &'b Layer)>,
+{
+    let (layers, bundled_layers): (Vec<_>, Vec<_>) = layers.into_iter().unzip();
+    let mut files = vec![(
+        format!("{}{}.xpm", folder, bundle_file_name(name)),
+        Cow::Owned(xpm),
+    )];
+    let sample_files = layers.iter().map(|layer| layer.file.as_str());
+    files.extend(bundle_samples(folder, sample_files, library, format)?);
+    bundle_crossfades(folder, &mut files, bundled_layers)?;
+    Ok(files)
+}
+
+/// Write the files of a bundle as a zip archive.
+pub fn write_zip<W: Write + Seek>(w: W, files: &[BundleFile<'_>]) -> Result<()> {
+    // Samples are already compressed enough, keep the bundle simple
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut zip = ZipWriter::new(w);
+    for (path, content) in files {
+        zip.start_file(path, options)?;
+        zip.write_all(content)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Write the files of a bundle in a folder, creating their sub-folders.
+pub fn write_folder(folder: &Path, files: &[BundleFile<'_>]) -> Result<()> {
+    for (path, content) in files {
+        let path = folder.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    use rstest::rstest;
+
+    pub use super::*;
+    use crate::utils::{write_wav, WavFormat};
+
+    #[rstest]
+    #[case("Piano", "Piano")]
+    #[case(" Soft Keys ", "Soft Keys")]
+    #[case("AC/DC: Live?", "AC_DC_ Live_")]
+    #[case("../..", ".._")]
+    #[case("..", "_")]
+    #[case("", "_")]
+    fn bundle_file_name_test(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(bundle_file_name(name), expected);
+    }
+
+    #[test]
+    fn bundle_samples_test() {
+        let mut library = SampleLibrary::default();
+        library.insert("Samples/A2.wav".to_string(), vec![1, 2, 3]);
+        library.insert("C3.wav".to_string(), vec![4, 5]);
+        library.insert("Unused.wav".to_string(), vec![6]);
+
+        let files = bundle_samples(
+            "Piano/",
+            ["Samples/A2.wav", "C3.wav", "Samples/A2.wav"],
+            &library,
+            &TargetFormat::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                ("Piano/A2.wav".to_string(), Cow::Borrowed(&[1, 2, 3][..])),
+                ("Piano/C3.wav".to_string(), Cow::Borrowed(&[4, 5][..])),
+            ]
+        );
+    }
+
+    #[test]
+    fn bundle_converted_samples_test() {
+        // One 16 bits mono sample at 44100 Hz
+        let aiff = vec![
+            0x46, 0x4F, 0x52, 0x4D, 0x00, 0x00, 0x00, 0x30, 0x41, 0x49, 0x46, 0x46, 0x43, 0x4F,
+            0x4D, 0x4D, 0x00, 0x00, 0x00, 0x12, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x10,
+            0x40, 0x0E, 0xAC, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x53, 0x53, 0x4E, 0x44,
+            0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34,
+        ];
+        let mut library = SampleLibrary::default();
+        library.insert("Samples/A2.aif".to_string(), aiff);
+        library.insert("A2.wav".to_string(), vec![1]);
+
+        let files = bundle_samples(
+            "Piano/",
+            ["Samples/A2.aif"],
+            &library,
+            &TargetFormat::default(),
+        )
+        .unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, "Piano/A2.wav");
+        assert_eq!(Wav::parse(&files[0].1).unwrap().data, &[0x34, 0x12]);
+        // The converted file would replace the WAV file
+        assert!(bundle_samples(
+            "",
+            ["Samples/A2.aif", "A2.wav"],
+            &library,
+            &TargetFormat::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn bundle_layer_test() {
+        let format = WavFormat {
+            sample_rate: 48000,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &[0; 960]).unwrap();
+        let mut library = SampleLibrary::default();
+        library.insert("Samples/A2.aif".to_string(), vec![]);
+        library.insert("Samples/A2.wav".to_string(), wav);
+        let target = TargetFormat {
+            sample_rate: Some(44100),
+            ..Default::default()
+        };
+
+        let mut layer = Layer::new("Samples/A2.aif".to_string(), 45, 0..=127);
+        bundle_layer(&mut layer, &library, &target).unwrap();
+        assert_eq!(layer.file, "A2.wav");
+
+        let mut layer = Layer::new("Samples/A2.wav".to_string(), 45, 0..=127);
+        layer.sample_region = Some(48..480);
+        layer.sample_loop = Some(96..480);
+        layer.loop_crossfade = 48;
+        bundle_layer(&mut layer, &library, &target).unwrap();
+        assert_eq!(layer.sample_region, Some(44..441));
+        assert_eq!(layer.sample_loop, Some(88..441));
+        assert_eq!(layer.loop_crossfade, 44);
+    }
+
+    #[test]
+    fn bundle_crossfades_test() {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 8,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &[128, 138, 148, 158, 168, 178]).unwrap();
+        let mut files = vec![
+            ("Piano/A2.wav".to_string(), Cow::Owned(wav.clone())),
+            ("Piano/C3.wav".to_string(), Cow::Owned(wav.clone())),
+        ];
+        let looped = Layer {
+            sample_loop: Some(2..5),
+            loop_crossfade: 1,
+            ..Layer::new("A2.wav".to_string(), 45, 0..=127)
+        };
+        let not_crossfaded = Layer {
+            sample_loop: Some(2..5),
+            ..Layer::new("C3.wav".to_string(), 48, 0..=127)
+        };
+
+        bundle_crossfades("Piano/", &mut files, [&looped, &looped, &not_crossfaded]).unwrap();
+
+        assert_eq!(
+            Wav::parse(&files[0].1).unwrap().data,
+            &[128, 138, 148, 158, 153, 178]
+        );
+        assert_eq!(files[1].1, wav);
+    }
+
+    #[test]
+    fn bundle_program_test() {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 8,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &[128, 138, 148, 158, 168, 178]).unwrap();
+        let mut library = SampleLibrary::default();
+        library.insert("Samples/A2.wav".to_string(), wav);
+        let layer = Layer {
+            sample_loop: Some(2..5),
+            loop_crossfade: 1,
+            ..Layer::new("Samples/A2.wav".to_string(), 45, 0..=127)
+        };
+        let mut bundled = layer.clone();
+        bundle_layer(&mut bundled, &library, &TargetFormat::default()).unwrap();
+
+        let files = bundle_program(
+            "Piano/",
+            "Soft/Piano",
+            b"<program/>".to_vec(),
+            [(&layer, &bundled)],
+            &library,
+            &TargetFormat::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            files.iter().map(|(path, _)| path.as_str()).collect_vec(),
+            vec!["Piano/Soft_Piano.xpm", "Piano/A2.wav"]
+        );
+        assert_eq!(
+            Wav::parse(&files[1].1).unwrap().data,
+            &[128, 138, 148, 158, 153, 178]
+        );
+    }
+
+    #[test]
+    fn bundle_samples_errors_test() {
+        let mut library = SampleLibrary::default();
+        library.insert("a/A2.wav".to_string(), vec![1]);
+        library.insert("b/A2.wav".to_string(), vec![2]);
+
+        assert!(bundle_samples("", ["missing.wav"], &library, &TargetFormat::default()).is_err());
+        assert!(bundle_samples(
+            "",
+            ["a/A2.wav", "b/A2.wav"],
+            &library,
+            &TargetFormat::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn write_zip_test() {
+        let files = vec![
+            ("Piano.xpm".to_string(), Cow::Owned(b"<program/>".to_vec())),
+            ("A2.wav".to_string(), Cow::Borrowed(&[1, 2, 3][..])),
+        ];
+
+        let mut zip = Cursor::new(Vec::new());
+        write_zip(&mut zip, &files).unwrap();
+
+        let mut zip = ZipArchive::new(zip).unwrap();
+        let mut names = zip.file_names().map(|n| n.to_string()).collect_vec();
+        names.sort();
+        assert_eq!(names, vec!["A2.wav", "Piano.xpm"]);
+
+        let mut content = Vec::new();
+        zip.by_name("A2.wav")
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn write_folder_test() {
+        let folder = std::env::temp_dir().join("mpc_valet_write_folder_test");
+        let _ = std::fs::remove_dir_all(&folder);
+        let files = vec![
+            (
+                "Keys/Piano/Piano.xpm".to_string(),
+                Cow::Owned(b"<program/>".to_vec()),
+            ),
+            (
+                "Keys/Piano/A2.wav".to_string(),
+                Cow::Borrowed(&[1, 2, 3][..]),
+            ),
+        ];
+
+        write_folder(&folder, &files).unwrap();
+
+        let content = std::fs::read(folder.join("Keys/Piano/A2.wav")).unwrap();
+        assert_eq!(content, vec![1, 2, 3]);
+        assert!(folder.join("Keys/Piano/Piano.xpm").is_file());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
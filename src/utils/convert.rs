@@ -0,0 +1,284 @@
+use std::borrow::Cow;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{aiff_to_wav, flac_to_wav, resample, write_wav_with_metadata, Wav, WavFormat};
+
+/// Extensions of the sample files that can be read.
+pub const SAMPLE_EXTENSIONS: [&str; 4] = ["wav", "aif", "aiff", "flac"];
+
+/// Lower case extension of a file name.
+fn extension(file: &str) -> String {
+    file.rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Whether a file is a sample file that can be read.
+pub fn is_sample_file(file: &str) -> bool {
+    SAMPLE_EXTENSIONS.contains(&extension(file).as_str())
+}
+
+/// Name of a sample file once converted to WAV.
+pub fn wav_file_name(file: &str) -> Cow<'_, str> {
+    match file.rsplit_once('.') {
+        Some((stem, extension)) if extension.to_lowercase() != "wav" => {
+            Cow::Owned(format!("{}.wav", stem))
+        }
+        _ => Cow::Borrowed(file),
+    }
+}
+
+/// Content of a sample file as a WAV file.
+///
+/// WAV files are kept as is, AIFF and FLAC files are decoded.
+pub fn sample_to_wav<'a>(file: &str, content: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+    match extension(file).as_str() {
+        "aif" | "aiff" => Ok(Cow::Owned(aiff_to_wav(content)?)),
+        "flac" => Ok(Cow::Owned(flac_to_wav(content)?)),
+        _ => Ok(Cow::Borrowed(content)),
+    }
+}
+
+/// Format the samples are converted to when they are bundled.
+///
+/// The properties left unset are kept from each sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetFormat {
+    /// Sample rate in Hz.
+    pub sample_rate: Option<u32>,
+
+    /// Bit depth of each sample.
+    pub bits_per_sample: Option<u16>,
+
+    /// Number of channels, the channels are mixed down to mono.
+    pub channels: Option<u16>,
+
+    /// Add a low level noise when lowering the bit depth, to avoid the quantization distortion.
+    pub dither: bool,
+}
+
+impl Default for TargetFormat {
+    fn default() -> Self {
+        Self {
+            sample_rate: None,
+            bits_per_sample: None,
+            channels: None,
+            dither: true,
+        }
+    }
+}
+
+impl TargetFormat {
+    /// Format of a sample once converted.
+    pub fn apply(&self, format: &WavFormat) -> WavFormat {
+        WavFormat {
+            sample_rate: self.sample_rate.unwrap_or(format.sample_rate),
+            channels: self.channels.unwrap_or(format.channels),
+            bits_per_sample: self.bits_per_sample.unwrap_or(format.bits_per_sample),
+        }
+    }
+}
+
+/// Triangular noise generator, one least significant bit wide on each side.
+struct Dither(u32);
+
+impl Dither {
+    fn uniform(&mut self) -> f32 {
+        // xorshift32
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn triangular(&mut self) -> f32 {
+        self.uniform() - self.uniform()
+    }
+}
+
+/// Convert a WAV file to a target format.
+///
+/// The content is kept as is when the file is already in this format. The
+/// sampler metadata is kept.
+pub fn convert_wav<'a>(content: Cow<'a, [u8]>, target: &TargetFormat) -> Result<Cow<'a, [u8]>> {
+    if target.sample_rate.is_none() && target.bits_per_sample.is_none() && target.channels.is_none()
+    {
+        return Ok(content);
+    }
+    let wav = Wav::parse(&content)?;
+    let format = target.apply(&wav.format);
+    if format == wav.format {
+        return Ok(content);
+    }
+
+    // Planar channels, between -1 and 1
+    let source_channels = wav.format.channels as usize;
+    let channels = if format.channels == 1 {
+        vec![wav.mono()]
+    } else {
+        // The mono samples are duplicated, the extra channels are dropped
+        (0..format.channels as usize)
+            .map(|channel| {
+                (0..wav.frames())
+                    .map(|frame| {
+                        wav.point(frame, channel % source_channels) as f32 / 2_f32.powi(31)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    };
+    let channels = channels
+        .iter()
+        .map(|channel| resample(channel, wav.format.sample_rate, format.sample_rate))
+        .collect::<Vec<_>>();
+
+    let bytes = format.bits_per_sample.div_ceil(8) as usize;
+    let shift = bytes * 8 - format.bits_per_sample as usize;
+    let scale = 2_f32.powi(format.bits_per_sample as i32 - 1);
+    let frames = channels.first().map(Vec::len).unwrap_or_default();
+    let dithered = target.dither && format.bits_per_sample < wav.format.bits_per_sample;
+    let mut dither = Dither(0x1234_5678);
+    let mut data = Vec::with_capacity(frames * format.block_align());
+    for frame in 0..frames {
+        for channel in channels.iter() {
+            let mut point = channel[frame] * scale;
+            if dithered {
+                point += dither.triangular();
+            }
+            let point = (point.round().clamp(-scale, scale - 1.0) as i32) << shift;
+            if bytes == 1 {
+                // 8 bits WAV files are unsigned
+                data.push((point + 128) as u8);
+            } else {
+                data.extend_from_slice(&point.to_le_bytes()[..bytes]);
+            }
+        }
+    }
+
+    let mut converted = Vec::new();
+    write_wav_with_metadata(&mut converted, &format, &data, &wav.metadata)?;
+    Ok(Cow::Owned(converted))
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::{wav_from_points, write_wav};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("Piano A2.wav", "Piano A2.wav")]
+    #[case("Piano A2.WAV", "Piano A2.WAV")]
+    #[case("Piano A2.aif", "Piano A2.wav")]
+    #[case("Samples/Piano A2.AIFF", "Samples/Piano A2.wav")]
+    #[case("Piano A2.flac", "Piano A2.wav")]
+    fn wav_file_name_test(#[case] file: &str, #[case] expected: &str) {
+        assert!(is_sample_file(file));
+        assert_eq!(wav_file_name(file), expected);
+    }
+
+    #[test]
+    fn convert_same_format_test() {
+        let wav = wav_from_points(44100, 2, &[1, 2]);
+        let target = TargetFormat {
+            sample_rate: Some(44100),
+            ..Default::default()
+        };
+
+        let converted = convert_wav(Cow::Borrowed(&wav), &target).unwrap();
+
+        assert!(matches!(converted, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn convert_to_mono_24_bits_test() {
+        let wav = wav_from_points(44100, 2, &[0x100, 0x300, -0x100, -0x300]);
+        let target = TargetFormat {
+            bits_per_sample: Some(24),
+            channels: Some(1),
+            dither: false,
+            ..Default::default()
+        };
+
+        let converted = convert_wav(Cow::Borrowed(&wav), &target).unwrap();
+        let converted = Wav::parse(&converted).unwrap();
+
+        assert_eq!(
+            converted.format,
+            WavFormat {
+                sample_rate: 44100,
+                channels: 1,
+                bits_per_sample: 24,
+            }
+        );
+        assert_eq!(converted.data, &[0x00, 0x00, 0x02, 0x00, 0x00, 0xFE]);
+    }
+
+    #[test]
+    fn convert_to_stereo_test() {
+        let wav = wav_from_points(44100, 1, &[5, -5]);
+        let target = TargetFormat {
+            channels: Some(2),
+            dither: false,
+            ..Default::default()
+        };
+
+        let converted = convert_wav(Cow::Borrowed(&wav), &target).unwrap();
+
+        assert_eq!(
+            Wav::parse(&converted).unwrap().data,
+            &[5, 0, 5, 0, 0xFB, 0xFF, 0xFB, 0xFF]
+        );
+    }
+
+    #[test]
+    fn convert_dither_test() {
+        let mono = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 24,
+        };
+        let mut content = Vec::new();
+        write_wav(&mut content, &mono, &[0; 3000]).unwrap();
+        let target = TargetFormat {
+            bits_per_sample: Some(16),
+            ..Default::default()
+        };
+
+        let converted = convert_wav(Cow::Borrowed(&content), &target).unwrap();
+        let converted = Wav::parse(&converted).unwrap();
+
+        // The silence gets a noise of one bit at most
+        assert_eq!(converted.frames(), 1000);
+        let points = (0..1000)
+            .map(|frame| converted.point(frame, 0) >> 16)
+            .collect::<Vec<_>>();
+        assert!(points.iter().all(|point| (-1..=1).contains(point)));
+        assert!(points.iter().any(|point| *point != 0));
+    }
+
+    #[test]
+    fn convert_sample_rate_test() {
+        let wav = wav_from_points(44100, 1, &[0; 4410]);
+        let target = TargetFormat {
+            sample_rate: Some(48000),
+            ..Default::default()
+        };
+
+        let converted = convert_wav(Cow::Borrowed(&wav), &target).unwrap();
+        let converted = Wav::parse(&converted).unwrap();
+
+        assert_eq!(converted.format.sample_rate, 48000);
+        assert_eq!(converted.frames(), 4800);
+        // The bit depth is kept, so no noise is added
+        assert!(converted.data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn unsupported_file_test() {
+        assert!(!is_sample_file("Piano A2.mp3"));
+        assert!(!is_sample_file("Piano"));
+    }
+}
@@ -0,0 +1,469 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use xmltree::{AttributeMap, Element, XMLNode};
+
+use crate::model::{ImportWarning, Keygroup, Layer};
+
+use super::{group_layers, split_tuning};
+
+/// Sample attributes read when importing a preset.
+const MAPPED_ATTRIBUTES: [&str; 14] = [
+    "path",
+    "rootNote",
+    "loNote",
+    "hiNote",
+    "loVel",
+    "hiVel",
+    "tuning",
+    "start",
+    "end",
+    "loopEnabled",
+    "loopStart",
+    "loopEnd",
+    "loopCrossfade",
+    "volume",
+];
+
+/// Export keygroups to a Decent Sampler XML ready to be saved as .dspreset.
+pub fn make_dspreset<'a, I>(keygroups: I) -> Element
+where
+    I: IntoIterator<Item = &'a Keygroup>,
+{
+    let mut groups = Element::new("groups");
+
+    for keygroup in keygroups.into_iter() {
+        let mut group = Element::new("group");
+        for layer in keygroup.layers.iter().filter_map(|l| l.as_ref()) {
+            let mut sample = Element::new("sample");
+            let mut attributes = vec![
+                ("path", layer.file.clone()),
+                ("rootNote", layer.root.to_string()),
+                ("loNote", keygroup.range.start().to_string()),
+                ("hiNote", keygroup.range.end().to_string()),
+                ("loVel", layer.velocity.start().to_string()),
+                ("hiVel", layer.velocity.end().to_string()),
+            ];
+            if layer.tune != 0 {
+                attributes.push(("tuning", (layer.tune as f32 / 100.0).to_string()));
+            }
+            // The Decent Sampler end points are the last played frames
+            if let Some(region) = &layer.sample_region {
+                attributes.push(("start", region.start.to_string()));
+                attributes.push(("end", region.end.saturating_sub(1).to_string()));
+            }
+            if let Some(sample_loop) = &layer.sample_loop {
+                attributes.push(("loopEnabled", "true".to_string()));
+                attributes.push(("loopStart", sample_loop.start.to_string()));
+                attributes.push(("loopEnd", sample_loop.end.saturating_sub(1).to_string()));
+                if layer.loop_crossfade > 0 {
+                    attributes.push(("loopCrossfade", layer.loop_crossfade.to_string()));
+                }
+            }
+            if layer.volume != 0 {
+                attributes.push(("volume", format!("{:.1}dB", layer.volume as f32 / 10.0)));
+            }
+            for (name, value) in attributes {
+                sample.attributes.insert(name.to_string(), value);
+            }
+            group.children.push(XMLNode::Element(sample));
+        }
+        groups.children.push(XMLNode::Element(group));
+    }
+
+    let mut preset = Element::new("DecentSampler");
+    preset
+        .attributes
+        .insert("minVersion".to_string(), "1.0.0".to_string());
+    preset.children.push(XMLNode::Element(groups));
+    preset
+}
+
+/// Parse a sample attribute, with a default if it's not set.
+fn sample_attribute(
+    attributes: &AttributeMap<String, String>,
+    name: &str,
+    default: Option<u8>,
+) -> Result<u8> {
+    match attributes.get(name) {
+        Some(value) => value
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|v| *v <= 127)
+            .context(format!("{} has an invalid value {}", name, value)),
+        None => default.context(format!("{} is missing", name)),
+    }
+}
+
+/// Parse a numeric sample attribute, if it's set.
+fn sample_number<T: FromStr>(
+    attributes: &AttributeMap<String, String>,
+    name: &str,
+) -> Result<Option<T>> {
+    attributes
+        .get(name)
+        .map(|value| {
+            value
+                .trim()
+                .parse::<T>()
+                .ok()
+                .context(format!("{} has an invalid value {}", name, value))
+        })
+        .transpose()
+}
+
+/// Parse the volume of a sample, either linear or in dB (ie. "-3dB"), in tenths of dB.
+fn sample_volume(attributes: &AttributeMap<String, String>) -> Result<i16> {
+    let Some(value) = attributes.get("volume") else {
+        return Ok(0);
+    };
+    let trimmed = value.trim();
+    let db = match trimmed.strip_suffix("dB").or(trimmed.strip_suffix("db")) {
+        Some(db) => db.trim().parse::<f32>().ok(),
+        None => trimmed
+            .parse::<f32>()
+            .ok()
+            .map(|gain| 20.0 * gain.max(0.001).log10()),
+    }
+    .context(format!("volume has an invalid value {}", value))?;
+    Ok((db * 10.0).round() as i16)
+}
+
+/// Read the key range and the layer of a sample.
+fn sample_layer(
+    attributes: &AttributeMap<String, String>,
+    path: &str,
+) -> Result<(RangeInclusive<u8>, Layer)> {
+    let root = sample_attribute(attributes, "rootNote", None)?;
+    let low_note = sample_attribute(attributes, "loNote", Some(0))?;
+    let high_note = sample_attribute(attributes, "hiNote", Some(127))?;
+    let low_velocity = sample_attribute(attributes, "loVel", Some(0))?;
+    let high_velocity = sample_attribute(attributes, "hiVel", Some(127))?;
+    let tuning: f32 = sample_number(attributes, "tuning")?.unwrap_or(0.0);
+    let (root, tune) = split_tuning(root, (tuning * 100.0).round() as i32);
+
+    // The Decent Sampler end points are the last played frames
+    let start: usize = sample_number(attributes, "start")?.unwrap_or(0);
+    let end: Option<usize> = sample_number(attributes, "end")?;
+    let sample_region = end.map(|end| start..end + 1);
+
+    let looped = attributes
+        .get("loopEnabled")
+        .is_none_or(|enabled| enabled.trim() == "true");
+    let loop_start: Option<usize> = sample_number(attributes, "loopStart")?;
+    let loop_end: Option<usize> = sample_number(attributes, "loopEnd")?;
+    let sample_loop = match (loop_start, loop_end) {
+        (Some(start), Some(end)) if looped => Some(start..end + 1),
+        _ => None,
+    };
+    let loop_crossfade = sample_number(attributes, "loopCrossfade")?
+        .filter(|_| sample_loop.is_some())
+        .unwrap_or(0);
+
+    Ok((
+        low_note..=high_note,
+        Layer {
+            tune,
+            sample_region,
+            sample_loop,
+            loop_crossfade,
+            volume: sample_volume(attributes)?,
+            ..Layer::new(path.replace('\\', "/"), root, low_velocity..=high_velocity)
+        },
+    ))
+}
+
+/// Import the samples of a Decent Sampler XML as keygroups.
+///
+/// Samples sharing the same key range are grouped as layers of a keygroup.
+/// Every attribute that can't be represented is reported as a warning.
+pub fn parse_dspreset(preset: &Element) -> Result<(Vec<Keygroup>, Vec<ImportWarning>)> {
+    if preset.name != "DecentSampler" {
+        bail!("The file is not a Decent Sampler preset");
+    }
+
+    let mut warnings = Vec::new();
+    for child in preset.children.iter().filter_map(|c| c.as_element()) {
+        if child.name != "groups" {
+            warnings.push(ImportWarning::new(
+                format!("<{}>", child.name),
+                "Element is not supported",
+            ));
+        }
+    }
+
+    let mut samples = Vec::new();
+    for groups in preset
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|e| e.name == "groups")
+    {
+        let groups_children = groups.children.iter().filter_map(|c| c.as_element());
+        for group in groups_children.filter(|e| e.name == "group") {
+            let group_children = group.children.iter().filter_map(|c| c.as_element());
+            for sample in group_children.filter(|e| e.name == "sample") {
+                // Sample attributes can be set on their groups
+                let mut attributes = groups.attributes.clone();
+                attributes.extend(group.attributes.clone());
+                attributes.extend(sample.attributes.clone());
+                samples.push(attributes);
+            }
+        }
+    }
+
+    // Report the unsupported attributes once, with the number of samples using them
+    let unsupported = samples
+        .iter()
+        .flat_map(|sample| sample.keys())
+        .filter(|attribute| !MAPPED_ATTRIBUTES.contains(&attribute.as_str()))
+        .counts();
+    for (attribute, count) in unsupported.into_iter().sorted() {
+        warnings.push(ImportWarning::new(
+            "Decent Sampler",
+            format!(
+                "{} is not supported, it is used by {} samples",
+                attribute, count
+            ),
+        ));
+    }
+
+    let mut layers = Vec::new();
+    for (index, sample) in samples.iter().enumerate() {
+        let location = format!("<sample> {}", index + 1);
+        let Some(path) = sample.get("path") else {
+            warnings.push(ImportWarning::new(location, "No path, it is ignored"));
+            continue;
+        };
+
+        if sample.contains_key("start") && !sample.contains_key("end") {
+            warnings.push(ImportWarning::new(
+                &location,
+                "start is only supported with end, it is ignored",
+            ));
+        }
+        match sample_layer(sample, path) {
+            Ok(layer) => layers.push(layer),
+            Err(e) => warnings.push(ImportWarning::new(
+                location,
+                format!("{}, it is ignored", e),
+            )),
+        }
+    }
+
+    let keygroups = group_layers(layers, &mut warnings);
+
+    if keygroups.is_empty() {
+        bail!("The preset does not contain any sample");
+    }
+
+    Ok((keygroups, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn dspreset_round_trip_test() {
+        let keygroups = vec![
+            Keygroup::new(
+                0..=50,
+                [
+                    Some(Layer::new("Samples/A2.wav".to_string(), 45, 0..=63)),
+                    Some(Layer::new("Samples/A2 loud.wav".to_string(), 45, 64..=127)),
+                    None,
+                    None,
+                ],
+            ),
+            Keygroup::new(
+                51..=127,
+                [
+                    Some(Layer {
+                        tune: -30,
+                        sample_region: Some(100..20000),
+                        sample_loop: Some(5000..15000),
+                        loop_crossfade: 500,
+                        volume: -35,
+                        ..Layer::new("Samples/C3.wav".to_string(), 48, 0..=127)
+                    }),
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+        ];
+
+        let preset = make_dspreset(&keygroups);
+        let (imported, warnings) = parse_dspreset(&preset).unwrap();
+
+        assert_eq!(imported, keygroups);
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn parse_dspreset_inheritance_test() {
+        let preset = r#"
+            <DecentSampler minVersion="1.0.0">
+                <ui width="812" height="375" />
+                <groups attack="0.1">
+                    <group loNote="60" hiNote="71" rootNote="60">
+                        <sample path="c4 soft.wav" hiVel="63" />
+                        <sample path="c4 loud.wav" loVel="64" />
+                    </group>
+                </groups>
+            </DecentSampler>
+        "#;
+        let preset = Element::parse(preset.as_bytes()).unwrap();
+
+        let (keygroups, warnings) = parse_dspreset(&preset).unwrap();
+
+        assert_eq!(
+            keygroups,
+            vec![Keygroup::new(
+                60..=71,
+                [
+                    Some(Layer::new("c4 soft.wav".to_string(), 60, 0..=63)),
+                    Some(Layer::new("c4 loud.wav".to_string(), 60, 64..=127)),
+                    None,
+                    None,
+                ],
+            )]
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                ImportWarning::new("<ui>", "Element is not supported"),
+                ImportWarning::new(
+                    "Decent Sampler",
+                    "attack is not supported, it is used by 2 samples"
+                ),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("3dB", 30)]
+    #[case(" -6.5 dB ", -65)]
+    #[case("0.5", -60)]
+    #[case("1", 0)]
+    fn sample_volume_test(#[case] volume: &str, #[case] expected: i16) {
+        let mut attributes = AttributeMap::new();
+        attributes.insert("volume".to_string(), volume.to_string());
+
+        assert_eq!(sample_volume(&attributes).unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_dspreset_sample_settings_test() {
+        let preset = r#"
+            <DecentSampler>
+                <groups>
+                    <group>
+                        <sample path="a.wav" rootNote="60" loNote="60" hiNote="60" tuning="1.5"
+                            start="10" end="99" loopStart="20" loopEnd="79" volume="0.5" />
+                        <sample path="b.wav" rootNote="61" loNote="61" hiNote="61" start="10"
+                            loopEnabled="false" loopStart="20" loopEnd="79" />
+                    </group>
+                </groups>
+            </DecentSampler>
+        "#;
+        let preset = Element::parse(preset.as_bytes()).unwrap();
+
+        let (keygroups, warnings) = parse_dspreset(&preset).unwrap();
+
+        assert_eq!(
+            keygroups,
+            vec![
+                Keygroup::new(
+                    60..=60,
+                    [
+                        Some(Layer {
+                            tune: -50,
+                            sample_region: Some(10..100),
+                            sample_loop: Some(20..80),
+                            volume: -60,
+                            ..Layer::new("a.wav".to_string(), 58, 0..=127)
+                        }),
+                        None,
+                        None,
+                        None,
+                    ],
+                ),
+                Keygroup::new(
+                    61..=61,
+                    [
+                        Some(Layer::new("b.wav".to_string(), 61, 0..=127)),
+                        None,
+                        None,
+                        None,
+                    ],
+                ),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![ImportWarning::new(
+                "<sample> 2",
+                "start is only supported with end, it is ignored"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_dspreset_missing_root_test() {
+        let preset = r#"
+            <DecentSampler>
+                <groups><group><sample path="a.wav" /></group></groups>
+            </DecentSampler>
+        "#;
+        let preset = Element::parse(preset.as_bytes()).unwrap();
+
+        assert!(parse_dspreset(&preset).is_err());
+    }
+
+    #[test]
+    fn parse_dspreset_invalid_sample_test() {
+        let preset = r#"
+            <DecentSampler>
+                <groups>
+                    <group>
+                        <sample path="a.wav" />
+                        <sample path="b.wav" rootNote="60" loNote="200" />
+                        <sample path="c.wav" rootNote="60" />
+                    </group>
+                </groups>
+            </DecentSampler>
+        "#;
+        let preset = Element::parse(preset.as_bytes()).unwrap();
+
+        let (keygroups, warnings) = parse_dspreset(&preset).unwrap();
+
+        assert_eq!(
+            keygroups,
+            vec![Keygroup::new(
+                0..=127,
+                [
+                    Some(Layer::new("c.wav".to_string(), 60, 0..=127)),
+                    None,
+                    None,
+                    None,
+                ],
+            )]
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                ImportWarning::new("<sample> 1", "rootNote is missing, it is ignored"),
+                ImportWarning::new(
+                    "<sample> 2",
+                    "loNote has an invalid value 200, it is ignored"
+                ),
+            ]
+        );
+    }
+}
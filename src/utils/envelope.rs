@@ -0,0 +1,120 @@
+//! Envelope analysis of the samples, to choose how they are played.
+use std::fmt::Display;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::Wav;
+
+/// Duration of the windows of the level envelope, in seconds.
+const WINDOW: f32 = 0.01;
+
+/// Time after the peak where the level is compared to the peak, in seconds.
+const SUSTAIN_DELAY: f32 = 0.5;
+
+/// Drop of the level after the peak making a sample percussive, in dB.
+const PERCUSSIVE_DROP: f32 = 20.0;
+
+/// Level under which a sample is considered silent.
+const SILENCE: f32 = 1e-4;
+
+/// How a sample is played.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Articulation {
+    /// A hit fading out by itself, played to its end.
+    Percussive,
+
+    /// A tone held as long as the note, with a short release.
+    Sustained,
+}
+
+impl Display for Articulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Articulation::Percussive => write!(f, "Percussive"),
+            Articulation::Sustained => write!(f, "Sustained"),
+        }
+    }
+}
+
+/// Classify a region of a sample as percussive or sustained, from its envelope.
+///
+/// A sample is percussive when its level dropped by 20 dB half a second
+/// after its peak. When the region ends before, the last level is used: it
+/// is percussive if it already dropped, and None is returned otherwise as the
+/// region is too short to judge. None is also returned for a silent sample.
+pub fn classify_envelope(wav: &Wav<'_>, region: Range<usize>) -> Option<Articulation> {
+    let points = wav.mono();
+    let end = region.end.min(points.len());
+    let start = region.start.min(end);
+    let window = ((WINDOW * wav.format.sample_rate as f32) as usize).max(1);
+    let levels = points[start..end]
+        .chunks(window)
+        .map(|chunk| (chunk.iter().map(|p| p * p).sum::<f32>() / chunk.len() as f32).sqrt())
+        .collect::<Vec<_>>();
+
+    let (peak_index, &peak) = levels
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if peak < SILENCE {
+        return None;
+    }
+
+    let delay = ((SUSTAIN_DELAY / WINDOW) as usize).max(1);
+    let later = levels.get(peak_index + delay);
+    let level = later.or(levels.last()).copied().unwrap_or(0.0);
+    let drop = 20.0 * (peak / level.max(SILENCE * 1e-2)).log10();
+    if drop >= PERCUSSIVE_DROP {
+        Some(Articulation::Percussive)
+    } else if later.is_some() {
+        Some(Articulation::Sustained)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::utils::wav_from_points;
+
+    /// A tone sampled at 1000 Hz, with an exponential decay (in dB per second).
+    fn tone(decay: f32, frames: usize) -> Vec<u8> {
+        let points = (0..frames)
+            .map(|frame| {
+                let t = frame as f32 / 1000.0;
+                let level = 10_f32.powf(-decay * t / 20.0);
+                let point = 20000.0 * level * (t * 2.0 * std::f32::consts::PI * 110.0).sin();
+                point as i16
+            })
+            .collect::<Vec<_>>();
+        wav_from_points(1000, 1, &points)
+    }
+
+    #[rstest]
+    #[case(tone(80.0, 1000), 0..1000, Some(Articulation::Percussive))]
+    #[case(tone(3.0, 1000), 0..1000, Some(Articulation::Sustained))]
+    #[case(tone(80.0, 1000), 0..300, Some(Articulation::Percussive))]
+    #[case(tone(3.0, 1000), 0..200, None)]
+    #[case(tone(3.0, 1000), 0..0, None)]
+    fn classify_envelope_test(
+        #[case] wav: Vec<u8>,
+        #[case] region: Range<usize>,
+        #[case] expected: Option<Articulation>,
+    ) {
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(classify_envelope(&wav, region), expected);
+    }
+
+    #[test]
+    fn classify_silence_test() {
+        let wav = wav_from_points(1000, 1, &[0; 1000]);
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(classify_envelope(&wav, 0..1000), None);
+    }
+}
@@ -0,0 +1,75 @@
+use xmltree::{Element, XMLNode};
+
+use crate::model::Expansion;
+
+use super::bundled_sample_name;
+
+/// Make an element only containing text.
+fn text_element(name: &str, text: &str) -> Element {
+    let mut element = Element::new(name);
+    if !text.is_empty() {
+        element.children.push(XMLNode::Text(text.to_string()));
+    }
+    element
+}
+
+/// Export the description of an expansion to an XML ready to be saved as Expansion.xml.
+pub fn make_expansion(expansion: &Expansion) -> Element {
+    let mut xml = Element::new("expansion");
+    xml.attributes
+        .insert("version".to_string(), "2.0.0.0".to_string());
+    xml.attributes
+        .insert("buildVersion".to_string(), "2.10.0.0".to_string());
+
+    let version = match expansion.version.trim() {
+        "" => "1.0.0",
+        version => version,
+    };
+    let mut children = vec![
+        Element::new("local"),
+        text_element("identifier", &expansion.identifier()),
+        text_element("title", &expansion.title),
+        text_element("manufacturer", &expansion.manufacturer),
+        text_element("version", version),
+        text_element("type", "instrument"),
+        text_element("priority", "50"),
+    ];
+    if let Some(cover) = &expansion.cover {
+        children.push(text_element("img", bundled_sample_name(cover)));
+    }
+    children.push(text_element("description", &expansion.description));
+    children.push(text_element("separator", "-"));
+
+    xml.children
+        .extend(children.into_iter().map(XMLNode::Element));
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+
+    #[test]
+    fn make_expansion_test() {
+        let expansion = Expansion {
+            title: "Keys".to_string(),
+            manufacturer: "Valet".to_string(),
+            cover: Some("Images/cover.png".to_string()),
+            ..Default::default()
+        };
+
+        let xml = make_expansion(&expansion);
+
+        let text = |name: &str| {
+            xml.get_child(name)
+                .unwrap()
+                .get_text()
+                .map(|t| t.to_string())
+        };
+        assert_eq!(text("identifier"), Some("com.valet.keys".to_string()));
+        assert_eq!(text("title"), Some("Keys".to_string()));
+        assert_eq!(text("version"), Some("1.0.0".to_string()));
+        assert_eq!(text("img"), Some("cover.png".to_string()));
+        assert_eq!(text("description"), None);
+    }
+}
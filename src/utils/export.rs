@@ -1,7 +1,11 @@
+use std::collections::BTreeMap;
+
 use anyhow::{bail, Context, Result};
 use xmltree::{Element, XMLNode};
 
-use crate::model::Keygroup;
+use crate::model::{Keygroup, Layer, Pad};
+
+use super::Articulation;
 
 /// Trait for the ability to set a child member.
 ///
@@ -34,14 +38,107 @@ impl SetText for Element {
     }
 }
 
+/// Parse the reference program used as a template for all the exports.
+pub(crate) fn reference_program() -> Result<Element> {
+    let reference = include_str!("Reference.xpm");
+    Element::parse(reference.as_bytes()).context("Failed to parse the reference XPM")
+}
+
+/// Parse the reference drum program used as a template for the drum exports.
+pub(crate) fn drum_reference_program() -> Result<Element> {
+    let reference = include_str!("DrumReference.xpm");
+    Element::parse(reference.as_bytes()).context("Failed to parse the reference drum XPM")
+}
+
+/// One-shot flag and amp envelope of an instrument, for an articulation.
+pub(crate) fn articulation_settings(
+    articulation: Articulation,
+) -> [(&'static str, &'static str); 6] {
+    match articulation {
+        // Played to the end of the sample, with a short fade out
+        Articulation::Percussive => [
+            ("OneShot", "True"),
+            ("VolumeADEnvelope", "True"),
+            ("VolumeAttack", "0.000000"),
+            ("VolumeDecay", "0.047244"),
+            ("VolumeSustain", "1.000000"),
+            ("VolumeRelease", "0.000000"),
+        ],
+        // Held while the note is on, with a release
+        Articulation::Sustained => [
+            ("OneShot", "False"),
+            ("VolumeADEnvelope", "False"),
+            ("VolumeAttack", "0.000000"),
+            ("VolumeDecay", "0.047244"),
+            ("VolumeSustain", "1.000000"),
+            ("VolumeRelease", "0.300000"),
+        ],
+    }
+}
+
+/// Assign the sample files of the layers of an instrument.
+fn set_layers(instrument: &mut Element, layers: &[Option<Layer>; 4]) -> Result<()> {
+    let program_layers = instrument
+        .get_mut_child("Layers")
+        .context("Failed to find the XPM reference program layers")?;
+
+    let program_layers: Vec<&mut Element> = program_layers
+        .children
+        .iter_mut()
+        .filter_map(|c| match c {
+            XMLNode::Element(e) => Some(e),
+            _ => None,
+        })
+        .filter(|e| e.name == "Layer")
+        .collect();
+
+    if program_layers.len() != 4 {
+        bail!("The reference program does not contain 4 layers");
+    }
+
+    for (layer, program_layer) in layers.iter().zip(program_layers) {
+        if let Some(layer) = layer {
+            let sample_file = layer.file.clone();
+
+            let sample_name = std::path::Path::new(&sample_file)
+                .file_stem()
+                .context("Failed to find the sample base name")?
+                .to_str()
+                .context("The sample does not have a valid base name")?
+                .to_string();
+
+            let velocity_start = layer.velocity.start().to_string();
+            let velocity_end = layer.velocity.end().to_string();
+
+            program_layer.set_child_text("SampleName", sample_name)?;
+            program_layer.set_child_text("SampleFile", sample_file)?;
+            program_layer.set_child_text("VelStart", velocity_start)?;
+            program_layer.set_child_text("VelEnd", velocity_end)?;
+            let root_note = (layer.root as u32) + 1; // off by one in the file format
+            program_layer.set_child_text("RootNote", root_note.to_string())?;
+            program_layer.set_child_text("TuneFine", layer.tune.to_string())?;
+            program_layer.set_child_text("Volume", format!("{:.6}", layer.gain()))?;
+            if let Some(region) = &layer.sample_region {
+                program_layer.set_child_text("SampleStart", region.start.to_string())?;
+                program_layer.set_child_text("SampleEnd", region.end.to_string())?;
+            }
+            if let Some(sample_loop) = &layer.sample_loop {
+                program_layer.set_child_text("Loop", "True".to_string())?;
+                program_layer.set_child_text("LoopStart", sample_loop.start.to_string())?;
+                program_layer.set_child_text("LoopEnd", sample_loop.end.to_string())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Export a keygroup program to an XML ready to be saved as .xpm.
 pub fn make_program<'a, I>(name: &str, keygroups: I) -> Result<Element>
 where
     I: IntoIterator<Item = &'a Keygroup>,
 {
-    let reference = include_str!("Reference.xpm");
-    let mut program_root =
-        Element::parse(reference.as_bytes()).context("Failed to parse the reference XPM")?;
+    let mut program_root = reference_program()?;
     let program = program_root
         .get_mut_child("Program")
         .context("Failed to get the XPM root program")?;
@@ -71,54 +168,70 @@ where
             .attributes
             .insert("number".to_string(), keygroup_number.to_string());
 
-        let program_layers = program_keygroup
-            .get_mut_child("Layers")
-            .context("Failed to find the XPM reference program layers")?;
+        if let Some(bpm) = keygroup.bpm() {
+            program_keygroup.set_child_text("WarpTempo", format!("{:.6}", bpm))?;
+        }
+        if keygroup.warp {
+            program_keygroup.set_child_text("WarpEnable", "True".to_string())?;
+        }
+        if keygroup.fixed_pitch {
+            program_keygroup.set_child_text("IgnoreBaseNote", "True".to_string())?;
+        }
+        if let Some(articulation) = keygroup.articulation {
+            for (child, value) in articulation_settings(articulation) {
+                program_keygroup.set_child_text(child, value.to_string())?;
+            }
+        }
+
+        set_layers(&mut program_keygroup, &keygroup.layers)?;
 
-        let program_layers: Vec<&mut Element> = program_layers
+        program_keygroups
             .children
-            .iter_mut()
-            .filter_map(|c| match c {
-                XMLNode::Element(e) => Some(e),
-                _ => None,
-            })
-            .filter(|e| e.name == "Layer")
-            .collect();
+            .push(XMLNode::Element(program_keygroup));
+    }
 
-        if program_layers.len() != 4 {
-            bail!("The reference program does not contain 4 layers");
-        }
+    program.set_child_text("KeygroupNumKeygroups", num_keygroups.to_string())?;
 
-        for (layer, program_layer) in keygroup.layers.iter().zip(program_layers) {
-            if let Some(layer) = layer {
-                let sample_file = layer.file.clone();
+    Ok(program_root)
+}
 
-                let sample_name = std::path::Path::new(&sample_file)
-                    .file_stem()
-                    .context("Failed to find the sample base name")?
-                    .to_str()
-                    .context("The sample does not have a valid base name")?
-                    .to_string();
+/// Export a drum program to an XML ready to be saved as .xpm.
+///
+/// A drum program has one instrument per note, the pads trigger them
+/// through the pad note map of the reference program.
+pub fn make_drum_program<'a, I>(name: &str, pads: I) -> Result<Element>
+where
+    I: IntoIterator<Item = &'a Pad>,
+{
+    let mut program_root = drum_reference_program()?;
+    let program = program_root
+        .get_mut_child("Program")
+        .context("Failed to get the XPM root program")?;
 
-                let velocity_start = layer.velocity.start().to_string();
-                let velocity_end = layer.velocity.end().to_string();
+    program.set_child_text("ProgramName", name.to_string())?;
 
-                program_layer.set_child_text("SampleName", sample_name)?;
-                program_layer.set_child_text("SampleFile", sample_file)?;
-                program_layer.set_child_text("VelStart", velocity_start)?;
-                program_layer.set_child_text("VelEnd", velocity_end)?;
-                let root_note = (layer.root as u32) + 1; // off by one in the file format
-                program_layer.set_child_text("RootNote", root_note.to_string())?;
-            }
+    let program_instruments = program
+        .get_mut_child("Instruments")
+        .context("Failed to get the XPM instruments")?;
+    let reference_instrument = program_instruments
+        .take_child("Instrument")
+        .context("Failed to get the XPM reference instrument")?;
+
+    let pads: BTreeMap<u8, &Pad> = pads.into_iter().map(|pad| (pad.note(), pad)).collect();
+    for note in 0..=127_u8 {
+        let mut program_instrument = reference_instrument.clone();
+        program_instrument
+            .attributes
+            .insert("number".to_string(), note.to_string());
+        if let Some(pad) = pads.get(&note) {
+            set_layers(&mut program_instrument, &pad.layers)?;
         }
 
-        program_keygroups
+        program_instruments
             .children
-            .push(XMLNode::Element(program_keygroup));
+            .push(XMLNode::Element(program_instrument));
     }
 
-    program.set_child_text("KeygroupNumKeygroups", num_keygroups.to_string())?;
-
     Ok(program_root)
 }
 
@@ -194,4 +307,81 @@ mod tests {
             "56"
         );
     }
+
+    #[test]
+    fn make_drum_program_test() {
+        let program = make_drum_program(
+            "Drums",
+            &vec![Pad::new(
+                1,
+                [
+                    Some(Layer::new("snare.wav".to_string(), 60, 0..=127)),
+                    None,
+                    None,
+                    None,
+                ],
+            )],
+        )
+        .expect("Could not make the drum program at all");
+
+        let program = program.get_child("Program").expect("no program root");
+        assert_eq!(program.attributes["type"], "Drum");
+
+        let instruments: Vec<&Element> = program
+            .get_child("Instruments")
+            .expect("no instrument list")
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .collect();
+        assert_eq!(instruments.len(), 128);
+
+        // A02 triggers the note 36
+        let sample_files: Vec<(String, String)> = instruments
+            .iter()
+            .map(|instrument| {
+                let sample_file = instrument
+                    .get_child("Layers")
+                    .expect("no layer list")
+                    .get_child("Layer")
+                    .expect("no layer in the list")
+                    .get_child("SampleFile")
+                    .expect("no sample file")
+                    .get_text()
+                    .unwrap_or_default()
+                    .to_string();
+                (instrument.attributes["number"].clone(), sample_file)
+            })
+            .filter(|(_, sample_file)| !sample_file.is_empty())
+            .collect();
+        assert_eq!(
+            sample_files,
+            vec![("36".to_string(), "snare.wav".to_string())]
+        );
+    }
+
+    #[test]
+    fn drum_reference_pad_note_map_test() {
+        let program = drum_reference_program().unwrap();
+        let notes: Vec<u8> = program
+            .get_child("Program")
+            .expect("no program root")
+            .get_child("PadNoteMap")
+            .expect("no pad note map")
+            .children
+            .iter()
+            .filter_map(|c| c.as_element())
+            .map(|pad_note| {
+                pad_note
+                    .get_child("Note")
+                    .expect("no pad note")
+                    .get_text()
+                    .expect("no pad note text")
+                    .parse()
+                    .expect("invalid pad note")
+            })
+            .collect();
+
+        assert_eq!(notes, crate::model::PAD_NOTES);
+    }
 }
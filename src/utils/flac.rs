@@ -0,0 +1,77 @@
+use anyhow::{bail, Result};
+use claxon::FlacReader;
+
+use super::{write_wav, WavFormat};
+
+/// Decode a FLAC file to a WAV file.
+pub fn flac_to_wav(flac: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = FlacReader::new(flac)?;
+    let info = reader.streaminfo();
+    let format = WavFormat {
+        sample_rate: info.sample_rate,
+        channels: info.channels as u16,
+        bits_per_sample: info.bits_per_sample as u16,
+    };
+    if !(1..=32).contains(&format.bits_per_sample) {
+        bail!("The FLAC file format is invalid");
+    }
+
+    // WAV samples are aligned on the most significant bits of their bytes
+    let bytes = format.bits_per_sample.div_ceil(8) as usize;
+    let shift = bytes * 8 - format.bits_per_sample as usize;
+    let mut data = Vec::with_capacity(info.samples.unwrap_or_default() as usize * bytes);
+    for sample in reader.samples() {
+        let sample = sample? << shift;
+        if bytes == 1 {
+            // 8 bits WAV files are unsigned
+            data.push((sample + 128) as u8);
+        } else {
+            data.extend_from_slice(&sample.to_le_bytes()[..bytes]);
+        }
+    }
+
+    let mut wav = Vec::new();
+    write_wav(&mut wav, &format, &data)?;
+    Ok(wav)
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::Wav;
+
+    /// Mono 16 bits FLAC file at 44100 Hz, starting with the samples 0, 1000, -1000 and 32767.
+    const FLAC: [u8; 84] = [
+        0x66, 0x4C, 0x61, 0x43, 0x80, 0x00, 0x00, 0x22, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x0A, 0xC4, 0x40, 0xF0, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xF8, 0x60,
+        0x08, 0x00, 0x0F, 0x96, 0x02, 0x00, 0x00, 0x03, 0xE8, 0xFC, 0x18, 0x7F, 0xFF, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4D, 0x56,
+    ];
+
+    #[test]
+    fn flac_to_wav_test() {
+        let wav = flac_to_wav(&FLAC).unwrap();
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(
+            wav.format,
+            WavFormat {
+                sample_rate: 44100,
+                channels: 1,
+                bits_per_sample: 16,
+            }
+        );
+        let points = (0..wav.frames())
+            .map(|frame| wav.point(frame, 0) >> 16)
+            .collect::<Vec<_>>();
+        assert_eq!(points[..4], [0, 1000, -1000, 32767]);
+        assert_eq!(points.len(), 16);
+    }
+
+    #[test]
+    fn invalid_flac_test() {
+        assert!(flac_to_wav(b"RIFF").is_err());
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use xmltree::Element;
+
+use crate::model::{ImportWarning, Keygroup, KeygroupProgram, Layer};
+
+use super::{articulation_settings, reference_program, Articulation};
+
+/// Iterate over the children elements with a given name.
+fn child_elements<'a>(element: &'a Element, name: &'a str) -> impl Iterator<Item = &'a Element> {
+    element
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(move |e| e.name == name)
+}
+
+/// Text content of a child element, if any.
+fn child_text(element: &Element, child: &str) -> Option<String> {
+    element
+        .get_child(child)
+        .and_then(|e| e.get_text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Parse the text content of a child element.
+fn parse_child<T: std::str::FromStr>(element: &Element, child: &str) -> Result<Option<T>> {
+    child_text(element, child)
+        .map(|text| {
+            text.parse::<T>()
+                .ok()
+                .context(format!("Invalid value for {}: {}", child, text))
+        })
+        .transpose()
+}
+
+/// Report the fields of an element that differ from the reference and are not mapped.
+///
+/// Only the "leaf" fields (containing text) are compared. The mapped fields
+/// are the ones read by the importer, that will be written back on export.
+fn warn_unmapped(
+    element: &Element,
+    reference: &Element,
+    mapped: &[&str],
+    location: &str,
+    warnings: &mut Vec<ImportWarning>,
+) {
+    let leaves = element
+        .children
+        .iter()
+        .filter_map(|c| c.as_element())
+        .filter(|e| !e.children.iter().any(|c| c.as_element().is_some()))
+        .filter(|e| !mapped.contains(&e.name.as_str()));
+
+    let mut unknown = Vec::new();
+    for leaf in leaves {
+        let value = leaf.get_text().unwrap_or_default();
+        match reference.get_child(leaf.name.as_str()) {
+            Some(reference_leaf) => {
+                let reference_value = reference_leaf.get_text().unwrap_or_default();
+                if value.trim() != reference_value.trim() {
+                    warnings.push(ImportWarning::new(
+                        location,
+                        format!(
+                            "{} is {}, it is not supported and will be reset to {}",
+                            leaf.name,
+                            value.trim(),
+                            reference_value.trim()
+                        ),
+                    ));
+                }
+            }
+            None => unknown.push(leaf.name.clone()),
+        }
+    }
+
+    if !unknown.is_empty() {
+        warnings.push(ImportWarning::new(
+            location,
+            format!("Unknown fields are ignored: {}", unknown.join(", ")),
+        ));
+    }
+}
+
+/// Split a tuning in cents between the root note and a fine tuning.
+///
+/// A sample tuned up by a semitone plays as if its root note was a semitone lower.
+pub(crate) fn split_tuning(root: u8, cents: i32) -> (u8, i8) {
+    let semitones = (cents as f32 / 100.0).round() as i32;
+    let shifted_root = (root as i32 - semitones).clamp(0, 127);
+    let tune = cents - (root as i32 - shifted_root) * 100;
+    (
+        shifted_root as u8,
+        tune.clamp(i8::MIN as i32, i8::MAX as i32) as i8,
+    )
+}
+
+/// Group layers sharing the same key range in keygroups.
+///
+/// This is used by the formats describing each sample with its own key range
+/// instead of keygroups. The layers of a keygroup are sorted by velocity.
+pub(crate) fn group_layers<I>(layers: I, warnings: &mut Vec<ImportWarning>) -> Vec<Keygroup>
+where
+    I: IntoIterator<Item = (RangeInclusive<u8>, Layer)>,
+{
+    let mut key_ranges = BTreeMap::<(u8, u8), Vec<Layer>>::new();
+    for (range, layer) in layers.into_iter() {
+        key_ranges
+            .entry((*range.start(), *range.end()))
+            .or_default()
+            .push(layer);
+    }
+
+    let mut keygroups = Vec::new();
+    for ((low_key, high_key), layers) in key_ranges {
+        let mut keygroup_layers: [Option<Layer>; 4] = Default::default();
+        let layers = layers
+            .into_iter()
+            .sorted_by_key(|l| *l.velocity.start())
+            .collect_vec();
+        for (index, layer) in layers.into_iter().enumerate() {
+            match keygroup_layers.get_mut(index) {
+                Some(slot) => *slot = Some(layer),
+                None => warnings.push(ImportWarning::new(
+                    format!("Keys {} to {}", low_key, high_key),
+                    format!("Only 4 layers are supported, {} is ignored", layer.file),
+                )),
+            }
+        }
+        keygroups.push(Keygroup::new(low_key..=high_key, keygroup_layers));
+    }
+    keygroups
+}
+
+/// Import a keygroup program from an XPM XML.
+///
+/// Every field that can't be represented in the model is reported as a warning.
+pub fn parse_program(program_root: &Element) -> Result<(KeygroupProgram, Vec<ImportWarning>)> {
+    let reference_root = reference_program()?;
+    let reference = reference_root
+        .get_child("Program")
+        .context("Failed to get the reference program")?;
+    let reference_instrument = reference
+        .get_child("Instruments")
+        .and_then(|i| i.get_child("Instrument"))
+        .context("Failed to get the reference instrument")?;
+    let reference_tempo = child_text(reference_instrument, "WarpTempo");
+    let reference_layer = reference_instrument
+        .get_child("Layers")
+        .and_then(|l| l.get_child("Layer"))
+        .context("Failed to get the reference layer")?;
+
+    let program = program_root
+        .get_child("Program")
+        .context("The file does not contain an MPC program")?;
+
+    if let Some(program_type) = program.attributes.get("type") {
+        if program_type != "Keygroup" {
+            bail!("Unsupported program type: {}", program_type);
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let name = child_text(program, "ProgramName").unwrap_or_default();
+    warn_unmapped(
+        program,
+        reference,
+        &["ProgramName", "ProgramPads", "KeygroupNumKeygroups"],
+        "Program",
+        &mut warnings,
+    );
+
+    let instruments = program
+        .get_child("Instruments")
+        .context("The program does not contain any instrument")?;
+
+    // MPCs can save more instruments than the actual keygroup count
+    let num_keygroups =
+        parse_child::<usize>(program, "KeygroupNumKeygroups")?.unwrap_or(usize::MAX);
+
+    let mut keygroups = Vec::new();
+    for (index, instrument) in child_elements(instruments, "Instrument")
+        .take(num_keygroups)
+        .enumerate()
+    {
+        let instrument_location = format!("Instrument {}", index + 1);
+
+        // Only the playback settings of an articulation are understood
+        let articulation = [Articulation::Percussive, Articulation::Sustained]
+            .into_iter()
+            .find(|&articulation| {
+                articulation_settings(articulation)
+                    .iter()
+                    .all(|(child, value)| child_text(instrument, child).as_deref() == Some(*value))
+            });
+        let mut mapped = vec![
+            "LowNote",
+            "HighNote",
+            "WarpTempo",
+            "WarpEnable",
+            "IgnoreBaseNote",
+        ];
+        if let Some(articulation) = articulation {
+            mapped.extend(articulation_settings(articulation).map(|(child, _)| child));
+        }
+        warn_unmapped(
+            instrument,
+            reference_instrument,
+            &mapped,
+            &instrument_location,
+            &mut warnings,
+        );
+
+        let low_note = parse_child::<u8>(instrument, "LowNote")?.unwrap_or(0);
+        let high_note = parse_child::<u8>(instrument, "HighNote")?.unwrap_or(127);
+        let warp = child_text(instrument, "WarpEnable")
+            .is_some_and(|warp| warp.eq_ignore_ascii_case("true"));
+        let fixed_pitch = child_text(instrument, "IgnoreBaseNote")
+            .is_some_and(|ignore| ignore.eq_ignore_ascii_case("true"));
+        // The tempo of the reference is not a detected tempo
+        let detected = warp || child_text(instrument, "WarpTempo") != reference_tempo;
+        let tempo = parse_child::<f32>(instrument, "WarpTempo")?
+            .filter(|_| detected)
+            .map(|tempo| (tempo * 1000.0).round() as u32);
+
+        let mut layers: [Option<Layer>; 4] = Default::default();
+        let program_layers = instrument
+            .get_child("Layers")
+            .into_iter()
+            .flat_map(|layers| child_elements(layers, "Layer"));
+
+        for (layer_index, program_layer) in program_layers.enumerate() {
+            let location = format!("{}, Layer {}", instrument_location, layer_index + 1);
+
+            // Recent MPC versions only store the sample name.
+            let file = child_text(program_layer, "SampleFile").or_else(|| {
+                child_text(program_layer, "SampleName").map(|name| format!("{}.wav", name))
+            });
+            let Some(file) = file else {
+                continue;
+            };
+
+            if layer_index >= layers.len() {
+                warnings.push(ImportWarning::new(
+                    location,
+                    format!("Only 4 layers are supported, {} is ignored", file),
+                ));
+                continue;
+            }
+
+            warn_unmapped(
+                program_layer,
+                reference_layer,
+                &[
+                    "SampleName",
+                    "SampleFile",
+                    "VelStart",
+                    "VelEnd",
+                    "RootNote",
+                    "TuneFine",
+                    "SampleStart",
+                    "SampleEnd",
+                    "Loop",
+                    "LoopStart",
+                    "LoopEnd",
+                    "Volume",
+                ],
+                &location,
+                &mut warnings,
+            );
+
+            let root = match parse_child::<u32>(program_layer, "RootNote")? {
+                Some(root) if (1..=128).contains(&root) => (root - 1) as u8, // off by one in the file format
+                root => {
+                    warnings.push(ImportWarning::new(
+                        &location,
+                        format!(
+                            "RootNote {} is invalid, it will be reset to 0",
+                            root.map(|r| r.to_string()).unwrap_or_default()
+                        ),
+                    ));
+                    0
+                }
+            };
+            let velocity_start = parse_child::<u8>(program_layer, "VelStart")?.unwrap_or(0);
+            let velocity_end = parse_child::<u8>(program_layer, "VelEnd")?.unwrap_or(127);
+
+            let tune = parse_child::<i8>(program_layer, "TuneFine")?.unwrap_or(0);
+            let sample_start = parse_child::<usize>(program_layer, "SampleStart")?.unwrap_or(0);
+            let sample_end = parse_child::<usize>(program_layer, "SampleEnd")?.unwrap_or(0);
+            // A zero end is the end of the sample
+            let sample_region = (sample_end > 0).then_some(sample_start..sample_end);
+            let looped = child_text(program_layer, "Loop")
+                .is_some_and(|looped| looped.eq_ignore_ascii_case("true"));
+            let loop_start = parse_child::<usize>(program_layer, "LoopStart")?.unwrap_or(0);
+            let loop_end = parse_child::<usize>(program_layer, "LoopEnd")?.unwrap_or(0);
+            let sample_loop = looped.then_some(loop_start..loop_end);
+            let gain = parse_child::<f32>(program_layer, "Volume")?.unwrap_or(1.0);
+            let volume = (200.0 * gain.max(0.001).log10()).round() as i16;
+
+            layers[layer_index] = Some(Layer {
+                tune,
+                sample_region,
+                sample_loop,
+                volume,
+                ..Layer::new(file, root, velocity_start..=velocity_end)
+            });
+        }
+
+        keygroups.push(Keygroup {
+            tempo,
+            warp,
+            fixed_pitch,
+            articulation,
+            ..Keygroup::new(low_note..=high_note, layers)
+        });
+    }
+
+    let empty_keygroups = keygroups
+        .iter()
+        .positions(|kg| kg.layer_count() == 0)
+        .map(|index| (index + 1).to_string())
+        .collect_vec();
+    if !empty_keygroups.is_empty() {
+        warnings.push(ImportWarning::new(
+            "Program",
+            format!(
+                "Instruments without samples are ignored: {}",
+                empty_keygroups.join(", ")
+            ),
+        ));
+        keygroups.retain(|kg| kg.layer_count() > 0);
+    }
+
+    Ok((KeygroupProgram { name, keygroups }, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use xmltree::XMLNode;
+
+    use crate::utils::make_program;
+
+    pub use super::*;
+
+    fn reference_keygroups() -> Vec<Keygroup> {
+        vec![
+            Keygroup {
+                fixed_pitch: true,
+                articulation: Some(Articulation::Sustained),
+                ..Keygroup::new(
+                    0..=50,
+                    [
+                        Some(Layer::new("A2.wav".to_string(), 45, 0..=63)),
+                        Some(Layer::new("A2_loud.wav".to_string(), 45, 64..=127)),
+                        None,
+                        None,
+                    ],
+                )
+            },
+            Keygroup {
+                articulation: Some(Articulation::Percussive),
+                tempo: Some(120500),
+                warp: true,
+                ..Keygroup::new(
+                    51..=127,
+                    [
+                        Some(Layer {
+                            tune: -12,
+                            sample_region: Some(100..20000),
+                            sample_loop: Some(5000..15000),
+                            volume: -35,
+                            ..Layer::new("C3.wav".to_string(), 48, 0..=127)
+                        }),
+                        None,
+                        None,
+                        None,
+                    ],
+                )
+            },
+        ]
+    }
+
+    fn first_layer(program: &mut Element) -> &mut Element {
+        program
+            .get_mut_child("Program")
+            .unwrap()
+            .get_mut_child("Instruments")
+            .unwrap()
+            .get_mut_child("Instrument")
+            .unwrap()
+            .get_mut_child("Layers")
+            .unwrap()
+            .get_mut_child("Layer")
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_program_round_trip_test() {
+        let keygroups = reference_keygroups();
+        let program = make_program("Round Trip", &keygroups).unwrap();
+
+        let (imported, warnings) = parse_program(&program).unwrap();
+
+        assert_eq!(imported.name, "Round Trip");
+        assert_eq!(imported.keygroups, keygroups);
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn parse_program_unmapped_test() {
+        let mut program = make_program("Unmapped", &reference_keygroups()).unwrap();
+        let layer = first_layer(&mut program);
+        let tune = layer.get_mut_child("TuneCoarse").unwrap();
+        tune.children = vec![XMLNode::Text("12".to_string())];
+        let mut unknown = Element::new("Warp");
+        unknown.children.push(XMLNode::Text("True".to_string()));
+        layer.children.push(XMLNode::Element(unknown));
+
+        let (_, warnings) = parse_program(&program).unwrap();
+
+        assert_eq!(
+            warnings,
+            vec![
+                ImportWarning::new(
+                    "Instrument 1, Layer 1",
+                    "TuneCoarse is 12, it is not supported and will be reset to 0"
+                ),
+                ImportWarning::new("Instrument 1, Layer 1", "Unknown fields are ignored: Warp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_program_sample_name_test() {
+        let mut program = make_program("Sample Name", &reference_keygroups()).unwrap();
+        let layer = first_layer(&mut program);
+        layer.get_mut_child("SampleFile").unwrap().children.clear();
+
+        let (imported, _) = parse_program(&program).unwrap();
+
+        assert_eq!(
+            imported.keygroups[0].layers[0].as_ref().unwrap().file,
+            "A2.wav"
+        );
+    }
+
+    #[test]
+    fn parse_program_drum_test() {
+        let mut program = make_program("Drum", &reference_keygroups()).unwrap();
+        program
+            .get_mut_child("Program")
+            .unwrap()
+            .attributes
+            .insert("type".to_string(), "Drum".to_string());
+
+        assert!(parse_program(&program).is_err());
+    }
+
+    #[rstest]
+    #[case(60, 0, (60, 0))]
+    #[case(60, 30, (60, 30))]
+    #[case(60, -70, (61, 30))]
+    #[case(60, 1210, (48, 10))]
+    #[case(0, 250, (0, 127))]
+    fn split_tuning_test(#[case] root: u8, #[case] cents: i32, #[case] expected: (u8, i8)) {
+        assert_eq!(split_tuning(root, cents), expected);
+    }
+}
@@ -0,0 +1,204 @@
+//! Detection of seamless sustain loops.
+use std::ops::Range;
+
+use anyhow::Result;
+
+use super::{write_wav_with_metadata, Wav};
+
+/// Shortest loop, in seconds.
+const MIN_LOOP: f32 = 0.1;
+
+/// Duration compared on each side of the loop points, in seconds.
+const WINDOW: f32 = 0.005;
+
+/// Largest number of candidates for the start and for the end of a loop.
+const MAX_CANDIDATES: usize = 100;
+
+/// Frames where the signal crosses zero upwards, in a range of frames.
+fn zero_crossings(points: &[f32], frames: Range<usize>) -> Vec<usize> {
+    frames
+        .filter(|&frame| frame > 0 && points[frame - 1] < 0.0 && points[frame] >= 0.0)
+        .collect()
+}
+
+/// Keep at most a number of evenly spread values.
+fn spread(values: Vec<usize>, count: usize) -> Vec<usize> {
+    if values.len() <= count {
+        return values;
+    }
+    (0..count)
+        .map(|index| values[index * values.len() / count])
+        .collect()
+}
+
+/// Difference between the waveforms around two frames, relative to their energy.
+fn mismatch(points: &[f32], a: usize, b: usize, window: usize) -> f32 {
+    let mut difference = 0.0;
+    let mut energy = 0.0;
+    for offset in 0..2 * window {
+        let x = points[a + offset - window];
+        let y = points[b + offset - window];
+        difference += (x - y) * (x - y);
+        energy += x * x + y * y;
+    }
+    if energy > 0.0 {
+        difference / energy
+    } else {
+        f32::INFINITY
+    }
+}
+
+/// Find a seamless sustain loop in a region of a sample, in frames.
+///
+/// Both loop points are upward zero crossings, chosen where the waveforms
+/// around them are the most similar. The first fifth of the region is left
+/// out for the attack, and its last tenth for the release. None is returned
+/// when the region is too short or silent.
+pub fn find_loop(wav: &Wav<'_>, region: Range<usize>) -> Option<Range<usize>> {
+    let points = wav.mono();
+    let sample_rate = wav.format.sample_rate as f32;
+    let window = ((WINDOW * sample_rate) as usize).max(1);
+    let min_loop = ((MIN_LOOP * sample_rate) as usize).max(1);
+
+    let end = region.end.min(points.len());
+    let start = region.start.min(end);
+    let length = end - start;
+    let sustain_start = (start + length / 5).max(window);
+    let sustain_end = (end - length / 10).min(points.len().saturating_sub(window));
+
+    let starts = zero_crossings(&points, sustain_start..sustain_end.saturating_sub(min_loop));
+    let ends = zero_crossings(&points, sustain_start + min_loop..sustain_end);
+    let starts = spread(starts, MAX_CANDIDATES);
+    let ends = spread(ends, MAX_CANDIDATES);
+
+    starts
+        .iter()
+        .flat_map(|&start| ends.iter().map(move |&end| start..end))
+        .filter(|candidate| candidate.len() >= min_loop)
+        .map(|candidate| {
+            let score = mismatch(&points, candidate.start, candidate.end, window);
+            (candidate, score)
+        })
+        .filter(|(_, score)| score.is_finite())
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+}
+
+/// Crossfade the end of a loop with the frames before its start.
+///
+/// The playback goes on smoothly when jumping back to the loop start. The
+/// crossfade, in frames, is shortened to fit before the loop start and in the
+/// loop. The WAV file is rewritten with its sampler metadata.
+pub fn crossfade_loop(
+    wav: &Wav<'_>,
+    sample_loop: &Range<usize>,
+    crossfade: usize,
+) -> Result<Vec<u8>> {
+    let end = sample_loop.end.min(wav.frames());
+    let start = sample_loop.start.min(end);
+    let crossfade = crossfade.min(start).min(end - start);
+    let bytes = wav.format.bits_per_sample.div_ceil(8) as usize;
+    let block_align = wav.format.block_align();
+
+    let mut data = wav.data.to_vec();
+    for index in 0..crossfade {
+        let frame = end - crossfade + index;
+        let source = start - crossfade + index;
+        let gain = (index + 1) as f64 / (crossfade + 1) as f64;
+        for channel in 0..wav.format.channels as usize {
+            let point = wav.point(frame, channel) as f64 * (1.0 - gain)
+                + wav.point(source, channel) as f64 * gain;
+            let point = point.round() as i32;
+            let offset = frame * block_align + channel * bytes;
+            if bytes == 1 {
+                // 8 bits WAV files are unsigned
+                data[offset] = ((point >> 24) + 128) as u8;
+            } else {
+                data[offset..offset + bytes].copy_from_slice(&point.to_le_bytes()[4 - bytes..]);
+            }
+        }
+    }
+
+    let mut content = Vec::new();
+    write_wav_with_metadata(&mut content, &wav.format, &data, &wav.metadata)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::utils::wav_from_points;
+
+    fn wav(points: &[f32]) -> Vec<u8> {
+        let points = points
+            .iter()
+            .map(|point| (point * 20000.0).round() as i16)
+            .collect::<Vec<_>>();
+        wav_from_points(1000, 1, &points)
+    }
+
+    /// A sine with a period of 20 frames.
+    fn sine(frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|frame| (std::f32::consts::TAU * (frame as f32 + 0.5) / 20.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn find_loop_test() {
+        let wav = wav(&sine(2000));
+        let wav = Wav::parse(&wav).unwrap();
+
+        let sample_loop = find_loop(&wav, 0..2000).unwrap();
+
+        assert!(sample_loop.start >= 400);
+        assert!(sample_loop.end <= 1800);
+        assert!(sample_loop.len() >= 100);
+        // Whole periods loop seamlessly
+        assert_eq!(sample_loop.len() % 20, 0);
+    }
+
+    #[test]
+    fn find_loop_region_test() {
+        // A tone in the middle of some silence
+        let mut points = vec![0.0; 1000];
+        points.extend(sine(1000));
+        points.extend(vec![0.0; 1000]);
+        let wav = wav(&points);
+        let wav = Wav::parse(&wav).unwrap();
+
+        let sample_loop = find_loop(&wav, 1000..2000).unwrap();
+
+        assert!(sample_loop.start >= 1200);
+        assert!(sample_loop.end <= 1900);
+    }
+
+    #[rstest]
+    #[case(vec![0.0; 2000])]
+    #[case(sine(100))]
+    fn find_no_loop_test(#[case] points: Vec<f32>) {
+        let wav = wav(&points);
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(find_loop(&wav, 0..points.len()), None);
+    }
+
+    #[test]
+    fn crossfade_loop_test() {
+        let points = [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7];
+        let wav = wav(&points);
+        let wav = Wav::parse(&wav).unwrap();
+
+        let crossfaded = crossfade_loop(&wav, &(3..7), 2).unwrap();
+        let crossfaded = Wav::parse(&crossfaded).unwrap();
+
+        let points = (0..8)
+            .map(|frame| crossfaded.point(frame, 0) >> 16)
+            .collect::<Vec<_>>();
+        // The frames before the loop end move toward the frames before its start
+        assert_eq!(points, vec![0, 2000, 4000, 6000, 8000, 7333, 6666, 14000]);
+        assert_eq!(crossfaded.format, wav.format);
+    }
+}
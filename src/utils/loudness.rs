@@ -0,0 +1,140 @@
+//! Loudness measurement of the samples.
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::Wav;
+
+/// Duration of the windows where the RMS level is measured, in seconds.
+const RMS_WINDOW: f32 = 0.05;
+
+/// Level of a sample, in dB relative to the full scale.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Loudness {
+    /// Highest absolute sample point.
+    pub peak: f32,
+
+    /// RMS level of the loudest window of the sample.
+    ///
+    /// Measuring a window avoids counting the silence and the release of the sample.
+    pub rms: f32,
+}
+
+/// Highest gain applied when evening out levels, in dB.
+const MAX_GAIN: f32 = 6.0;
+
+/// Convert a linear amplitude to dB, with a floor at -120 dB for the silence.
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-6).log10()
+}
+
+/// Measure the peak and RMS level of a region of a sample, all the channels combined.
+pub fn measure_loudness(wav: &Wav<'_>, region: Range<usize>) -> Loudness {
+    let channels = wav.format.channels as usize;
+    let window = ((RMS_WINDOW * wav.format.sample_rate as f32) as usize).max(1);
+
+    let mut peak: f32 = 0.0;
+    let mut loudest_window: f32 = 0.0;
+    let mut window_sum = 0.0;
+    let mut window_points = 0;
+    let end = region.end.min(wav.frames());
+    for frame in region.start..end {
+        for channel in 0..channels {
+            let point = wav.point(frame, channel) as f32 / 2_f32.powi(31);
+            peak = peak.max(point.abs());
+            window_sum += point * point;
+            window_points += 1;
+        }
+        if (frame + 1 - region.start).is_multiple_of(window) || frame + 1 == end {
+            loudest_window = loudest_window.max(window_sum / window_points as f32);
+            window_sum = 0.0;
+            window_points = 0;
+        }
+    }
+
+    Loudness {
+        peak: to_db(peak),
+        rms: to_db(loudest_window.sqrt()),
+    }
+}
+
+/// Gains (in dB) bringing each level to the median of the levels.
+///
+/// The gains are limited to +6 dB, to avoid raising the noise of the quiet samples.
+pub fn leveling_gains(levels: &[f32]) -> Vec<f32> {
+    if levels.is_empty() {
+        return Vec::new();
+    }
+
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let middle = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    };
+
+    levels
+        .iter()
+        .map(|level| (median - level).min(MAX_GAIN))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::utils::{write_wav, WavFormat};
+
+    #[rstest]
+    #[case(vec![], vec![])]
+    #[case(vec![-12.0], vec![0.0])]
+    #[case(vec![-12.0, -10.0, -14.0], vec![0.0, -2.0, 2.0])]
+    #[case(vec![-12.0, -10.0], vec![1.0, -1.0])]
+    #[case(vec![-12.0, -12.0, -40.0], vec![0.0, 0.0, 6.0])]
+    fn leveling_gains_test(#[case] levels: Vec<f32>, #[case] expected: Vec<f32>) {
+        assert_eq!(leveling_gains(&levels), expected);
+    }
+
+    #[rstest]
+    #[case(1.0, -3.01)]
+    #[case(0.5, -9.03)]
+    #[case(0.1, -23.01)]
+    fn measure_loudness_test(#[case] amplitude: f32, #[case] expected_rms: f32) {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        // A sine followed by some silence, that should not lower the RMS level
+        let data: Vec<u8> = (0..44100)
+            .map(|i| {
+                let t = i as f32 / 44100.0;
+                let value = if i < 22050 {
+                    amplitude * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+                } else {
+                    0.0
+                };
+                (value * i16::MAX as f32) as i16
+            })
+            .flat_map(|p| p.to_le_bytes())
+            .collect();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &data).unwrap();
+
+        let wav = Wav::parse(&wav).unwrap();
+        let loudness = measure_loudness(&wav, 0..44100);
+
+        assert!(
+            (loudness.peak - to_db(amplitude)).abs() < 0.1,
+            "{loudness:?}"
+        );
+        assert!((loudness.rms - expected_rms).abs() < 0.1, "{loudness:?}");
+
+        // The silence alone
+        let silence = measure_loudness(&wav, 22050..44100);
+        assert_eq!(silence.rms, to_db(0.0));
+    }
+}
@@ -0,0 +1,136 @@
+//! Standard MIDI files, to play the notes of a sampling plan.
+use crate::model::PlannedNote;
+
+/// Ticks per quarter note.
+const DIVISION: u16 = 480;
+
+/// Tempo of the file, in microseconds per quarter note (120 BPM).
+const TEMPO: u32 = 500_000;
+
+/// Number of ticks per second at the tempo of the file.
+const TICKS_PER_SECOND: f32 = DIVISION as f32 * 1_000_000.0 / TEMPO as f32;
+
+/// Write a variable length quantity.
+fn write_vlq(track: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    track.extend(bytes.iter().rev());
+}
+
+fn ticks(seconds: f32) -> u32 {
+    (seconds.max(0.0) * TICKS_PER_SECOND).round() as u32
+}
+
+/// Make a single track Standard MIDI File playing the notes on the first channel.
+pub fn make_midi(name: &str, notes: &[PlannedNote]) -> Vec<u8> {
+    // Events as (tick, is note on, note, velocity), the note offs come first
+    let mut events = notes
+        .iter()
+        .flat_map(|note| {
+            [
+                (ticks(note.start), true, note.root, note.velocity),
+                (ticks(note.start + note.length), false, note.root, 0),
+            ]
+        })
+        .collect::<Vec<_>>();
+    events.sort_by_key(|(tick, on, _, _)| (*tick, *on));
+
+    let mut track = Vec::new();
+    // Track name
+    write_vlq(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x03]);
+    write_vlq(&mut track, name.len() as u32);
+    track.extend_from_slice(name.as_bytes());
+    // Tempo
+    write_vlq(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x51, 0x03]);
+    track.extend_from_slice(&TEMPO.to_be_bytes()[1..]);
+
+    let mut time = 0;
+    for (tick, on, note, velocity) in events {
+        write_vlq(&mut track, tick - time);
+        time = tick;
+        let status = if on { 0x90 } else { 0x80 };
+        track.extend_from_slice(&[status, note.min(127), velocity.min(127)]);
+    }
+    // End of track
+    write_vlq(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+    let mut midi = Vec::with_capacity(track.len() + 22);
+    midi.extend_from_slice(b"MThd");
+    midi.extend_from_slice(&6_u32.to_be_bytes());
+    midi.extend_from_slice(&0_u16.to_be_bytes()); // Single track
+    midi.extend_from_slice(&1_u16.to_be_bytes());
+    midi.extend_from_slice(&DIVISION.to_be_bytes());
+    midi.extend_from_slice(b"MTrk");
+    midi.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    midi.extend_from_slice(&track);
+    midi
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    pub use super::*;
+
+    #[rstest]
+    #[case(0, &[0x00])]
+    #[case(0x7F, &[0x7F])]
+    #[case(0x80, &[0x81, 0x00])]
+    #[case(960, &[0x87, 0x40])]
+    #[case(0x0FFFFFFF, &[0xFF, 0xFF, 0xFF, 0x7F])]
+    fn write_vlq_test(#[case] value: u32, #[case] expected: &[u8]) {
+        let mut bytes = Vec::new();
+        write_vlq(&mut bytes, value);
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn make_midi_test() {
+        let notes = [
+            PlannedNote {
+                root: 60,
+                velocity: 100,
+                velocity_range: 0..=127,
+                start: 0.0,
+                length: 1.0,
+            },
+            PlannedNote {
+                root: 62,
+                velocity: 50,
+                velocity_range: 0..=127,
+                start: 1.0,
+                length: 0.5,
+            },
+        ];
+
+        let midi = make_midi("Plan", &notes);
+
+        assert_eq!(&midi[0..4], b"MThd");
+        assert_eq!(&midi[8..14], &[0, 0, 0, 1, 0x01, 0xE0]);
+        assert_eq!(&midi[14..18], b"MTrk");
+        let track = &midi[22..];
+        assert_eq!(
+            track,
+            &[
+                0x00, 0xFF, 0x03, 0x04, b'P', b'l', b'a', b'n', // name
+                0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // tempo
+                0x00, 0x90, 60, 100, // first note on
+                0x87, 0x40, 0x80, 60, 0, // first note off after 1 s
+                0x00, 0x90, 62, 50, // second note on
+                0x83, 0x60, 0x80, 62, 0, // second note off after 0.5 s
+                0x00, 0xFF, 0x2F, 0x00, // end of track
+            ][..]
+        );
+        assert_eq!(
+            u32::from_be_bytes([midi[18], midi[19], midi[20], midi[21]]) as usize,
+            track.len()
+        );
+    }
+}
@@ -1,7 +1,47 @@
+mod aiff;
+mod bundle;
+mod convert;
+mod dspreset;
+mod envelope;
+mod expansion;
 mod export;
+mod flac;
+mod import;
+mod looping;
+mod loudness;
+mod midi;
+mod pitch;
 mod range;
+mod resample;
+mod riff;
+mod sf2;
+mod sfz;
+mod silence;
+mod slice;
 mod static_iterable;
+mod tempo;
+mod wav;
 
+pub use aiff::*;
+pub use bundle::*;
+pub use convert::*;
+pub use dspreset::*;
+pub use envelope::*;
+pub use expansion::*;
 pub use export::*;
+pub use flac::*;
+pub use import::*;
+pub use looping::*;
+pub use loudness::*;
+pub use midi::*;
+pub use pitch::*;
 pub use range::*;
+pub use resample::*;
+pub(crate) use riff::*;
+pub use sf2::*;
+pub use sfz::*;
+pub use silence::*;
+pub use slice::*;
 pub use static_iterable::*;
+pub use tempo::*;
+pub use wav::*;
@@ -0,0 +1,164 @@
+//! Fundamental frequency estimation of the samples.
+
+/// Lowest detected frequency (A0).
+const MIN_FREQUENCY: f32 = 27.5;
+
+/// Highest detected frequency (C8).
+const MAX_FREQUENCY: f32 = 4186.0;
+
+/// Threshold of the normalized difference under which a period is accepted.
+const THRESHOLD: f32 = 0.15;
+
+/// Duration skipped after the attack peak, to avoid analysing the attack.
+const ATTACK_DURATION: f32 = 0.05;
+
+/// Estimate the fundamental frequency of a mono signal, in Hz.
+///
+/// This is the YIN algorithm, applied on a window after the attack of the sample.
+/// None is returned when the signal is too short or has no clear pitch.
+pub fn detect_pitch(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let sample_rate = sample_rate as f32;
+    let min_period = (sample_rate / MAX_FREQUENCY).floor().max(2.0) as usize;
+    let max_period = (sample_rate / MIN_FREQUENCY).ceil() as usize;
+    let window = max_period;
+
+    // Start the analysis after the attack, when possible. The attack is where
+    // the signal first reaches half of its peak, as a louder noise can end it.
+    let loudest = samples.iter().map(|s| s.abs()).fold(0.0, f32::max);
+    let attack = samples.iter().position(|s| s.abs() >= loudest * 0.5)?;
+    let latest_start = samples.len().checked_sub(window + max_period)?;
+    let start = (attack + (ATTACK_DURATION * sample_rate) as usize).min(latest_start);
+    let samples = &samples[start..start + window + max_period];
+
+    if samples.iter().all(|s| s.abs() < 1e-4) {
+        return None;
+    }
+
+    // Difference function
+    let difference: Vec<f32> = (0..=max_period)
+        .map(|period| {
+            (0..window)
+                .map(|i| {
+                    let delta = samples[i] - samples[i + period];
+                    delta * delta
+                })
+                .sum()
+        })
+        .collect();
+
+    // Cumulative mean normalized difference
+    let mut normalized = vec![1.0; difference.len()];
+    let mut sum = 0.0;
+    for period in 1..difference.len() {
+        sum += difference[period];
+        normalized[period] = if sum > 0.0 {
+            difference[period] * period as f32 / sum
+        } else {
+            1.0
+        };
+    }
+
+    // First dip under the threshold, up to its local minimum
+    let mut period = (min_period..max_period).find(|&p| normalized[p] < THRESHOLD)?;
+    while period + 1 < max_period && normalized[period + 1] < normalized[period] {
+        period += 1;
+    }
+
+    // Parabolic interpolation around the minimum
+    let (previous, current, next) = (
+        normalized[period - 1],
+        normalized[period],
+        normalized[period + 1],
+    );
+    let denominator = previous - 2.0 * current + next;
+    let offset = if denominator.abs() > f32::EPSILON {
+        (0.5 * (previous - next) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+
+    Some(sample_rate / (period as f32 + offset))
+}
+
+/// Convert a frequency to a MIDI note, in cents (100 per semitone).
+pub fn frequency_to_cents(frequency: f32) -> i32 {
+    (6900.0 + 1200.0 * (frequency / 440.0).log2()).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: u32, duration: f32) -> Vec<f32> {
+        (0..(duration * sample_rate as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                // Some harmonics, and a decay
+                let phase = 2.0 * std::f32::consts::PI * frequency * t;
+                (0.6 * phase.sin() + 0.3 * (2.0 * phase).sin() + 0.1 * (3.0 * phase).sin())
+                    * (-t).exp()
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(55.0, 44100)]
+    #[case(110.0, 44100)]
+    #[case(440.0, 48000)]
+    #[case(466.16, 44100)]
+    #[case(1760.0, 44100)]
+    fn detect_pitch_test(#[case] frequency: f32, #[case] sample_rate: u32) {
+        let samples = sine(frequency, sample_rate, 0.5);
+
+        let detected = detect_pitch(&samples, sample_rate).unwrap();
+
+        assert!(
+            (frequency_to_cents(detected) - frequency_to_cents(frequency)).abs() <= 5,
+            "{detected} Hz detected instead of {frequency} Hz"
+        );
+    }
+
+    #[test]
+    fn detect_pitch_late_peak_test() {
+        // A note ending with a release noise louder than its attack
+        let mut samples = sine(220.0, 44100, 1.0);
+        let mut state = 1_u32;
+        samples.extend((0..4410).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            1.2 * ((state >> 16) as f32 / 32768.0 - 1.0)
+        }));
+
+        let detected = detect_pitch(&samples, 44100).unwrap();
+
+        assert!(
+            (frequency_to_cents(detected) - frequency_to_cents(220.0)).abs() <= 5,
+            "{detected} Hz detected instead of 220 Hz"
+        );
+    }
+
+    #[test]
+    fn detect_pitch_noise_test() {
+        // Deterministic pseudo random noise
+        let mut state = 1_u32;
+        let noise: Vec<f32> = (0..44100)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as f32 / 32768.0 - 1.0
+            })
+            .collect();
+
+        assert_eq!(detect_pitch(&noise, 44100), None);
+        assert_eq!(detect_pitch(&[0.0; 44100], 44100), None);
+        assert_eq!(detect_pitch(&[0.5; 100], 44100), None);
+    }
+
+    #[rstest]
+    #[case(440.0, 6900)]
+    #[case(261.63, 6000)]
+    #[case(452.9, 6950)]
+    fn frequency_to_cents_test(#[case] frequency: f32, #[case] expected: i32) {
+        assert_eq!(frequency_to_cents(frequency), expected);
+    }
+}
@@ -24,11 +24,8 @@ where
             return root1;
         }
         let distance = (root2 - root1).semitones();
-        let cut_point = Interval::new(
-            ((pitch_preference * distance as f32) as u8)
-                .max(0)
-                .min(distance - 1),
-        );
+        let cut_point =
+            Interval::new(((pitch_preference * distance as f32) as u8).min(distance - 1));
         root1 + cut_point
     }));
     cuts.push(MidiNote::from(127));
@@ -48,6 +45,46 @@ where
         .collect()
 }
 
+/// Create a set of ranges from midi notes, keeping the given ranges where set.
+///
+/// The other ranges are guessed from the notes. The neighbours of the kept
+/// ranges are extended or shrunk so that the whole keyboard is covered with no
+/// overlap, and a kept range is shrunk when it overlaps the next one.
+pub fn build_constrained_ranges(
+    notes: &[u8],
+    constraints: &[Option<RangeInclusive<u8>>],
+    pitch_preference: f32,
+) -> Vec<RangeInclusive<u8>> {
+    let ranges = build_ranges(notes, pitch_preference);
+    let count = ranges.len();
+
+    // Move the end of the ranges to the kept ones
+    let mut highs = ranges.iter().map(|range| *range.end()).collect::<Vec<_>>();
+    for (index, constraint) in constraints.iter().enumerate().take(count) {
+        if let Some(constraint) = constraint {
+            if index > 0 {
+                highs[index - 1] = constraint.start().saturating_sub(1);
+            }
+            highs[index] = *constraint.end();
+        }
+    }
+
+    // Chain the ranges, leaving at least a note to each
+    let mut low = 0_u8;
+    let mut result = Vec::with_capacity(count);
+    for (index, high) in highs.into_iter().enumerate() {
+        let high = if index + 1 == count {
+            127
+        } else {
+            high.max(low)
+                .min(127_u8.saturating_sub((count - 1 - index) as u8))
+        };
+        result.push(low..=high);
+        low = high.saturating_add(1);
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -93,4 +130,33 @@ mod tests {
         let ranges = build_ranges(&input, pitch_preference);
         assert_eq!(ranges, expected);
     }
+
+    #[rstest]
+    #[case(
+        vec![None, Some(40..=50), None],
+        vec![0..=39, 40..=50, 51..=127],
+    )]
+    #[case(
+        vec![Some(30..=40), None, Some(80..=90)],
+        vec![0..=40, 41..=79, 80..=127],
+    )]
+    #[case(
+        vec![Some(40..=60), Some(50..=70), None],
+        vec![0..=49, 50..=70, 71..=127],
+    )]
+    #[case(
+        vec![None, Some(0..=127), None],
+        vec![0..=0, 1..=126, 127..=127],
+    )]
+    fn test_build_constrained_ranges(
+        #[case] constraints: Vec<Option<RangeInclusive<u8>>>,
+        #[case] expected: Vec<RangeInclusive<u8>>,
+    ) {
+        let ranges = build_constrained_ranges(&[45, 57, 69], &constraints, 0.5);
+        assert_eq!(ranges, expected);
+        // The whole keyboard is covered, with no overlap
+        assert_eq!(*ranges[0].start(), 0);
+        assert_eq!(*ranges[2].end(), 127);
+        assert!(ranges.windows(2).all(|w| *w[1].start() == *w[0].end() + 1));
+    }
 }
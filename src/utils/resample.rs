@@ -0,0 +1,140 @@
+//! Sample rate conversion of the samples.
+use std::f64::consts::PI;
+
+use lazy_static::lazy_static;
+
+/// Number of zero crossings of the interpolation filter on each side.
+const ZERO_CROSSINGS: f64 = 32.0;
+
+/// Number of points of the interpolation filter table, per zero crossing.
+const TABLE_RESOLUTION: usize = 512;
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window, between -1 and 1.
+fn blackman(t: f64) -> f64 {
+    0.42 + 0.5 * (PI * t).cos() + 0.08 * (2.0 * PI * t).cos()
+}
+
+/// Value of the interpolation filter at a distance, in zero crossings.
+///
+/// It's interpolated from a precomputed table of the windowed sinc.
+fn kernel(x: f64) -> f64 {
+    lazy_static! {
+        static ref TABLE: Vec<f64> = {
+            let points = ZERO_CROSSINGS as usize * TABLE_RESOLUTION;
+            (0..=points + 1)
+                .map(|i| {
+                    let x = i as f64 / TABLE_RESOLUTION as f64;
+                    if x < ZERO_CROSSINGS {
+                        sinc(x) * blackman(x / ZERO_CROSSINGS)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        };
+    }
+    let position = x.abs() * TABLE_RESOLUTION as f64;
+    let index = position as usize;
+    if index + 1 >= TABLE.len() {
+        return 0.0;
+    }
+    let fraction = position - index as f64;
+    TABLE[index] + (TABLE[index + 1] - TABLE[index]) * fraction
+}
+
+/// Change the sample rate of a signal with a windowed sinc interpolation.
+///
+/// When lowering the sample rate, the frequencies above the new Nyquist
+/// frequency are filtered out to avoid aliasing.
+pub fn resample(input: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || input.is_empty() {
+        return input.to_vec();
+    }
+    let ratio = to as f64 / from as f64;
+    let cutoff = ratio.min(1.0);
+    let half_width = ZERO_CROSSINGS / cutoff;
+    let frames = (input.len() as f64 * ratio).round() as usize;
+
+    (0..frames)
+        .map(|frame| {
+            let center = frame as f64 / ratio;
+            let first = (center - half_width).ceil().max(0.0) as usize;
+            let last = ((center + half_width).floor() as usize).min(input.len() - 1);
+            let sum: f64 = (first..=last)
+                .map(|index| {
+                    let distance = index as f64 - center;
+                    input[index] as f64 * cutoff * kernel(distance * cutoff)
+                })
+                .sum();
+            sum as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use crate::utils::detect_pitch;
+    use rstest::rstest;
+
+    fn sine(frequency: f32, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| {
+                (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin() * 0.5
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(48000, 44100)]
+    #[case(44100, 48000)]
+    #[case(96000, 44100)]
+    #[case(22050, 44100)]
+    fn resample_sine_test(#[case] from: u32, #[case] to: u32) {
+        let input = sine(440.0, from, from as usize / 2);
+
+        let output = resample(&input, from, to);
+
+        assert_eq!(output.len(), to as usize / 2);
+        let pitch = detect_pitch(&output, to).unwrap();
+        assert!((pitch - 440.0).abs() < 1.0, "{}", pitch);
+        // Away from the edges, the amplitude is kept
+        let peak = output[1000..output.len() - 1000]
+            .iter()
+            .fold(0_f32, |peak, point| peak.max(point.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "{}", peak);
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.25)]
+    #[case(1.5)]
+    #[case(-7.3)]
+    #[case(31.9)]
+    fn kernel_test(#[case] x: f64) {
+        let expected = sinc(x) * blackman(x / ZERO_CROSSINGS);
+        assert!((kernel(x) - expected).abs() < 1e-5, "{}", kernel(x));
+        assert_eq!(kernel(ZERO_CROSSINGS + 1.0), 0.0);
+    }
+
+    #[test]
+    fn resample_filters_aliasing_test() {
+        // 30 kHz can't be represented at 44.1 kHz
+        let input = sine(30000.0, 96000, 9600);
+
+        let output = resample(&input, 96000, 44100);
+
+        let peak = output[100..output.len() - 100]
+            .iter()
+            .fold(0_f32, |peak, point| peak.max(point.abs()));
+        assert!(peak < 0.01, "{}", peak);
+    }
+}
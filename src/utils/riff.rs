@@ -0,0 +1,161 @@
+//! RIFF chunks reading and writing, as used by WAV and SoundFont files.
+use std::io::Write;
+
+use anyhow::{bail, Context, Result};
+
+/// A RIFF chunk with its identifier and content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    /// Four characters identifier of the chunk.
+    pub id: [u8; 4],
+
+    /// Content of the chunk, without the padding byte.
+    pub data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// Read the first chunk of a buffer.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        Chunks::new(data)
+            .next()
+            .context("The file does not contain any RIFF chunk")?
+    }
+
+    /// Type and sub-chunks of a RIFF or LIST chunk.
+    pub fn list(&self) -> Result<(&'a [u8], Chunks<'a>)> {
+        if &self.id != b"RIFF" && &self.id != b"LIST" {
+            bail!("{} is not a list chunk", self.name());
+        }
+        if self.data.len() < 4 {
+            bail!("The {} chunk is truncated", self.name());
+        }
+        Ok((&self.data[..4], Chunks::new(&self.data[4..])))
+    }
+
+    /// Printable name of the chunk.
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.id).to_string()
+    }
+}
+
+/// Iterator over consecutive RIFF chunks.
+#[derive(Debug, Clone)]
+pub struct Chunks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Chunks<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 8 {
+            return None;
+        }
+
+        let id: [u8; 4] = self.data[..4].try_into().expect("BUG: chunk id length");
+        let size = read_u32(self.data, 4) as usize;
+        let data = &self.data[8..];
+        if size > data.len() {
+            self.data = &[];
+            return Some(Err(anyhow::anyhow!(
+                "The {} chunk is truncated",
+                String::from_utf8_lossy(&id)
+            )));
+        }
+
+        // Chunks are aligned on 16 bits
+        let padded_size = (size + size % 2).min(data.len());
+        self.data = &data[padded_size..];
+        Some(Ok(Chunk {
+            id,
+            data: &data[..size],
+        }))
+    }
+}
+
+/// Read a little endian u16 at an offset.
+pub fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Read a little endian u32 at an offset.
+pub fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Write a chunk with its header and padding.
+pub fn write_chunk<W: Write>(w: &mut W, id: &[u8; 4], data: &[u8]) -> Result<()> {
+    let size = u32::try_from(data.len()).context("The chunk is too big")?;
+    w.write_all(id)?;
+    w.write_all(&size.to_le_bytes())?;
+    w.write_all(data)?;
+    if data.len() % 2 == 1 {
+        w.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+/// Write a RIFF or LIST chunk containing already serialized sub-chunks.
+pub fn write_list<W: Write>(
+    w: &mut W,
+    id: &[u8; 4],
+    list_type: &[u8; 4],
+    chunks: &[u8],
+) -> Result<()> {
+    let mut data = Vec::with_capacity(chunks.len() + 4);
+    data.extend_from_slice(list_type);
+    data.extend_from_slice(chunks);
+    write_chunk(w, id, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+
+    #[test]
+    fn riff_round_trip_test() {
+        let mut chunks = Vec::new();
+        write_chunk(&mut chunks, b"abcd", &[1, 2, 3]).unwrap();
+        write_chunk(&mut chunks, b"efgh", &[4, 5]).unwrap();
+        let mut riff = Vec::new();
+        write_list(&mut riff, b"RIFF", b"TEST", &chunks).unwrap();
+
+        let riff = Chunk::parse(&riff).unwrap();
+        let (list_type, chunks) = riff.list().unwrap();
+        let chunks: Vec<Chunk<'_>> = chunks.collect::<Result<_>>().unwrap();
+
+        assert_eq!(list_type, b"TEST");
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk {
+                    id: *b"abcd",
+                    data: &[1, 2, 3]
+                },
+                Chunk {
+                    id: *b"efgh",
+                    data: &[4, 5]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn riff_truncated_test() {
+        let mut chunk = Vec::new();
+        write_chunk(&mut chunk, b"abcd", &[1, 2, 3, 4]).unwrap();
+
+        assert!(Chunk::parse(&chunk[..10]).is_err());
+    }
+}
@@ -0,0 +1,1075 @@
+use std::collections::BTreeMap;
+use std::ops::{Range, RangeInclusive};
+
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+
+use crate::model::{ImportWarning, Keygroup, KeygroupProgram, Layer, SampleLibrary};
+
+use super::{
+    group_layers, read_u16, read_u32, split_tuning, write_chunk, write_list, write_wav, Chunk, Wav,
+    WavFormat,
+};
+
+/// Generator operators of the SoundFont 2 specification.
+const GENERATOR_NAMES: [&str; 61] = [
+    "startAddrsOffset",
+    "endAddrsOffset",
+    "startloopAddrsOffset",
+    "endloopAddrsOffset",
+    "startAddrsCoarseOffset",
+    "modLfoToPitch",
+    "vibLfoToPitch",
+    "modEnvToPitch",
+    "initialFilterFc",
+    "initialFilterQ",
+    "modLfoToFilterFc",
+    "modEnvToFilterFc",
+    "endAddrsCoarseOffset",
+    "modLfoToVolume",
+    "unused1",
+    "chorusEffectsSend",
+    "reverbEffectsSend",
+    "pan",
+    "unused2",
+    "unused3",
+    "unused4",
+    "delayModLFO",
+    "freqModLFO",
+    "delayVibLFO",
+    "freqVibLFO",
+    "delayModEnv",
+    "attackModEnv",
+    "holdModEnv",
+    "decayModEnv",
+    "sustainModEnv",
+    "releaseModEnv",
+    "keynumToModEnvHold",
+    "keynumToModEnvDecay",
+    "delayVolEnv",
+    "attackVolEnv",
+    "holdVolEnv",
+    "decayVolEnv",
+    "sustainVolEnv",
+    "releaseVolEnv",
+    "keynumToVolEnvHold",
+    "keynumToVolEnvDecay",
+    "instrument",
+    "reserved1",
+    "keyRange",
+    "velRange",
+    "startloopAddrsCoarseOffset",
+    "keynum",
+    "velocity",
+    "initialAttenuation",
+    "reserved2",
+    "endloopAddrsCoarseOffset",
+    "coarseTune",
+    "fineTune",
+    "sampleID",
+    "sampleModes",
+    "reserved3",
+    "scaleTuning",
+    "exclusiveClass",
+    "overridingRootKey",
+    "unused5",
+    "endOper",
+];
+
+const GEN_PAN: u16 = 17;
+const GEN_INSTRUMENT: u16 = 41;
+const GEN_KEY_RANGE: u16 = 43;
+const GEN_VEL_RANGE: u16 = 44;
+const GEN_COARSE_TUNE: u16 = 51;
+const GEN_FINE_TUNE: u16 = 52;
+const GEN_SAMPLE_ID: u16 = 53;
+const GEN_OVERRIDING_ROOT_KEY: u16 = 58;
+
+const SAMPLE_TYPE_MONO: u16 = 1;
+const SAMPLE_TYPE_RIGHT: u16 = 2;
+const SAMPLE_TYPE_LEFT: u16 = 4;
+const SAMPLE_TYPE_ROM: u16 = 0x8000;
+
+/// Generators of a zone, by operator.
+type Zone = BTreeMap<u16, [u8; 2]>;
+
+/// Name of a generator operator.
+fn generator_name(operator: u16) -> String {
+    GENERATOR_NAMES
+        .get(operator as usize)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("generator {}", operator))
+}
+
+/// Read a fixed size, zero terminated name.
+fn read_name(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+/// Tuning of a zone, in cents.
+fn zone_tuning(zone: &Zone) -> i32 {
+    let amount = |operator| zone.get(&operator).map(|a| i16::from_le_bytes(*a) as i32);
+    amount(GEN_COARSE_TUNE).unwrap_or(0) * 100 + amount(GEN_FINE_TUNE).unwrap_or(0)
+}
+
+/// Read a range generator amount.
+fn range_amount(amount: [u8; 2]) -> RangeInclusive<u8> {
+    amount[0]..=amount[1]
+}
+
+/// Intersection of two ranges, if they overlap.
+fn intersect(a: &RangeInclusive<u8>, b: &RangeInclusive<u8>) -> Option<RangeInclusive<u8>> {
+    let start = *a.start().max(b.start());
+    let end = *a.end().min(b.end());
+    (start <= end).then_some(start..=end)
+}
+
+/// Fixed size records of a "pdta" sub-chunk.
+struct Records<'a> {
+    data: &'a [u8],
+    size: usize,
+}
+
+impl<'a> Records<'a> {
+    fn new(chunks: &BTreeMap<[u8; 4], &'a [u8]>, id: &[u8; 4], size: usize) -> Result<Self> {
+        let name = String::from_utf8_lossy(id);
+        let data = *chunks
+            .get(id)
+            .context(format!("The SoundFont does not contain a {} chunk", name))?;
+        if data.len() % size != 0 {
+            bail!("The {} chunk of the SoundFont is malformed", name);
+        }
+        Ok(Self { data, size })
+    }
+
+    fn len(&self) -> usize {
+        self.data.len() / self.size
+    }
+
+    fn get(&self, index: usize) -> Result<&'a [u8]> {
+        if index >= self.len() {
+            bail!("Invalid SoundFont record index {}", index);
+        }
+        Ok(&self.data[index * self.size..(index + 1) * self.size])
+    }
+
+    /// Bag index of a preset or instrument header, stored at an offset.
+    fn bag_range(&self, index: usize, offset: usize) -> Result<Range<usize>> {
+        let start = read_u16(self.get(index)?, offset) as usize;
+        let end = read_u16(self.get(index + 1)?, offset) as usize;
+        if end < start {
+            bail!("Invalid SoundFont bag indexes");
+        }
+        Ok(start..end)
+    }
+}
+
+/// Read the zones of a preset or an instrument.
+///
+/// The global zone (if any) is merged in each of the other zones.
+fn read_zones(
+    bags: &Records<'_>,
+    generators: &Records<'_>,
+    bag_range: Range<usize>,
+    terminal: u16,
+) -> Result<Vec<Zone>> {
+    let mut zones = Vec::new();
+    for bag in bag_range {
+        let start = read_u16(bags.get(bag)?, 0) as usize;
+        let end = read_u16(bags.get(bag + 1)?, 0) as usize;
+        let mut zone = Zone::new();
+        for generator in start..end {
+            let generator = generators.get(generator)?;
+            zone.insert(read_u16(generator, 0), [generator[2], generator[3]]);
+        }
+        zones.push(zone);
+    }
+
+    let global = match zones.first() {
+        Some(zone) if !zone.contains_key(&terminal) => Some(zones.remove(0)),
+        _ => None,
+    };
+
+    Ok(zones
+        .into_iter()
+        .filter(|zone| zone.contains_key(&terminal))
+        .map(|zone| {
+            let mut merged = global.clone().unwrap_or_default();
+            merged.extend(zone);
+            merged
+        })
+        .collect())
+}
+
+/// A sample header of the SoundFont.
+struct SampleHeader {
+    name: String,
+    start: usize,
+    end: usize,
+    sample_rate: u32,
+    original_pitch: u8,
+    pitch_correction: i8,
+    link: usize,
+    sample_type: u16,
+}
+
+impl SampleHeader {
+    fn parse(record: &[u8]) -> Self {
+        Self {
+            name: read_name(&record[..20]),
+            start: read_u32(record, 20) as usize,
+            end: read_u32(record, 24) as usize,
+            sample_rate: read_u32(record, 36),
+            original_pitch: record[40],
+            pitch_correction: record[41] as i8,
+            link: read_u16(record, 42) as usize,
+            sample_type: read_u16(record, 44),
+        }
+    }
+}
+
+/// Audio data of a SoundFont.
+struct SampleData<'a> {
+    /// 16 bits samples
+    smpl: &'a [u8],
+
+    /// Optional lower 8 bits of the 24 bits samples
+    sm24: Option<&'a [u8]>,
+}
+
+impl SampleData<'_> {
+    fn bits_per_sample(&self) -> u16 {
+        if self.sm24.is_some() {
+            24
+        } else {
+            16
+        }
+    }
+
+    /// Append the little endian PCM value of a sample point.
+    fn push_point(&self, index: usize, pcm: &mut Vec<u8>) {
+        if let Some(sm24) = self.sm24 {
+            pcm.push(sm24[index]);
+        }
+        pcm.extend_from_slice(&self.smpl[index * 2..index * 2 + 2]);
+    }
+
+    /// Extract one or two (stereo) samples as a WAV file.
+    fn extract(&self, headers: &[&SampleHeader]) -> Result<Vec<u8>> {
+        let point_count = self.smpl.len() / 2;
+        for header in headers {
+            if header.start > header.end || header.end > point_count {
+                bail!("The sample {} is out of the SoundFont data", header.name);
+            }
+            if self.sm24.is_some_and(|sm24| header.end > sm24.len()) {
+                bail!(
+                    "The sample {} is out of the SoundFont 24 bits data",
+                    header.name
+                );
+            }
+        }
+
+        let frames = headers
+            .iter()
+            .map(|h| h.end - h.start)
+            .min()
+            .unwrap_or_default();
+        let format = WavFormat {
+            sample_rate: headers[0].sample_rate,
+            channels: headers.len() as u16,
+            bits_per_sample: self.bits_per_sample(),
+        };
+
+        let mut pcm = Vec::with_capacity(frames * format.block_align());
+        for frame in 0..frames {
+            for header in headers {
+                self.push_point(header.start + frame, &mut pcm);
+            }
+        }
+
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &pcm)?;
+        Ok(wav)
+    }
+}
+
+/// Make a file name out of a sample name, unique in the library.
+fn sample_file_name(name: &str, library: &SampleLibrary) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stem = match stem.trim() {
+        "" => "Sample",
+        stem => stem,
+    };
+
+    (1..)
+        .map(|index| match index {
+            1 => format!("{}.wav", stem),
+            index => format!("{} {}.wav", stem, index),
+        })
+        .find(|file| library.get(file).is_none())
+        .expect("BUG: no free sample name")
+}
+
+/// List the preset names of a SoundFont.
+pub fn sf2_presets(sf2: &[u8]) -> Result<Vec<String>> {
+    let chunks = read_sf2_chunks(sf2)?;
+    let presets = Records::new(&chunks, b"phdr", 38)?;
+    (0..presets.len().saturating_sub(1))
+        .map(|index| Ok(read_name(&presets.get(index)?[..20])))
+        .collect()
+}
+
+/// Read the sub-chunks of the INFO, sdta and pdta lists of a SoundFont.
+fn read_sf2_chunks(sf2: &[u8]) -> Result<BTreeMap<[u8; 4], &[u8]>> {
+    let riff = Chunk::parse(sf2)?;
+    let (riff_type, lists) = riff.list()?;
+    if riff_type != b"sfbk" {
+        bail!("The file is not a SoundFont");
+    }
+
+    let mut chunks = BTreeMap::new();
+    for list in lists {
+        let list = list?;
+        if &list.id != b"LIST" {
+            continue;
+        }
+        let (_, sub_chunks) = list.list()?;
+        for chunk in sub_chunks {
+            let chunk = chunk?;
+            chunks.insert(chunk.id, chunk.data);
+        }
+    }
+    Ok(chunks)
+}
+
+/// Import a preset of a SoundFont as a keygroup program.
+///
+/// The samples used by the preset are extracted as WAV files. The zones
+/// sharing the same key range are grouped as layers of a keygroup.
+/// Every generator that can't be represented is reported as a warning.
+pub fn parse_sf2(
+    sf2: &[u8],
+    preset: usize,
+) -> Result<(KeygroupProgram, SampleLibrary, Vec<ImportWarning>)> {
+    let chunks = read_sf2_chunks(sf2)?;
+    let presets = Records::new(&chunks, b"phdr", 38)?;
+    let preset_bags = Records::new(&chunks, b"pbag", 4)?;
+    let preset_generators = Records::new(&chunks, b"pgen", 4)?;
+    let instruments = Records::new(&chunks, b"inst", 22)?;
+    let instrument_bags = Records::new(&chunks, b"ibag", 4)?;
+    let instrument_generators = Records::new(&chunks, b"igen", 4)?;
+    let sample_headers = Records::new(&chunks, b"shdr", 46)?;
+    let sample_data = SampleData {
+        smpl: chunks
+            .get(b"smpl")
+            .context("The SoundFont does not contain any sample data")?,
+        sm24: chunks.get(b"sm24").copied(),
+    };
+
+    // The last preset is the terminal record
+    let preset_count = presets.len().saturating_sub(1);
+    if preset >= preset_count {
+        bail!("The SoundFont does not have a preset {}", preset + 1);
+    }
+
+    let mut warnings = Vec::new();
+    let name = read_name(&presets.get(preset)?[..20]);
+    if preset_count > 1 {
+        warnings.push(ImportWarning::new(
+            "SoundFont",
+            format!(
+                "The SoundFont contains {} presets, only {} is imported",
+                preset_count, name
+            ),
+        ));
+    }
+
+    let mut unsupported = Vec::new();
+    let mut library = SampleLibrary::default();
+    let mut sample_files = BTreeMap::<usize, String>::new();
+    let mut layers = Vec::new();
+
+    let preset_zones = read_zones(
+        &preset_bags,
+        &preset_generators,
+        presets.bag_range(preset, 24)?,
+        GEN_INSTRUMENT,
+    )?;
+    for preset_zone in preset_zones {
+        unsupported.extend(
+            preset_zone
+                .keys()
+                .filter(|g| {
+                    ![
+                        GEN_INSTRUMENT,
+                        GEN_KEY_RANGE,
+                        GEN_VEL_RANGE,
+                        GEN_COARSE_TUNE,
+                        GEN_FINE_TUNE,
+                    ]
+                    .contains(g)
+                })
+                .map(|g| generator_name(*g)),
+        );
+        let preset_keys = preset_zone
+            .get(&GEN_KEY_RANGE)
+            .map(|a| range_amount(*a))
+            .unwrap_or(0..=127);
+        let preset_velocities = preset_zone
+            .get(&GEN_VEL_RANGE)
+            .map(|a| range_amount(*a))
+            .unwrap_or(0..=127);
+        let preset_tuning = zone_tuning(&preset_zone);
+
+        let instrument = u16::from_le_bytes(preset_zone[&GEN_INSTRUMENT]) as usize;
+        let instrument_zones = read_zones(
+            &instrument_bags,
+            &instrument_generators,
+            instruments.bag_range(instrument, 20)?,
+            GEN_SAMPLE_ID,
+        )?;
+
+        for zone in instrument_zones {
+            let sample_index = u16::from_le_bytes(zone[&GEN_SAMPLE_ID]) as usize;
+            let header = SampleHeader::parse(sample_headers.get(sample_index)?);
+
+            let mut supported = vec![
+                GEN_SAMPLE_ID,
+                GEN_KEY_RANGE,
+                GEN_VEL_RANGE,
+                GEN_OVERRIDING_ROOT_KEY,
+                GEN_COARSE_TUNE,
+                GEN_FINE_TUNE,
+            ];
+            if [SAMPLE_TYPE_LEFT, SAMPLE_TYPE_RIGHT].contains(&header.sample_type) {
+                // The channels of stereo samples are usually panned to each side
+                supported.push(GEN_PAN);
+            }
+            unsupported.extend(
+                zone.keys()
+                    .filter(|g| !supported.contains(g))
+                    .map(|g| generator_name(*g)),
+            );
+
+            let keys = zone
+                .get(&GEN_KEY_RANGE)
+                .map(|a| range_amount(*a))
+                .unwrap_or(0..=127);
+            let velocities = zone
+                .get(&GEN_VEL_RANGE)
+                .map(|a| range_amount(*a))
+                .unwrap_or(0..=127);
+            let (Some(keys), Some(velocities)) = (
+                intersect(&keys, &preset_keys),
+                intersect(&velocities, &preset_velocities),
+            ) else {
+                continue;
+            };
+
+            if header.sample_type & SAMPLE_TYPE_ROM != 0 {
+                warnings.push(ImportWarning::new(
+                    header.name,
+                    "ROM samples are not supported, it is ignored",
+                ));
+                continue;
+            }
+
+            // Stereo samples are made of a left and a right sample linked together
+            let linked = sample_headers
+                .get(header.link)
+                .ok()
+                .map(SampleHeader::parse);
+            let stereo = match (header.sample_type, linked) {
+                (SAMPLE_TYPE_RIGHT, Some(linked)) if linked.sample_type == SAMPLE_TYPE_LEFT => {
+                    // Imported along with its left sample
+                    continue;
+                }
+                (SAMPLE_TYPE_LEFT, Some(linked)) if linked.sample_type == SAMPLE_TYPE_RIGHT => {
+                    Some(linked)
+                }
+                _ => None,
+            };
+
+            let file = match sample_files.get(&sample_index) {
+                Some(file) => file.clone(),
+                None => {
+                    let headers = std::iter::once(&header)
+                        .chain(stereo.as_ref())
+                        .collect_vec();
+                    let file = sample_file_name(&header.name, &library);
+                    library.insert(file.clone(), sample_data.extract(&headers)?);
+                    sample_files.insert(sample_index, file.clone());
+                    file
+                }
+            };
+
+            let root = zone
+                .get(&GEN_OVERRIDING_ROOT_KEY)
+                .map(|a| i16::from_le_bytes(*a))
+                .filter(|root| (0..=127).contains(root))
+                .map(|root| root as u8)
+                .or_else(|| Some(header.original_pitch).filter(|p| *p <= 127))
+                .unwrap_or(60);
+            let cents = preset_tuning + zone_tuning(&zone) + header.pitch_correction as i32;
+            let (root, tune) = split_tuning(root, cents);
+
+            layers.push((
+                keys,
+                Layer {
+                    tune,
+                    ..Layer::new(file, root, velocities)
+                },
+            ));
+        }
+    }
+
+    for (generator, count) in unsupported.into_iter().counts().into_iter().sorted() {
+        warnings.push(ImportWarning::new(
+            "SoundFont",
+            format!(
+                "{} is not supported, it is used by {} zones",
+                generator, count
+            ),
+        ));
+    }
+
+    let keygroups = group_layers(layers, &mut warnings);
+    if keygroups.is_empty() {
+        bail!("The preset {} does not contain any sample", name);
+    }
+
+    Ok((KeygroupProgram { name, keygroups }, library, warnings))
+}
+
+/// Number of zero points written after each sample, as required by the specification.
+const SAMPLE_PADDING: usize = 46;
+
+/// Make a fixed size, zero terminated name.
+fn write_name(name: &str, size: usize) -> Vec<u8> {
+    let mut record: Vec<u8> = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() {
+                c as u8
+            } else {
+                b'_'
+            }
+        })
+        .take(size - 1)
+        .collect();
+    record.resize(size, 0);
+    record
+}
+
+/// Make a generator record.
+fn write_generator(operator: u16, amount: [u8; 2]) -> Vec<u8> {
+    let mut generator = operator.to_le_bytes().to_vec();
+    generator.extend_from_slice(&amount);
+    generator
+}
+
+/// Make a preset or instrument bag record.
+fn write_bag(generator_index: usize) -> Result<Vec<u8>> {
+    let generator_index = u16::try_from(generator_index).context("Too many SoundFont zones")?;
+    Ok([generator_index.to_le_bytes(), [0, 0]].concat())
+}
+
+/// Audio data of a SoundFont being built.
+#[derive(Default)]
+struct SampleDataWriter {
+    smpl: Vec<u8>,
+    sm24: Vec<u8>,
+    shdr: Vec<u8>,
+    count: usize,
+}
+
+impl SampleDataWriter {
+    /// Add a sample file, as one (mono) or two (stereo) linked samples.
+    ///
+    /// Returns the indexes of the added samples.
+    fn add(&mut self, name: &str, wav: &Wav<'_>, root: u8) -> Result<Vec<u16>> {
+        let channels = wav.format.channels as usize;
+        let (sample_types, suffixes) = match channels {
+            1 => (vec![SAMPLE_TYPE_MONO], vec![""]),
+            2 => (vec![SAMPLE_TYPE_LEFT, SAMPLE_TYPE_RIGHT], vec![" L", " R"]),
+            _ => bail!("{}: Only mono and stereo samples are supported", name),
+        };
+        let indexes = (0..channels)
+            .map(|channel| u16::try_from(self.count + channel))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Too many SoundFont samples")?;
+
+        for channel in 0..channels {
+            let start = self.smpl.len() / 2;
+            for frame in 0..wav.frames() {
+                let point = wav.point(frame, channel).to_le_bytes();
+                self.sm24.push(point[1]);
+                self.smpl.extend_from_slice(&point[2..]);
+            }
+            let end = self.smpl.len() / 2;
+            self.sm24.resize(self.sm24.len() + SAMPLE_PADDING, 0);
+            self.smpl.resize(self.smpl.len() + SAMPLE_PADDING * 2, 0);
+
+            let positions = [start, end, start, end]
+                .map(|position| u32::try_from(position).context("The SoundFont is too big"));
+            let link = indexes[(channel + 1) % channels];
+
+            // Keep the channel suffix when the name is truncated
+            let suffix = suffixes[channel];
+            let name: String = name.chars().take(19 - suffix.len()).collect();
+            let mut header = write_name(&format!("{}{}", name, suffix), 20);
+            for position in positions {
+                header.extend_from_slice(&position?.to_le_bytes());
+            }
+            header.extend_from_slice(&wav.format.sample_rate.to_le_bytes());
+            header.extend_from_slice(&[root, 0]);
+            header.extend_from_slice(&link.to_le_bytes());
+            header.extend_from_slice(&sample_types[channel].to_le_bytes());
+            self.shdr.extend(header);
+        }
+        self.count += channels;
+
+        Ok(indexes)
+    }
+}
+
+/// Export keygroups as a SoundFont, with the samples embedded.
+///
+/// The program is made of a single preset and instrument. Each layer of each
+/// keygroup is an instrument zone, stereo samples are made of two zones.
+pub fn make_sf2(name: &str, keygroups: &[Keygroup], library: &SampleLibrary) -> Result<Vec<u8>> {
+    let layers = keygroups
+        .iter()
+        .flat_map(|kg| kg.layers.iter().flatten().map(|layer| (&kg.range, layer)))
+        .collect_vec();
+
+    let missing = layers
+        .iter()
+        .map(|(_, layer)| &layer.file)
+        .filter(|file| library.get(file).is_none())
+        .unique()
+        .join(", ");
+    if !missing.is_empty() {
+        bail!(
+            "The content of these samples is not available, add them again: {}",
+            missing
+        );
+    }
+
+    // Add each sample once, the root note of its first layer is its original pitch
+    let mut samples = SampleDataWriter::default();
+    let mut sample_indexes = BTreeMap::<&str, Vec<u16>>::new();
+    let mut high_resolution = false;
+    for (_, layer) in layers.iter() {
+        if sample_indexes.contains_key(layer.file.as_str()) {
+            continue;
+        }
+        let content = library.wav(&layer.file)?;
+        let wav = Wav::parse(&content).with_context(|| format!("Failed to read {}", layer.file))?;
+        high_resolution |= wav.format.bits_per_sample > 16;
+        let stem = layer.file.rsplit('/').next().unwrap_or_default();
+        let stem = stem.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(stem);
+        let indexes = samples.add(stem, &wav, layer.root)?;
+        sample_indexes.insert(&layer.file, indexes);
+    }
+    samples.shdr.extend(write_name("EOS", 46));
+
+    let mut ibag = Vec::new();
+    let mut igen = Vec::new();
+    for (range, layer) in layers.iter() {
+        let indexes = &sample_indexes[layer.file.as_str()];
+        for (channel, index) in indexes.iter().enumerate() {
+            ibag.extend(write_bag(igen.len() / 4)?);
+            igen.extend(write_generator(
+                GEN_KEY_RANGE,
+                [*range.start(), *range.end()],
+            ));
+            igen.extend(write_generator(
+                GEN_VEL_RANGE,
+                [*layer.velocity.start(), *layer.velocity.end()],
+            ));
+            if indexes.len() == 2 {
+                let pan: i16 = if channel == 0 { -500 } else { 500 };
+                igen.extend(write_generator(GEN_PAN, pan.to_le_bytes()));
+            }
+            igen.extend(write_generator(
+                GEN_OVERRIDING_ROOT_KEY,
+                (layer.root as i16).to_le_bytes(),
+            ));
+            if layer.tune != 0 {
+                igen.extend(write_generator(
+                    GEN_FINE_TUNE,
+                    (layer.tune as i16).to_le_bytes(),
+                ));
+            }
+            igen.extend(write_generator(GEN_SAMPLE_ID, index.to_le_bytes()));
+        }
+    }
+    let instrument_zones = u16::try_from(ibag.len() / 4).context("Too many SoundFont zones")?;
+    ibag.extend(write_bag(igen.len() / 4)?);
+    igen.extend(write_generator(0, [0, 0]));
+
+    let mut inst = write_name(name, 20);
+    inst.extend_from_slice(&0_u16.to_le_bytes());
+    inst.extend(write_name("EOI", 20));
+    inst.extend_from_slice(&instrument_zones.to_le_bytes());
+
+    let mut phdr = Vec::new();
+    for (preset_name, bag) in [(name, 0_u16), ("EOP", 1)] {
+        phdr.extend(write_name(preset_name, 20));
+        phdr.extend_from_slice(&[0; 4]); // preset and bank numbers
+        phdr.extend_from_slice(&bag.to_le_bytes());
+        phdr.extend_from_slice(&[0; 12]); // library, genre and morphology
+    }
+    let pbag = [write_bag(0)?, write_bag(1)?].concat();
+    let pgen = [
+        write_generator(GEN_INSTRUMENT, [0, 0]),
+        write_generator(0, [0, 0]),
+    ]
+    .concat();
+
+    // Version 2.04 is required for 24 bits samples
+    let version: [u16; 2] = if high_resolution { [2, 4] } else { [2, 1] };
+    let mut info = Vec::new();
+    write_chunk(
+        &mut info,
+        b"ifil",
+        &[version[0].to_le_bytes(), version[1].to_le_bytes()].concat(),
+    )?;
+    write_chunk(&mut info, b"isng", b"EMU8000\0")?;
+    let mut inam = write_name(name, 256);
+    inam.truncate(inam.iter().position(|b| *b == 0).unwrap_or_default() + 1);
+    if inam.len() % 2 == 1 {
+        inam.push(0);
+    }
+    write_chunk(&mut info, b"INAM", &inam)?;
+
+    let mut sdta = Vec::new();
+    write_chunk(&mut sdta, b"smpl", &samples.smpl)?;
+    if high_resolution {
+        write_chunk(&mut sdta, b"sm24", &samples.sm24)?;
+    }
+
+    let mut pdta = Vec::new();
+    for (id, data) in [
+        (b"phdr", &phdr[..]),
+        (b"pbag", &pbag[..]),
+        (b"pmod", &[0; 10][..]),
+        (b"pgen", &pgen[..]),
+        (b"inst", &inst[..]),
+        (b"ibag", &ibag[..]),
+        (b"imod", &[0; 10][..]),
+        (b"igen", &igen[..]),
+        (b"shdr", &samples.shdr[..]),
+    ] {
+        write_chunk(&mut pdta, id, data)?;
+    }
+
+    let mut lists = Vec::new();
+    write_list(&mut lists, b"LIST", b"INFO", &info)?;
+    write_list(&mut lists, b"LIST", b"sdta", &sdta)?;
+    write_list(&mut lists, b"LIST", b"pdta", &pdta)?;
+    let mut sf2 = Vec::new();
+    write_list(&mut sf2, b"RIFF", b"sfbk", &lists)?;
+    Ok(sf2)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Keygroup;
+    use crate::utils::{write_chunk, write_list};
+
+    pub use super::*;
+
+    /// Build a SoundFont with one preset of one instrument.
+    ///
+    /// Each zone is (key range, velocity range, sample index), the samples are
+    /// (name, points, root, type, link).
+    fn make_test_sf2(
+        global_velocity: Option<[u8; 2]>,
+        zones: &[([u8; 2], [u8; 2], u16)],
+        samples: &[(&str, Vec<i16>, u8, u16, u16)],
+    ) -> Vec<u8> {
+        fn record(name: &str, size: usize) -> Vec<u8> {
+            let mut record = name.as_bytes().to_vec();
+            record.resize(size, 0);
+            record
+        }
+
+        fn generator(operator: u16, amount: [u8; 2]) -> Vec<u8> {
+            let mut generator = operator.to_le_bytes().to_vec();
+            generator.extend_from_slice(&amount);
+            generator
+        }
+
+        let mut smpl = Vec::new();
+        let mut shdr = Vec::new();
+        for (name, points, root, sample_type, link) in samples {
+            let start = (smpl.len() / 2) as u32;
+            for point in points {
+                smpl.extend_from_slice(&point.to_le_bytes());
+            }
+            let end = (smpl.len() / 2) as u32;
+            let mut header = record(name, 20);
+            for value in [start, end, start, end, 44100] {
+                header.extend_from_slice(&value.to_le_bytes());
+            }
+            header.extend_from_slice(&[*root, 0]);
+            header.extend_from_slice(&link.to_le_bytes());
+            header.extend_from_slice(&sample_type.to_le_bytes());
+            shdr.extend(header);
+        }
+        shdr.extend(record("EOS", 46));
+
+        let mut igen = Vec::new();
+        let mut ibag = Vec::new();
+        let mut generator_count = 0_u16;
+        if let Some(velocity) = global_velocity {
+            ibag.extend_from_slice(&[generator_count.to_le_bytes(), [0, 0]].concat());
+            igen.extend(generator(GEN_VEL_RANGE, velocity));
+            generator_count += 1;
+        }
+        for (keys, velocities, sample) in zones {
+            ibag.extend_from_slice(&[generator_count.to_le_bytes(), [0, 0]].concat());
+            igen.extend(generator(GEN_KEY_RANGE, *keys));
+            igen.extend(generator(GEN_VEL_RANGE, *velocities));
+            igen.extend(generator(GEN_SAMPLE_ID, sample.to_le_bytes()));
+            generator_count += 3;
+        }
+        let bag_count = (ibag.len() / 4) as u16;
+        ibag.extend_from_slice(&[generator_count.to_le_bytes(), [0, 0]].concat());
+        igen.extend(generator(0, [0, 0]));
+
+        let mut inst = record("Instrument", 20);
+        inst.extend_from_slice(&0_u16.to_le_bytes());
+        inst.extend(record("EOI", 20));
+        inst.extend_from_slice(&bag_count.to_le_bytes());
+
+        let mut phdr = record("Test Piano", 20);
+        phdr.extend_from_slice(&[0; 4]);
+        phdr.extend_from_slice(&0_u16.to_le_bytes());
+        phdr.extend_from_slice(&[0; 12]);
+        phdr.extend(record("EOP", 20));
+        phdr.extend_from_slice(&[0; 4]);
+        phdr.extend_from_slice(&1_u16.to_le_bytes());
+        phdr.extend_from_slice(&[0; 12]);
+        let pbag = [0, 0, 0, 0, 1, 0, 0, 0];
+        let pgen = [generator(GEN_INSTRUMENT, [0, 0]), generator(0, [0, 0])].concat();
+
+        let mut sdta = Vec::new();
+        write_chunk(&mut sdta, b"smpl", &smpl).unwrap();
+        let mut pdta = Vec::new();
+        for (id, data) in [
+            (b"phdr", &phdr[..]),
+            (b"pbag", &pbag[..]),
+            (b"pmod", &[0; 10][..]),
+            (b"pgen", &pgen[..]),
+            (b"inst", &inst[..]),
+            (b"ibag", &ibag[..]),
+            (b"imod", &[0; 10][..]),
+            (b"igen", &igen[..]),
+            (b"shdr", &shdr[..]),
+        ] {
+            write_chunk(&mut pdta, id, data).unwrap();
+        }
+        let mut lists = Vec::new();
+        write_list(&mut lists, b"LIST", b"sdta", &sdta).unwrap();
+        write_list(&mut lists, b"LIST", b"pdta", &pdta).unwrap();
+        let mut sf2 = Vec::new();
+        write_list(&mut sf2, b"RIFF", b"sfbk", &lists).unwrap();
+        sf2
+    }
+
+    #[test]
+    fn parse_sf2_test() {
+        let sf2 = make_test_sf2(
+            Some([0, 100]),
+            &[
+                ([0, 50], [0, 63], 0),
+                ([0, 50], [64, 127], 1),
+                ([51, 127], [0, 127], 2),
+            ],
+            &[
+                ("A2 soft", vec![1, 2, 3], 45, 1, 0),
+                ("A2 loud", vec![4, 5], 45, 1, 0),
+                ("C3", vec![6, 7, 8, 9], 48, 1, 0),
+            ],
+        );
+
+        let (program, library, warnings) = parse_sf2(&sf2, 0).unwrap();
+
+        assert_eq!(program.name, "Test Piano");
+        assert_eq!(
+            program.keygroups,
+            vec![
+                Keygroup::new(
+                    0..=50,
+                    [
+                        Some(Layer::new("A2 soft.wav".to_string(), 45, 0..=63)),
+                        Some(Layer::new("A2 loud.wav".to_string(), 45, 64..=127)),
+                        None,
+                        None,
+                    ]
+                ),
+                Keygroup::new(
+                    51..=127,
+                    [
+                        Some(Layer::new("C3.wav".to_string(), 48, 0..=127)),
+                        None,
+                        None,
+                        None,
+                    ]
+                ),
+            ]
+        );
+        assert_eq!(warnings, vec![]);
+        assert_eq!(library.len(), 3);
+
+        let wav = Chunk::parse(library.get("C3.wav").unwrap()).unwrap();
+        let (_, chunks) = wav.list().unwrap();
+        let data = chunks
+            .map(|c| c.unwrap())
+            .find(|c| &c.id == b"data")
+            .unwrap();
+        assert_eq!(data.data, &[6, 0, 7, 0, 8, 0, 9, 0]);
+    }
+
+    #[test]
+    fn parse_sf2_stereo_test() {
+        let sf2 = make_test_sf2(
+            None,
+            &[([0, 127], [0, 127], 0), ([0, 127], [0, 127], 1)],
+            &[
+                ("Pad L", vec![1, 2], 60, SAMPLE_TYPE_LEFT, 1),
+                ("Pad R", vec![3, 4], 60, SAMPLE_TYPE_RIGHT, 0),
+            ],
+        );
+
+        let (program, library, _) = parse_sf2(&sf2, 0).unwrap();
+
+        assert_eq!(program.layer_count(), 1);
+        let wav = Chunk::parse(library.get("Pad L.wav").unwrap()).unwrap();
+        let (_, chunks) = wav.list().unwrap();
+        let data = chunks
+            .map(|c| c.unwrap())
+            .find(|c| &c.id == b"data")
+            .unwrap();
+        assert_eq!(data.data, &[1, 0, 3, 0, 2, 0, 4, 0]);
+    }
+
+    #[test]
+    fn sf2_presets_test() {
+        let sf2 = make_test_sf2(
+            None,
+            &[([0, 127], [0, 127], 0)],
+            &[("A", vec![0], 60, 1, 0)],
+        );
+
+        assert_eq!(sf2_presets(&sf2).unwrap(), vec!["Test Piano"]);
+        assert!(parse_sf2(&sf2, 1).is_err());
+    }
+
+    /// Make a WAV file out of interleaved PCM data.
+    fn make_test_wav(channels: u16, bits_per_sample: u16, pcm: &[u8]) -> Vec<u8> {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels,
+            bits_per_sample,
+        };
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, pcm).unwrap();
+        wav
+    }
+
+    #[test]
+    fn sf2_round_trip_test() {
+        let keygroups = vec![
+            Keygroup::new(
+                0..=50,
+                [
+                    Some(Layer::new("A2.wav".to_string(), 45, 0..=63)),
+                    Some(Layer::new("A2 loud.wav".to_string(), 45, 64..=127)),
+                    None,
+                    None,
+                ],
+            ),
+            Keygroup::new(
+                51..=127,
+                [
+                    Some(Layer {
+                        tune: -30,
+                        ..Layer::new("C3.wav".to_string(), 48, 0..=127)
+                    }),
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+        ];
+        let mut library = SampleLibrary::default();
+        library.insert("A2.wav".to_string(), make_test_wav(1, 16, &[1, 0, 2, 0]));
+        library.insert("A2 loud.wav".to_string(), make_test_wav(1, 16, &[3, 0]));
+        library.insert("C3.wav".to_string(), make_test_wav(1, 16, &[4, 0, 5, 0]));
+
+        let sf2 = make_sf2("Piano", &keygroups, &library).unwrap();
+        let (program, imported_library, warnings) = parse_sf2(&sf2, 0).unwrap();
+
+        assert_eq!(program.name, "Piano");
+        assert_eq!(program.keygroups, keygroups);
+        assert_eq!(warnings, vec![]);
+        assert_eq!(imported_library, library);
+    }
+
+    #[test]
+    fn sf2_round_trip_stereo_test() {
+        let keygroups = vec![Keygroup::new(
+            0..=127,
+            [
+                Some(Layer::new("Samples/Pad.wav".to_string(), 60, 0..=127)),
+                None,
+                None,
+                None,
+            ],
+        )];
+        let mut library = SampleLibrary::default();
+        library.insert(
+            "Samples/Pad.wav".to_string(),
+            make_test_wav(2, 24, &[1, 2, 3, 4, 5, 6]),
+        );
+
+        let sf2 = make_sf2("Pad", &keygroups, &library).unwrap();
+        let (program, imported_library, warnings) = parse_sf2(&sf2, 0).unwrap();
+
+        assert_eq!(program.layer_count(), 1);
+        assert_eq!(warnings, vec![]);
+        assert_eq!(
+            imported_library.get("Pad L.wav"),
+            library.get("Samples/Pad.wav")
+        );
+    }
+
+    #[test]
+    fn make_sf2_missing_sample_test() {
+        let keygroups = vec![Keygroup::new(
+            0..=127,
+            [
+                Some(Layer::new("A.wav".to_string(), 60, 0..=127)),
+                None,
+                None,
+                None,
+            ],
+        )];
+
+        assert!(make_sf2("Test", &keygroups, &SampleLibrary::default()).is_err());
+    }
+}
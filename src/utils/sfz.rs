@@ -0,0 +1,555 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::model::{ImportWarning, Keygroup, Layer};
+
+use super::{group_layers, split_tuning};
+
+/// Opcodes of a region, after the inheritance from its headers.
+type Opcodes = BTreeMap<String, String>;
+
+/// A header name with its opcodes, in the file order.
+type Header = (String, Vec<(String, String)>);
+
+/// Opcodes read when importing a region.
+const MAPPED_OPCODES: [&str; 14] = [
+    "sample",
+    "pitch_keycenter",
+    "key",
+    "lokey",
+    "hikey",
+    "lovel",
+    "hivel",
+    "tune",
+    "offset",
+    "end",
+    "loop_mode",
+    "loop_start",
+    "loop_end",
+    "volume",
+];
+
+/// Export keygroups as SFZ regions.
+pub fn make_sfz<'a, I>(name: &str, keygroups: I) -> Result<String>
+where
+    I: IntoIterator<Item = &'a Keygroup>,
+{
+    let mut sfz = String::new();
+    writeln!(sfz, "// {}", name)?;
+
+    for keygroup in keygroups.into_iter() {
+        writeln!(sfz)?;
+        writeln!(sfz, "<group>")?;
+        for layer in keygroup.layers.iter().filter_map(|l| l.as_ref()) {
+            let mut opcodes = vec![
+                format!("sample={}", layer.file),
+                format!("pitch_keycenter={}", layer.root),
+                format!("lokey={}", keygroup.range.start()),
+                format!("hikey={}", keygroup.range.end()),
+                format!("lovel={}", layer.velocity.start()),
+                format!("hivel={}", layer.velocity.end()),
+            ];
+            if layer.tune != 0 {
+                opcodes.push(format!("tune={}", layer.tune));
+            }
+            // The SFZ end points are the last played frames
+            if let Some(region) = &layer.sample_region {
+                opcodes.push(format!("offset={}", region.start));
+                opcodes.push(format!("end={}", region.end.saturating_sub(1)));
+            }
+            if let Some(sample_loop) = &layer.sample_loop {
+                opcodes.push("loop_mode=loop_continuous".to_string());
+                opcodes.push(format!("loop_start={}", sample_loop.start));
+                opcodes.push(format!("loop_end={}", sample_loop.end.saturating_sub(1)));
+            }
+            if layer.volume != 0 {
+                opcodes.push(format!("volume={:.1}", layer.volume as f32 / 10.0));
+            }
+            writeln!(sfz, "<region> {}", opcodes.join(" "))?;
+        }
+    }
+
+    Ok(sfz)
+}
+
+/// Remove the line and block comments of an SFZ file.
+fn strip_comments(sfz: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"(?s)/\*.*?\*/|//[^\n]*").expect("BUG: Invalid comment regex");
+    }
+    RE.replace_all(sfz, " ").to_string()
+}
+
+/// Parse a note opcode value, either a midi number or a note name (c4 = 60).
+fn parse_note(value: &str) -> Option<u8> {
+    if let Ok(number) = value.parse::<u8>() {
+        return (number <= 127).then_some(number);
+    }
+
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^(?i)([a-g])([#b]?)(-?\d)$").expect("BUG: Invalid note regex");
+    }
+    let capture = RE.captures(value)?;
+    let natural: i32 = match capture[1].to_ascii_lowercase().as_str() {
+        "c" => 0,
+        "d" => 2,
+        "e" => 4,
+        "f" => 5,
+        "g" => 7,
+        "a" => 9,
+        "b" => 11,
+        _ => unreachable!(),
+    };
+    let accidental = match &capture[2] {
+        "#" => 1,
+        "b" => -1,
+        _ => 0,
+    };
+    let octave: i32 = capture[3].parse().ok()?;
+    let note = (octave + 1) * 12 + natural + accidental;
+    u8::try_from(note).ok().filter(|n| *n <= 127)
+}
+
+/// Parse a note opcode of a region, with a default if it's not set.
+fn region_note(
+    opcodes: &Opcodes,
+    opcode: &str,
+    default: u8,
+    location: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> u8 {
+    match opcodes.get(opcode) {
+        Some(value) => parse_note(value).unwrap_or_else(|| {
+            warnings.push(ImportWarning::new(
+                location,
+                format!("{} has an invalid value {}", opcode, value),
+            ));
+            default
+        }),
+        None => default,
+    }
+}
+
+/// Parse the velocity opcode of a region, with a default if it's not set.
+fn region_velocity(
+    opcodes: &Opcodes,
+    opcode: &str,
+    default: u8,
+    location: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> u8 {
+    match opcodes.get(opcode) {
+        Some(value) => value
+            .parse::<u8>()
+            .ok()
+            .filter(|v| *v <= 127)
+            .unwrap_or_else(|| {
+                warnings.push(ImportWarning::new(
+                    location,
+                    format!("{} has an invalid value {}", opcode, value),
+                ));
+                default
+            }),
+        None => default,
+    }
+}
+
+/// Parse a numeric opcode of a region, if it's set and valid.
+fn region_number<T: FromStr>(
+    opcodes: &Opcodes,
+    opcode: &str,
+    location: &str,
+    warnings: &mut Vec<ImportWarning>,
+) -> Option<T> {
+    let value = opcodes.get(opcode)?;
+    let number = value.parse::<T>().ok();
+    if number.is_none() {
+        warnings.push(ImportWarning::new(
+            location,
+            format!("{} has an invalid value {}", opcode, value),
+        ));
+    }
+    number
+}
+
+/// Split an SFZ file in its headers and their opcodes.
+///
+/// Opcode values can contain spaces (ie. sample paths), so a value extends
+/// until the next opcode or header.
+fn parse_headers(sfz: &str) -> Result<Vec<Header>> {
+    lazy_static! {
+        static ref HEADER_RE: Regex = Regex::new(r"<(\w+)>").expect("BUG: Invalid header regex");
+        static ref OPCODE_RE: Regex =
+            Regex::new(r"(?:^|\s)([A-Za-z0-9_]+)=").expect("BUG: Invalid opcode regex");
+    }
+
+    let sfz = strip_comments(sfz);
+    let mut headers = Vec::new();
+    let mut header_matches = HEADER_RE.captures_iter(&sfz).peekable();
+
+    if let Some(first) = header_matches.peek() {
+        let preamble = sfz[..first.get(0).expect("BUG: no header match").start()].trim();
+        if !preamble.is_empty() {
+            bail!("Unexpected content before the first header: {}", preamble);
+        }
+    }
+
+    while let Some(header) = header_matches.next() {
+        let header_match = header.get(0).expect("BUG: no header match");
+        let end = header_matches
+            .peek()
+            .map(|next| next.get(0).expect("BUG: no header match").start())
+            .unwrap_or(sfz.len());
+        let body = &sfz[header_match.end()..end];
+
+        let opcode_matches = OPCODE_RE.captures_iter(body).collect_vec();
+        let mut opcodes = Vec::new();
+        for (index, opcode) in opcode_matches.iter().enumerate() {
+            let value_start = opcode.get(0).expect("BUG: no opcode match").end();
+            let value_end = opcode_matches
+                .get(index + 1)
+                .map(|next| next.get(0).expect("BUG: no opcode match").start())
+                .unwrap_or(body.len());
+            opcodes.push((
+                opcode[1].to_string(),
+                body[value_start..value_end].trim().to_string(),
+            ));
+        }
+        headers.push((header[1].to_string(), opcodes));
+    }
+
+    Ok(headers)
+}
+
+/// Import the regions of an SFZ file as keygroups.
+///
+/// Regions sharing the same key range are grouped as layers of a keygroup.
+/// Every opcode that can't be represented is reported as a warning.
+pub fn parse_sfz(sfz: &str) -> Result<(Vec<Keygroup>, Vec<ImportWarning>)> {
+    let mut warnings = Vec::new();
+
+    if sfz.lines().any(|l| l.trim_start().starts_with('#')) {
+        warnings.push(ImportWarning::new(
+            "SFZ",
+            "#define and #include directives are not supported",
+        ));
+    }
+    let sfz = sfz
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .join("\n");
+
+    let mut default_path = String::new();
+    let mut global = Opcodes::new();
+    let mut master = Opcodes::new();
+    let mut group = Opcodes::new();
+    let mut regions = Vec::new();
+
+    for (header, opcodes) in parse_headers(&sfz)? {
+        let opcodes = opcodes.into_iter();
+        match header.as_str() {
+            "control" => {
+                for (opcode, value) in opcodes {
+                    if opcode == "default_path" {
+                        default_path = value;
+                    } else {
+                        warnings.push(ImportWarning::new(
+                            "<control>",
+                            format!("{} is not supported", opcode),
+                        ));
+                    }
+                }
+            }
+            "global" => {
+                global = opcodes.collect();
+                master.clear();
+                group.clear();
+            }
+            "master" => {
+                master = opcodes.collect();
+                group.clear();
+            }
+            "group" => {
+                group = opcodes.collect();
+            }
+            "region" => {
+                let mut region = global.clone();
+                region.extend(master.clone());
+                region.extend(group.clone());
+                region.extend(opcodes);
+                regions.push(region);
+            }
+            header => warnings.push(ImportWarning::new(
+                format!("<{}>", header),
+                "Header is not supported",
+            )),
+        }
+    }
+
+    // Report the unsupported opcodes once, with the number of regions using them
+    let unsupported = regions
+        .iter()
+        .flat_map(|region| region.keys())
+        .filter(|opcode| !MAPPED_OPCODES.contains(&opcode.as_str()))
+        .counts();
+    for (opcode, count) in unsupported.into_iter().sorted() {
+        warnings.push(ImportWarning::new(
+            "SFZ",
+            format!(
+                "{} is not supported, it is used by {} regions",
+                opcode, count
+            ),
+        ));
+    }
+
+    let mut layers = Vec::new();
+    for (index, region) in regions.iter().enumerate() {
+        let location = format!("<region> {}", index + 1);
+        let Some(sample) = region.get("sample") else {
+            warnings.push(ImportWarning::new(location, "No sample, it is ignored"));
+            continue;
+        };
+        let file = format!("{}{}", default_path, sample).replace('\\', "/");
+
+        let key = region_note(region, "key", 60, &location, &mut warnings);
+        let (default_low, default_high, default_root) = if region.contains_key("key") {
+            (key, key, key)
+        } else {
+            (0, 127, 60)
+        };
+        let low_key = region_note(region, "lokey", default_low, &location, &mut warnings);
+        let high_key = region_note(region, "hikey", default_high, &location, &mut warnings);
+        let root = region_note(
+            region,
+            "pitch_keycenter",
+            default_root,
+            &location,
+            &mut warnings,
+        );
+        let low_velocity = region_velocity(region, "lovel", 0, &location, &mut warnings);
+        let high_velocity = region_velocity(region, "hivel", 127, &location, &mut warnings);
+        let cents = region_number(region, "tune", &location, &mut warnings).unwrap_or(0);
+        let (root, tune) = split_tuning(root, cents);
+
+        // The SFZ end points are the last played frames
+        let offset: usize = region_number(region, "offset", &location, &mut warnings).unwrap_or(0);
+        let end: Option<usize> = region_number(region, "end", &location, &mut warnings);
+        let sample_region = end.map(|end| offset..end + 1);
+        if offset > 0 && end.is_none() {
+            warnings.push(ImportWarning::new(
+                &location,
+                "offset is only supported with end, it is ignored",
+            ));
+        }
+
+        let looped = match region.get("loop_mode").map(String::as_str) {
+            None | Some("loop_continuous") | Some("loop_sustain") => true,
+            Some("no_loop") => false,
+            Some(mode) => {
+                warnings.push(ImportWarning::new(
+                    &location,
+                    format!("loop_mode {} is not supported", mode),
+                ));
+                false
+            }
+        };
+        let loop_start: Option<usize> =
+            region_number(region, "loop_start", &location, &mut warnings);
+        let loop_end: Option<usize> = region_number(region, "loop_end", &location, &mut warnings);
+        let sample_loop = match (loop_start, loop_end) {
+            (Some(start), Some(end)) if looped => Some(start..end + 1),
+            _ => None,
+        };
+
+        let volume = region_number::<f32>(region, "volume", &location, &mut warnings)
+            .map(|volume| (volume * 10.0).round() as i16)
+            .unwrap_or(0);
+
+        layers.push((
+            low_key..=high_key,
+            Layer {
+                tune,
+                sample_region,
+                sample_loop,
+                volume,
+                ..Layer::new(file, root, low_velocity..=high_velocity)
+            },
+        ));
+    }
+
+    let keygroups = group_layers(layers, &mut warnings);
+
+    if keygroups.is_empty() {
+        bail!("The SFZ file does not contain any region");
+    }
+
+    Ok((keygroups, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    pub use super::*;
+
+    #[rstest]
+    #[case("60", Some(60))]
+    #[case("c4", Some(60))]
+    #[case("C#4", Some(61))]
+    #[case("db4", Some(61))]
+    #[case("a-1", Some(9))]
+    #[case("g9", Some(127))]
+    #[case("h4", None)]
+    #[case("128", None)]
+    fn parse_note_test(#[case] input: &str, #[case] expected: Option<u8>) {
+        assert_eq!(parse_note(input), expected);
+    }
+
+    #[test]
+    fn sfz_round_trip_test() {
+        let keygroups = vec![
+            Keygroup::new(
+                0..=50,
+                [
+                    Some(Layer::new("Piano A2.wav".to_string(), 45, 0..=63)),
+                    Some(Layer::new("Piano A2 loud.wav".to_string(), 45, 64..=127)),
+                    None,
+                    None,
+                ],
+            ),
+            Keygroup::new(
+                51..=127,
+                [
+                    Some(Layer {
+                        tune: -30,
+                        sample_region: Some(100..20000),
+                        sample_loop: Some(5000..15000),
+                        volume: -35,
+                        ..Layer::new("Piano C3.wav".to_string(), 48, 0..=127)
+                    }),
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+        ];
+
+        let sfz = make_sfz("Piano", &keygroups).unwrap();
+        let (imported, warnings) = parse_sfz(&sfz).unwrap();
+
+        assert_eq!(imported, keygroups);
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn parse_sfz_inheritance_test() {
+        let sfz = r"
+            <control> default_path=samples\
+            <global> lovel=0 hivel=127 ampeg_release=0.5
+            <group> lokey=c4 hikey=b4 pitch_keycenter=c4 // comment
+            <region> sample=c4 soft.wav hivel=63
+            <region> sample=c4 loud.wav lovel=64
+            /* block
+               comment */
+            <group> key=72
+            <region> sample=c5.wav
+        ";
+
+        let (keygroups, warnings) = parse_sfz(sfz).unwrap();
+
+        assert_eq!(
+            keygroups,
+            vec![
+                Keygroup::new(
+                    60..=71,
+                    [
+                        Some(Layer::new("samples/c4 soft.wav".to_string(), 60, 0..=63)),
+                        Some(Layer::new("samples/c4 loud.wav".to_string(), 60, 64..=127)),
+                        None,
+                        None,
+                    ],
+                ),
+                Keygroup::new(
+                    72..=72,
+                    [
+                        Some(Layer::new("samples/c5.wav".to_string(), 72, 0..=127)),
+                        None,
+                        None,
+                        None,
+                    ],
+                ),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![ImportWarning::new(
+                "SFZ",
+                "ampeg_release is not supported, it is used by 3 regions"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_sfz_sample_settings_test() {
+        let sfz = r"
+            <region> sample=a.wav key=60 tune=150 offset=10 end=99 loop_start=20 loop_end=79 volume=-6
+            <region> sample=b.wav key=61 offset=10 loop_mode=one_shot tune=high
+        ";
+
+        let (keygroups, warnings) = parse_sfz(sfz).unwrap();
+
+        assert_eq!(
+            keygroups,
+            vec![
+                Keygroup::new(
+                    60..=60,
+                    [
+                        Some(Layer {
+                            tune: -50,
+                            sample_region: Some(10..100),
+                            sample_loop: Some(20..80),
+                            volume: -60,
+                            ..Layer::new("a.wav".to_string(), 58, 0..=127)
+                        }),
+                        None,
+                        None,
+                        None,
+                    ],
+                ),
+                Keygroup::new(
+                    61..=61,
+                    [
+                        Some(Layer::new("b.wav".to_string(), 61, 0..=127)),
+                        None,
+                        None,
+                        None,
+                    ],
+                ),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                ImportWarning::new("<region> 2", "tune has an invalid value high"),
+                ImportWarning::new(
+                    "<region> 2",
+                    "offset is only supported with end, it is ignored"
+                ),
+                ImportWarning::new("<region> 2", "loop_mode one_shot is not supported"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_sfz_no_region_test() {
+        assert!(parse_sfz("<group> lokey=1").is_err());
+    }
+}
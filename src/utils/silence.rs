@@ -0,0 +1,68 @@
+//! Detection of the silence around the samples.
+use std::ops::Range;
+
+use super::Wav;
+
+/// Find the audible part of a region of a sample, in frames.
+///
+/// The region starts at the first frame louder than the threshold (in dB, below
+/// the full scale) and ends after the last one. The start is moved earlier by
+/// the pre-roll duration (in seconds), to keep the beginning of the attack,
+/// without leaving the searched region. None is returned when the whole region
+/// is under the threshold.
+pub fn audible_region(
+    wav: &Wav<'_>,
+    region: Range<usize>,
+    threshold: f32,
+    pre_roll: f32,
+) -> Option<Range<usize>> {
+    let threshold = 10_f32.powf(threshold / 20.0) * 2_f32.powi(31);
+    let channels = wav.format.channels as usize;
+    let is_audible = |frame: usize| {
+        (0..channels).any(|channel| (wav.point(frame, channel) as f32).abs() > threshold)
+    };
+
+    let end = region.end.min(wav.frames());
+    let first = (region.start..end).find(|&frame| is_audible(frame))?;
+    let last = (first..end).rev().find(|&frame| is_audible(frame))?;
+
+    let pre_roll = (pre_roll.max(0.0) * wav.format.sample_rate as f32) as usize;
+    Some(first.saturating_sub(pre_roll).max(region.start)..last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::utils::{write_wav, WavFormat};
+
+    #[rstest]
+    #[case(&[0, 0, 10, 20000, -20000, 5, 0], 0..7, -60.0, 0.0, Some(3..5))]
+    #[case(&[0, 0, 10, 20000, -20000, 5, 0], 0..7, -90.0, 0.0, Some(2..6))]
+    #[case(&[0, 0, 10, 20000, -20000, 5, 0], 0..7, -60.0, 0.5, Some(1..5))]
+    #[case(&[0, 0, 10, 20000, -20000, 5, 0], 0..7, -60.0, 10.0, Some(0..5))]
+    #[case(&[0, 0, 10, 5, 0], 0..5, -60.0, 0.0, None)]
+    #[case(&[20000, 0, 0, 20000, -20000, 5, 0], 2..4, -60.0, 10.0, Some(2..4))]
+    #[case(&[20000, 0, 0, 20000, -20000, 5, 0], 1..3, -60.0, 0.0, None)]
+    fn audible_region_test(
+        #[case] points: &[i16],
+        #[case] region: Range<usize>,
+        #[case] threshold: f32,
+        #[case] pre_roll: f32,
+        #[case] expected: Option<Range<usize>>,
+    ) {
+        // A sample rate of 4 Hz, so that the pre-roll is easy to express
+        let format = WavFormat {
+            sample_rate: 4,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let data: Vec<u8> = points.iter().flat_map(|p| p.to_le_bytes()).collect();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &data).unwrap();
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(audible_region(&wav, region, threshold, pre_roll), expected);
+    }
+}
@@ -0,0 +1,229 @@
+//! Cutting a recording of sequential notes or a loop into samples.
+use std::ops::Range;
+
+use anyhow::Result;
+
+use super::{write_wav_with_metadata, Wav, WavMetadata};
+
+/// Duration kept before the start of each detected note, in seconds.
+const PRE_ROLL: f32 = 0.005;
+
+/// Duration of the windows compared to find the transients, in seconds.
+const TRANSIENT_WINDOW: f32 = 0.005;
+
+/// Level under which no transient is detected, relative to the full scale.
+const TRANSIENT_FLOOR: f32 = 0.001;
+
+/// Find the notes of a recording, separated by silences.
+///
+/// A note starts at the first frame louder than the threshold (in dB, below the
+/// full scale), and ends once the level stayed under the threshold for the
+/// minimum silence duration (in seconds).
+pub fn detect_notes(wav: &Wav<'_>, threshold: f32, min_silence: f32) -> Vec<Range<usize>> {
+    let threshold = 10_f32.powf(threshold / 20.0) * 2_f32.powi(31);
+    let channels = wav.format.channels as usize;
+    let is_audible = |frame: usize| {
+        (0..channels).any(|channel| (wav.point(frame, channel) as f32).abs() > threshold)
+    };
+    let sample_rate = wav.format.sample_rate as f32;
+    let min_silence = ((min_silence.max(0.0) * sample_rate) as usize).max(1);
+    let pre_roll = (PRE_ROLL * sample_rate) as usize;
+
+    let mut notes = Vec::new();
+    let mut note: Option<(usize, usize)> = None;
+    let mut previous_end = 0;
+    for frame in 0..wav.frames() {
+        if is_audible(frame) {
+            match note.as_mut() {
+                Some((_, last)) => *last = frame,
+                None => note = Some((frame, frame)),
+            }
+        } else if let Some((first, last)) = note {
+            if frame - last >= min_silence {
+                let start = first.saturating_sub(pre_roll).max(previous_end);
+                notes.push(start..last + 1);
+                previous_end = last + 1;
+                note = None;
+            }
+        }
+    }
+    if let Some((first, last)) = note {
+        notes.push(first.saturating_sub(pre_roll).max(previous_end)..last + 1);
+    }
+    notes
+}
+
+/// Cut a recording in notes of a fixed length, separated by a gap.
+///
+/// The lengths are in frames. The last note stops at the end of the recording.
+pub fn fixed_slices(frames: usize, length: usize, gap: usize) -> Vec<Range<usize>> {
+    if length == 0 {
+        return Vec::new();
+    }
+    (0..frames)
+        .step_by(length + gap)
+        .map(|start| start..(start + length).min(frames))
+        .collect()
+}
+
+/// Cut a loop at its transients.
+///
+/// A transient is a rise of the level by more than the sensitivity (in dB)
+/// from one short window to the next, at least the minimum gap (in seconds)
+/// after the previous one. The first slice starts with the loop, and each
+/// slice ends at the next transient.
+pub fn detect_transients(wav: &Wav<'_>, sensitivity: f32, min_gap: f32) -> Vec<Range<usize>> {
+    let points = wav.mono();
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let sample_rate = wav.format.sample_rate as f32;
+    let window = ((TRANSIENT_WINDOW * sample_rate) as usize).max(1);
+    let min_gap = ((min_gap.max(0.0) * sample_rate) as usize).max(1);
+    let levels = points
+        .chunks(window)
+        .map(|chunk| {
+            let power = chunk.iter().map(|p| p * p).sum::<f32>() / chunk.len() as f32;
+            power.sqrt().max(TRANSIENT_FLOOR)
+        })
+        .collect::<Vec<_>>();
+
+    let mut starts = vec![0];
+    let mut previous = 0;
+    for (index, pair) in levels.windows(2).enumerate() {
+        let rise = 20.0 * (pair[1] / pair[0]).log10();
+        let frame = (index + 1) * window;
+        if rise >= sensitivity && frame - previous >= min_gap {
+            starts.push(frame);
+            previous = frame;
+        }
+    }
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&points.len()]))
+        .map(|(&start, &end)| start..end)
+        .collect()
+}
+
+/// Cut a loop in a number of slices of the same length.
+pub fn equal_slices(frames: usize, count: usize) -> Vec<Range<usize>> {
+    if frames == 0 {
+        return Vec::new();
+    }
+    let count = count.clamp(1, frames);
+    (0..count)
+        .map(|index| index * frames / count..(index + 1) * frames / count)
+        .collect()
+}
+
+/// Write a region of a WAV file as a new WAV file, with sampler metadata.
+pub fn cut_wav(wav: &Wav<'_>, region: Range<usize>, metadata: &WavMetadata) -> Result<Vec<u8>> {
+    let block_align = wav.format.block_align();
+    let end = region.end.min(wav.frames());
+    let start = region.start.min(end);
+    let data = &wav.data[start * block_align..end * block_align];
+
+    let mut content = Vec::new();
+    write_wav_with_metadata(&mut content, &wav.format, data, metadata)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::utils::wav_from_points;
+
+    fn wav(points: &[i16]) -> Vec<u8> {
+        // A sample rate of 10 Hz, so that the durations are easy to express
+        wav_from_points(10, 1, points)
+    }
+
+    #[rstest]
+    #[case(&[0, 900, 900, 0, 0, 0, 900, 0, 0], 0.3, vec![1..3, 6..7])]
+    #[case(&[0, 900, 900, 0, 0, 0, 900, 0, 0], 0.4, vec![1..7])]
+    #[case(&[900, 0, 0, 900, 900], 0.2, vec![0..1, 3..5])]
+    #[case(&[0, 0, 0], 0.2, vec![])]
+    fn detect_notes_test(
+        #[case] points: &[i16],
+        #[case] min_silence: f32,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let wav = wav(points);
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(detect_notes(&wav, -40.0, min_silence), expected);
+    }
+
+    #[rstest]
+    #[case(10, 3, 1, vec![0..3, 4..7, 8..10])]
+    #[case(8, 4, 0, vec![0..4, 4..8])]
+    #[case(8, 0, 2, vec![])]
+    fn fixed_slices_test(
+        #[case] frames: usize,
+        #[case] length: usize,
+        #[case] gap: usize,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        assert_eq!(fixed_slices(frames, length, gap), expected);
+    }
+
+    /// Decaying clicks starting at the given frames, at 1000 Hz.
+    fn clicks(starts: &[usize], frames: usize) -> Vec<i16> {
+        (0..frames)
+            .map(|frame| {
+                let Some(start) = starts.iter().rev().find(|&&start| start <= frame) else {
+                    return 0;
+                };
+                let since = (frame - start) as f32;
+                (20000.0 * (-since / 20.0).exp() * (since * 0.5).cos()) as i16
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(&[0, 300, 700], 0.05, vec![0..300, 300..700, 700..1000])]
+    #[case(&[100, 300, 320], 0.05, vec![0..100, 100..300, 300..1000])]
+    #[case(&[], 0.05, vec![0..1000])]
+    fn detect_transients_test(
+        #[case] starts: &[usize],
+        #[case] min_gap: f32,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        let wav = wav_from_points(1000, 1, &clicks(starts, 1000));
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(detect_transients(&wav, 12.0, min_gap), expected);
+    }
+
+    #[rstest]
+    #[case(10, 3, vec![0..3, 3..6, 6..10])]
+    #[case(4, 4, vec![0..1, 1..2, 2..3, 3..4])]
+    #[case(2, 4, vec![0..1, 1..2])]
+    #[case(5, 0, vec![0..5])]
+    #[case(0, 4, vec![])]
+    fn equal_slices_test(
+        #[case] frames: usize,
+        #[case] count: usize,
+        #[case] expected: Vec<Range<usize>>,
+    ) {
+        assert_eq!(equal_slices(frames, count), expected);
+    }
+
+    #[test]
+    fn cut_wav_test() {
+        let wav = wav(&[1, 2, 3, 4]);
+        let wav = Wav::parse(&wav).unwrap();
+        let metadata = WavMetadata {
+            root: Some(60),
+            ..Default::default()
+        };
+
+        let slice = cut_wav(&wav, 1..3, &metadata).unwrap();
+        let slice = Wav::parse(&slice).unwrap();
+
+        assert_eq!(slice.data, &[2, 0, 3, 0]);
+        assert_eq!(slice.metadata.root, Some(60));
+    }
+}
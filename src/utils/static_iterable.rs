@@ -1,4 +1,4 @@
-///! Static Iterable trait
+//! Static Iterable trait
 use std::slice::Iter;
 
 use lazy_static::lazy_static;
@@ -0,0 +1,149 @@
+//! Tempo estimation of the loops.
+
+/// Slowest detected tempo, in BPM.
+const MIN_TEMPO: f32 = 80.0;
+
+/// Fastest detected tempo, in BPM. The range covers a doubling of the tempo.
+const MAX_TEMPO: f32 = 160.0;
+
+/// Duration of each step of the onset envelope, in seconds.
+const HOP: f32 = 0.01;
+
+/// Largest relative difference to snap the tempo to the loop length.
+const SNAP_TOLERANCE: f32 = 0.03;
+
+/// Strength of the note onsets, one value per hop: the rises of energy.
+fn onset_envelope(samples: &[f32], hop: usize) -> Vec<f32> {
+    let energies = samples
+        .chunks(hop)
+        .map(|chunk| chunk.iter().map(|s| s * s).sum::<f32>().sqrt())
+        .collect::<Vec<_>>();
+    energies
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect()
+}
+
+/// Similarity of the envelope with itself, delayed by a number of hops.
+///
+/// The delays of two and four beats are added, so that the rhythm of the
+/// whole loop is taken into account.
+fn beat_similarity(envelope: &[f32], lag: usize) -> f32 {
+    [lag, 2 * lag, 4 * lag]
+        .iter()
+        .filter(|&&lag| lag < envelope.len())
+        .map(|&lag| {
+            let products: f32 = envelope
+                .iter()
+                .zip(&envelope[lag..])
+                .map(|(a, b)| a * b)
+                .sum();
+            products / (envelope.len() - lag) as f32
+        })
+        .sum()
+}
+
+/// Estimate the tempo of a mono loop, in BPM.
+///
+/// The beat period is found from the autocorrelation of the note onsets, in a
+/// range of tempos between 80 and 160 BPM. When the loop holds a whole number
+/// of beats at a close tempo, this more precise tempo is used. None is
+/// returned when the signal is too short or has no clear rhythm.
+pub fn detect_tempo(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let hop = ((HOP * sample_rate as f32) as usize).max(1);
+    let hop_duration = hop as f32 / sample_rate as f32;
+    let envelope = onset_envelope(samples, hop);
+
+    let min_lag = (60.0 / MAX_TEMPO / hop_duration).floor().max(1.0) as usize;
+    let max_lag = (60.0 / MIN_TEMPO / hop_duration).ceil() as usize;
+    if envelope.len() < 2 * max_lag {
+        return None;
+    }
+
+    let similarities = (min_lag - 1..=max_lag + 1)
+        .map(|lag| beat_similarity(&envelope, lag))
+        .collect::<Vec<_>>();
+    let (index, &best) = similarities[1..similarities.len() - 1]
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if best <= 0.0 {
+        return None;
+    }
+
+    // Parabolic interpolation around the best lag, for a finer period
+    let (before, after) = (similarities[index], similarities[index + 2]);
+    let curvature = before - 2.0 * best + after;
+    let shift = if curvature < 0.0 {
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let lag = (index + min_lag) as f32 + shift;
+    let tempo = 60.0 / (lag * hop_duration);
+
+    // Loops usually hold a whole number of beats
+    let duration = samples.len() as f32 / sample_rate as f32;
+    let beats = (tempo * duration / 60.0).round();
+    let loop_tempo = 60.0 * beats / duration;
+    if beats > 0.0 && (loop_tempo - tempo).abs() <= tempo * SNAP_TOLERANCE {
+        Some(loop_tempo)
+    } else {
+        Some(tempo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    /// Short decaying clicks at a tempo, with an accent on the first beat of each bar.
+    fn clicks(tempo: f32, duration: f32, sample_rate: u32) -> Vec<f32> {
+        let frames = (duration * sample_rate as f32) as usize;
+        let beat = 60.0 / tempo * sample_rate as f32;
+        (0..frames)
+            .map(|frame| {
+                let beat_index = (frame as f32 / beat).floor();
+                let since_beat = frame as f32 - beat_index * beat;
+                let level = if (beat_index as usize).is_multiple_of(4) {
+                    1.0
+                } else {
+                    0.5
+                };
+                let decay = (-since_beat / (0.02 * sample_rate as f32)).exp();
+                level * decay * (since_beat * 0.3).sin()
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(120.0, 4.0)]
+    #[case(90.0, 8.0)]
+    #[case(140.0, 60.0 / 140.0 * 16.0)]
+    fn detect_loop_tempo_test(#[case] tempo: f32, #[case] duration: f32) {
+        let samples = clicks(tempo, duration, 8000);
+
+        let detected = detect_tempo(&samples, 8000).unwrap();
+
+        assert!((detected - tempo).abs() < 0.01, "{} != {}", detected, tempo);
+    }
+
+    #[test]
+    fn detect_tempo_without_whole_beats_test() {
+        // 6.7 beats, the tempo can't follow the loop length
+        let samples = clicks(130.0, 3.1, 8000);
+
+        let detected = detect_tempo(&samples, 8000).unwrap();
+
+        assert!((detected - 130.0).abs() < 130.0 * SNAP_TOLERANCE);
+    }
+
+    #[rstest]
+    #[case(vec![0.0; 32000])]
+    #[case(clicks(120.0, 0.5, 8000))]
+    fn detect_no_tempo_test(#[case] samples: Vec<f32>) {
+        assert_eq!(detect_tempo(&samples, 8000), None);
+    }
+}
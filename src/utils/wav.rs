@@ -0,0 +1,334 @@
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{read_u16, read_u32, write_chunk, write_list, Chunk};
+
+/// PCM format tag of the fmt chunk.
+const FORMAT_PCM: u16 = 1;
+
+/// Extensible format tag of the fmt chunk, the actual format is in a sub format.
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Format of the PCM audio of a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WavFormat {
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+
+    /// Number of interleaved channels.
+    pub channels: u16,
+
+    /// Bit depth of each sample.
+    pub bits_per_sample: u16,
+}
+
+impl WavFormat {
+    /// Size in bytes of a frame (one sample of each channel).
+    pub fn block_align(&self) -> usize {
+        self.channels as usize * self.bits_per_sample.div_ceil(8) as usize
+    }
+
+    /// Size in bytes of a frame as written in the WAV header.
+    ///
+    /// None when the frame is empty or too large to be described by a WAV header.
+    pub fn header_block_align(&self) -> Option<u16> {
+        (self.channels as usize)
+            .checked_mul(self.bits_per_sample.div_ceil(8) as usize)
+            .and_then(|block_align| u16::try_from(block_align).ok())
+            .filter(|block_align| *block_align != 0)
+    }
+}
+
+/// Sampler metadata of a WAV file, from its smpl and inst chunks.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WavMetadata {
+    /// MIDI note of the recorded pitch.
+    pub root: Option<u8>,
+
+    /// Correction to apply to play the root note in tune, in cents.
+    pub tune: i8,
+
+    /// Note range where the sample should be played.
+    pub keys: Option<RangeInclusive<u8>>,
+
+    /// Velocity range where the sample should be played.
+    pub velocity: Option<RangeInclusive<u8>>,
+}
+
+impl WavMetadata {
+    /// Read the smpl and inst chunks, when present.
+    ///
+    /// The smpl chunk is preferred for the root note and tuning.
+    fn parse(smpl: Option<&[u8]>, inst: Option<&[u8]>) -> Self {
+        let mut metadata = Self::default();
+
+        if let Some(inst) = inst.filter(|inst| inst.len() >= 7) {
+            metadata.root = Some(inst[0]).filter(|root| *root < 128);
+            metadata.tune = (inst[1] as i8).clamp(-50, 50);
+            metadata.keys = Some(inst[3].min(127)..=inst[4].min(127)).filter(|r| !r.is_empty());
+            metadata.velocity =
+                Some(inst[5].clamp(1, 127)..=inst[6].min(127)).filter(|r| !r.is_empty());
+        }
+
+        if let Some(smpl) = smpl.filter(|smpl| smpl.len() >= 36) {
+            let unity_note = read_u32(smpl, 12);
+            if unity_note < 128 {
+                // The pitch fraction is the part of a semitone above the unity note
+                let cents =
+                    ((read_u32(smpl, 16) as f64) * 100.0 / (u32::MAX as f64 + 1.0)).round() as u8;
+                if cents > 50 && unity_note < 127 {
+                    metadata.root = Some(unity_note as u8 + 1);
+                    metadata.tune = (100 - cents) as i8;
+                } else {
+                    metadata.root = Some(unity_note as u8);
+                    metadata.tune = -(cents.min(50) as i8);
+                }
+            }
+        }
+
+        metadata
+    }
+
+    /// Write the smpl and inst chunks of this metadata.
+    pub(crate) fn write<W: Write>(&self, w: &mut W, sample_rate: u32) -> Result<()> {
+        let Some(root) = self.root else {
+            return Ok(());
+        };
+
+        let mut smpl = Vec::with_capacity(36);
+        smpl.extend_from_slice(&[0; 8]); // Manufacturer and product
+        smpl.extend_from_slice(&(1_000_000_000 / sample_rate.max(1)).to_le_bytes());
+        // The recorded pitch is the root, corrected by the tuning
+        let pitch = root as f64 - self.tune as f64 / 100.0;
+        let unity_note = pitch.floor().max(0.0);
+        let fraction = (pitch - unity_note) * (u32::MAX as f64 + 1.0);
+        smpl.extend_from_slice(&(unity_note as u32).to_le_bytes());
+        smpl.extend_from_slice(&(fraction.round() as u32).to_le_bytes());
+        smpl.extend_from_slice(&[0; 16]); // SMPTE, loops and sampler data
+        write_chunk(w, b"smpl", &smpl)?;
+
+        let keys = self.keys.clone().unwrap_or(0..=127);
+        let velocity = self.velocity.clone().unwrap_or(1..=127);
+        let inst = [
+            root,
+            self.tune as u8,
+            0,
+            *keys.start(),
+            *keys.end(),
+            *velocity.start(),
+            *velocity.end(),
+        ];
+        write_chunk(w, b"inst", &inst)
+    }
+}
+
+/// The PCM audio of a WAV file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wav<'a> {
+    /// Format of the audio data.
+    pub format: WavFormat,
+
+    /// Interleaved little endian PCM data.
+    pub data: &'a [u8],
+
+    /// Sampler metadata.
+    pub metadata: WavMetadata,
+}
+
+impl<'a> Wav<'a> {
+    /// Read the format and audio data of a WAV file.
+    ///
+    /// Only integer PCM data is supported.
+    pub fn parse(wav: &'a [u8]) -> Result<Self> {
+        let riff = Chunk::parse(wav)?;
+        let (riff_type, chunks) = riff.list()?;
+        if &riff.id != b"RIFF" || riff_type != b"WAVE" {
+            bail!("The file is not a WAV file");
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut smpl = None;
+        let mut inst = None;
+        for chunk in chunks {
+            let chunk = chunk?;
+            match &chunk.id {
+                b"fmt " => format = Some(chunk.data),
+                b"data" => data = Some(chunk.data),
+                b"smpl" => smpl = Some(chunk.data),
+                b"inst" => inst = Some(chunk.data),
+                _ => {}
+            }
+        }
+
+        let fmt = format.context("The WAV file does not have a fmt chunk")?;
+        let data = data.context("The WAV file does not have a data chunk")?;
+        if fmt.len() < 16 {
+            bail!("The fmt chunk of the WAV file is truncated");
+        }
+
+        let format_tag = match read_u16(fmt, 0) {
+            FORMAT_EXTENSIBLE if fmt.len() >= 26 => read_u16(fmt, 24),
+            format_tag => format_tag,
+        };
+        if format_tag != FORMAT_PCM {
+            bail!("Compressed and floating point WAV files are not supported");
+        }
+
+        let format = WavFormat {
+            sample_rate: read_u32(fmt, 4),
+            channels: read_u16(fmt, 2),
+            bits_per_sample: read_u16(fmt, 14),
+        };
+        if format.channels == 0
+            || !(1..=32).contains(&format.bits_per_sample)
+            || format.header_block_align().is_none()
+        {
+            bail!("The WAV file format is invalid");
+        }
+
+        Ok(Self {
+            format,
+            data,
+            metadata: WavMetadata::parse(smpl, inst),
+        })
+    }
+
+    /// Number of frames (one sample of each channel) of the audio data.
+    pub fn frames(&self) -> usize {
+        self.data.len() / self.format.block_align()
+    }
+
+    /// Value of a sample point, scaled to the full range of an i32.
+    pub fn point(&self, frame: usize, channel: usize) -> i32 {
+        let bytes = self.format.bits_per_sample.div_ceil(8) as usize;
+        let offset = frame * self.format.block_align() + channel * bytes;
+        let point = &self.data[offset..offset + bytes];
+        if bytes == 1 {
+            // 8 bits WAV files are unsigned
+            return ((point[0] as i32) - 128) << 24;
+        }
+
+        let mut value = [0; 4];
+        value[4 - bytes..].copy_from_slice(point);
+        i32::from_le_bytes(value)
+    }
+
+    /// Mix down the audio data to mono, between -1 and 1.
+    pub fn mono(&self) -> Vec<f32> {
+        let channels = self.format.channels as usize;
+        (0..self.frames())
+            .map(|frame| {
+                let sum: f32 = (0..channels)
+                    .map(|channel| self.point(frame, channel) as f32 / 2_f32.powi(31))
+                    .sum();
+                sum / channels as f32
+            })
+            .collect()
+    }
+}
+
+/// Write interleaved little endian PCM data as a WAV file.
+pub fn write_wav<W: Write>(w: &mut W, format: &WavFormat, data: &[u8]) -> Result<()> {
+    write_wav_with_metadata(w, format, data, &WavMetadata::default())
+}
+
+/// Write interleaved 16 bits points as a WAV file, to build the test samples.
+#[cfg(test)]
+pub(crate) fn wav_from_points(sample_rate: u32, channels: u16, points: &[i16]) -> Vec<u8> {
+    let format = WavFormat {
+        sample_rate,
+        channels,
+        bits_per_sample: 16,
+    };
+    let data = points
+        .iter()
+        .flat_map(|point| point.to_le_bytes())
+        .collect::<Vec<_>>();
+    let mut wav = Vec::new();
+    write_wav(&mut wav, &format, &data).unwrap();
+    wav
+}
+
+/// Write interleaved little endian PCM data as a WAV file, with sampler metadata.
+pub fn write_wav_with_metadata<W: Write>(
+    w: &mut W,
+    format: &WavFormat,
+    data: &[u8],
+    metadata: &WavMetadata,
+) -> Result<()> {
+    let block_align = format
+        .header_block_align()
+        .context("The WAV format is invalid")?;
+    let byte_rate = format
+        .sample_rate
+        .checked_mul(block_align as u32)
+        .context("The WAV sample rate is too high")?;
+    let mut fmt = Vec::with_capacity(16);
+    fmt.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+    fmt.extend_from_slice(&format.channels.to_le_bytes());
+    fmt.extend_from_slice(&format.sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&byte_rate.to_le_bytes());
+    fmt.extend_from_slice(&block_align.to_le_bytes());
+    fmt.extend_from_slice(&format.bits_per_sample.to_le_bytes());
+
+    let mut chunks = Vec::with_capacity(data.len() + 32);
+    write_chunk(&mut chunks, b"fmt ", &fmt)?;
+    write_chunk(&mut chunks, b"data", data)?;
+    metadata.write(&mut chunks, format.sample_rate)?;
+    write_list(w, b"RIFF", b"WAVE", &chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    pub use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(WavMetadata::default())]
+    #[case(WavMetadata { root: Some(60), tune: 0, keys: Some(55..=65), velocity: Some(1..=64) })]
+    #[case(WavMetadata { root: Some(61), tune: 30, keys: Some(0..=127), velocity: Some(1..=127) })]
+    #[case(WavMetadata { root: Some(45), tune: -20, keys: Some(40..=50), velocity: Some(65..=127) })]
+    fn wav_metadata_round_trip_test(#[case] metadata: WavMetadata) {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 1,
+            bits_per_sample: 16,
+        };
+        let mut chunks = Vec::new();
+        metadata.write(&mut chunks, format.sample_rate).unwrap();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &[0, 0]).unwrap();
+        // Append the metadata chunks to the RIFF chunk
+        wav.extend_from_slice(&chunks);
+        let riff_size = (wav.len() - 8) as u32;
+        wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(wav.metadata, metadata);
+    }
+
+    #[test]
+    fn wav_round_trip_test() {
+        let format = WavFormat {
+            sample_rate: 44100,
+            channels: 2,
+            bits_per_sample: 24,
+        };
+        let pcm = [1, 2, 3, 4, 5, 6, 7, 8, 0xFF, 0xFF, 0xFF, 0xFF];
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &format, &pcm).unwrap();
+
+        let wav = Wav::parse(&wav).unwrap();
+
+        assert_eq!(wav.format, format);
+        assert_eq!(wav.data, &pcm);
+        assert_eq!(wav.frames(), 2);
+        assert_eq!(wav.point(0, 1), 0x060504 << 8);
+        assert_eq!(wav.point(1, 1), -1 << 8);
+    }
+}